@@ -0,0 +1,127 @@
+use anyhow::Result;
+
+use crate::failure::Failure;
+use crate::Action;
+
+const BIN: &str = "screen-recorder";
+/// Options every action accepts.
+const GLOBAL_FLAGS: &[&str] = &["--json-events"];
+
+/// Completion script for `shell`, generated from the action table.
+pub(crate) fn script(shell: &str, actions: &[Action]) -> Result<String> {
+    match shell {
+        "bash" => Ok(bash(actions)),
+        "zsh" => Ok(zsh(actions)),
+        "fish" => Ok(fish(actions)),
+        other => Err(Failure::Usage(format!(
+            "unsupported shell {} (use bash, zsh or fish)",
+            other
+        ))
+        .into()),
+    }
+}
+
+fn words(action: &Action) -> String {
+    action
+        .flags
+        .iter()
+        .chain(GLOBAL_FLAGS)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn names(actions: &[Action]) -> String {
+    actions
+        .iter()
+        .map(|action| action.name)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn function_name() -> String {
+    format!("_{}", BIN.replace('-', "_"))
+}
+
+fn bash(actions: &[Action]) -> String {
+    let mut cases = String::new();
+    for action in actions {
+        cases.push_str(&format!(
+            "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;\n",
+            action.name,
+            words(action)
+        ));
+    }
+    format!(
+        r#"{function}() {{
+    local cur=${{COMP_WORDS[COMP_CWORD]}}
+    if [ "$COMP_CWORD" -eq 1 ]; then
+        COMPREPLY=($(compgen -W "{names}" -- "$cur"))
+        return
+    fi
+    case "${{COMP_WORDS[1]}}" in
+{cases}    esac
+}}
+complete -o default -F {function} {bin}
+"#,
+        function = function_name(),
+        names = names(actions),
+        cases = cases,
+        bin = BIN,
+    )
+}
+
+fn zsh(actions: &[Action]) -> String {
+    let mut cases = String::new();
+    for action in actions {
+        cases.push_str(&format!(
+            "        {}) compadd -- {} ;;\n",
+            action.name,
+            words(action)
+        ));
+    }
+    format!(
+        r#"#compdef {bin}
+
+{function}() {{
+    if (( CURRENT == 2 )); then
+        compadd -- {names}
+        return
+    fi
+    case $words[2] in
+{cases}    esac
+    _files
+}}
+
+{function} "$@"
+"#,
+        bin = BIN,
+        function = function_name(),
+        names = names(actions),
+        cases = cases,
+    )
+}
+
+fn fish(actions: &[Action]) -> String {
+    let mut script = format!(
+        "complete -c {} -f -n '__fish_use_subcommand' -a '{}'\n",
+        BIN,
+        names(actions)
+    );
+    for action in actions {
+        for flag in action.flags.iter().chain(GLOBAL_FLAGS) {
+            let spec = if let Some(long) = flag.strip_prefix("--") {
+                format!("-l {}", long)
+            } else if let Some(short) = flag.strip_prefix('-') {
+                format!("-s {}", short)
+            } else {
+                format!("-f -a {}", flag)
+            };
+            script.push_str(&format!(
+                "complete -c {} -n '__fish_seen_subcommand_from {}' {}\n",
+                BIN, action.name, spec
+            ));
+        }
+    }
+    script
+}
@@ -0,0 +1,137 @@
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+
+/// Whether `path` names an ISO base media file (MP4/MOV), whose index is
+/// written last unless fragmented.
+pub(crate) fn is_mp4_family(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("mp4") || ext.eq_ignore_ascii_case("mov"))
+}
+
+/// Whether `path` is stdout (`-`) or a named pipe. Such targets are written
+/// once, front to back, and cannot be joined or post-processed afterwards.
+pub(crate) fn is_stream(path: &Path) -> bool {
+    path == Path::new("-") || is_fifo(path)
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_path: &Path) -> bool {
+    false
+}
+
+/// `video.format` value that records numbered PNG frames into a directory.
+pub(crate) const IMAGE_SEQUENCE: &str = "png-seq";
+
+/// Whether `format` writes a directory of frames rather than a video file.
+pub(crate) fn is_image_sequence(format: &str) -> bool {
+    format == IMAGE_SEQUENCE
+}
+
+/// `video.format` value that records to MKV and picks the final container
+/// once the recording is done.
+pub(crate) const AUTO: &str = "auto";
+
+/// Container a `video.format` value captures into.
+pub(crate) fn recording_format(format: &str) -> &str {
+    match format {
+        AUTO => "mkv",
+        other => other,
+    }
+}
+
+/// Longest side, in pixels, of a clip `auto` still turns into a GIF.
+const AUTO_GIF_MAX_WIDTH: i32 = 1280;
+
+/// Container an `auto` recording is finally written as: a GIF for short,
+/// silent, small clips, otherwise the first of MP4 and WebM that holds its
+/// streams without re-encoding, else the MKV it was captured in.
+pub(crate) fn auto_container(
+    secs: i64,
+    width: i32,
+    video_codec: &str,
+    audio_codec: Option<&str>,
+    gif_max_secs: u32,
+) -> &'static str {
+    if audio_codec.is_none()
+        && secs <= i64::from(gif_max_secs)
+        && gif_max_secs > 0
+        && width <= AUTO_GIF_MAX_WIDTH
+    {
+        return "gif";
+    }
+    ["mp4", "webm"]
+        .into_iter()
+        .find(|container| check_codecs(container, video_codec, audio_codec).is_ok())
+        .unwrap_or("mkv")
+}
+
+/// ffmpeg muxer for a `video.format` value, needed when the output path has
+/// no extension to infer it from.
+#[allow(dead_code, reason = "only the linux platform impl runs the encoder")]
+pub(crate) fn muxer(format: &str) -> &str {
+    match recording_format(format) {
+        "mkv" => "matroska",
+        other => other,
+    }
+}
+
+/// Container a recording ends up in: the extension of an explicit output
+/// file, else the configured `video.format`.
+pub(crate) fn target_container(output: Option<&Path>, format: &str) -> String {
+    output
+        .filter(|path| !is_stream(path))
+        .and_then(|path| path.extension())
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_else(|| recording_format(format).to_string())
+}
+
+/// Rejects codec choices `container` cannot hold, so a bad config fails
+/// before region selection instead of halfway into ffmpeg.
+pub(crate) fn check_codecs(
+    container: &str,
+    video_codec: &str,
+    audio_codec: Option<&str>,
+) -> Result<()> {
+    let (videos, audios): (&[&str], &[&str]) = match container {
+        "webm" => (
+            &["libvpx", "libvpx-vp9", "libsvtav1", "libaom-av1"],
+            &["libopus", "libvorbis"],
+        ),
+        "mp4" => (
+            &["libx264", "libx265", "libsvtav1", "libaom-av1"],
+            &["aac", "libmp3lame", "libopus"],
+        ),
+        "mov" => (
+            &["libx264", "libx265", "prores_ks"],
+            &["aac", "alac", "pcm_s16le", "pcm_s24le"],
+        ),
+        // Matroska and anything ffmpeg infers from an unknown extension.
+        _ => return Ok(()),
+    };
+    if !videos.contains(&video_codec) {
+        return Err(anyhow!(
+            "{} cannot hold {} video; use one of {} or record to mkv",
+            container,
+            video_codec,
+            videos.join(", ")
+        ));
+    }
+    if let Some(audio_codec) = audio_codec.filter(|codec| !audios.contains(codec)) {
+        return Err(anyhow!(
+            "{} cannot hold {} audio; use one of {}, record to mkv or disable audio",
+            container,
+            audio_codec,
+            audios.join(", ")
+        ));
+    }
+    Ok(())
+}
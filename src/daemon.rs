@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::fd::FromRawFd;
+use std::os::unix::net::{UnixDatagram, UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::process::{self, Command};
+use std::thread;
+use std::time::Duration;
+
+use crate::recorder;
+use crate::remote;
+use crate::signals;
+use crate::state;
+use crate::tray;
+
+const SOCKET_NAME: &str = "screen-recorder.sock";
+/// First file descriptor systemd hands over under socket activation.
+const SD_LISTEN_FDS_START: i32 = 3;
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Reply to one control socket request.
+#[derive(Debug, Serialize)]
+pub(crate) struct Reply {
+    pub code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Control socket path: `$XDG_RUNTIME_DIR/screen-recorder.sock`, or one in
+/// the state directory when no runtime directory is set.
+pub(crate) fn socket_path() -> PathBuf {
+    match env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join(SOCKET_NAME),
+        _ => PathBuf::from(state::STATE_DIR).join(SOCKET_NAME),
+    }
+}
+
+/// Serves the control socket until SIGTERM, SIGINT or SIGHUP, then finalizes every
+/// active recording before exiting. Each request is one line holding an
+/// action and its arguments, e.g. `stop --all`; the reply is one JSON line
+/// with the action's exit code and output.
+pub(crate) fn run() -> Result<()> {
+    signals::install();
+    let (listener, owned_path) = match activated_listener() {
+        Some(listener) => (listener, None),
+        None => {
+            let path = socket_path();
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).context("failed to create socket directory")?;
+            }
+            let _ = fs::remove_file(&path);
+            let listener = UnixListener::bind(&path)
+                .with_context(|| format!("failed to bind {}", path.display()))?;
+            (listener, Some(path))
+        }
+    };
+    listener
+        .set_nonblocking(true)
+        .context("failed to configure control socket")?;
+    let config = crate::load_config();
+    if config.remote.enabled {
+        remote::spawn(&config.remote)?;
+    }
+    sd_notify("READY=1");
+
+    while !signals::requested() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                thread::spawn(move || {
+                    let _ = serve(stream);
+                });
+            }
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_INTERVAL)
+            }
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error).context("control socket failed"),
+        }
+    }
+
+    sd_notify("STOPPING=1");
+    let config = crate::load_config();
+    for recording in recorder::active_recordings() {
+        if let Err(error) = recorder::stop(&recording, &config) {
+            eprintln!("{:#}", error);
+        }
+    }
+    tray::publish_status(&recorder::status_report());
+    if let Some(path) = owned_path {
+        let _ = fs::remove_file(path);
+    }
+    Ok(())
+}
+
+fn activated_listener() -> Option<UnixListener> {
+    let pid: u32 = env::var("LISTEN_PID").ok()?.parse().ok()?;
+    let fds: u32 = env::var("LISTEN_FDS").ok()?.parse().ok()?;
+    if pid != process::id() || fds == 0 {
+        return None;
+    }
+    // SAFETY: systemd passes the activated socket as the first inherited
+    // descriptor, and nothing else in this process has claimed it.
+    Some(unsafe { UnixListener::from_raw_fd(SD_LISTEN_FDS_START) })
+}
+
+/// Runs one request as a child invocation, so a failing or blocking action
+/// cannot take the daemon down with it.
+fn serve(stream: UnixStream) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let args: Vec<&str> = line.split_whitespace().collect();
+    let reply = match args.first() {
+        None | Some(&"daemon") | Some(&"--daemon") => Reply {
+            code: 2,
+            stdout: String::new(),
+            stderr: "expected an action".to_string(),
+        },
+        Some(_) => run_action(&args)?,
+    };
+    let json = serde_json::to_string(&reply)?;
+    writeln!(&stream, "{}", json)?;
+    Ok(())
+}
+
+/// Runs one action as a child invocation and collects its output.
+pub(crate) fn run_action(args: &[&str]) -> Result<Reply> {
+    let output = Command::new(env::current_exe()?).args(args).output()?;
+    Ok(Reply {
+        code: output.status.code().unwrap_or(1),
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+    })
+}
+
+/// Reports service state to systemd when started as a `Type=notify` unit.
+fn sd_notify(message: &str) {
+    let Ok(target) = env::var("NOTIFY_SOCKET") else {
+        return;
+    };
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return;
+    };
+    if let Some(name) = target.strip_prefix('@') {
+        send_abstract(&socket, name, message);
+    } else {
+        let _ = socket.send_to(message.as_bytes(), &target);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn send_abstract(socket: &UnixDatagram, name: &str, message: &str) {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+    if let Ok(address) = SocketAddr::from_abstract_name(name.as_bytes()) {
+        let _ = socket.send_to_addr(message.as_bytes(), &address);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send_abstract(_socket: &UnixDatagram, _name: &str, _message: &str) {
+    // Abstract socket names only exist on Linux.
+}
@@ -0,0 +1,77 @@
+use chrono::Local;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::Rect;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Lifecycle events printed as JSON lines under `--json-events`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub(crate) enum Event {
+    Selected {
+        rect: Rect,
+    },
+    Started {
+        id: u32,
+        output: PathBuf,
+    },
+    Progress {
+        id: u32,
+        elapsed_secs: i64,
+        paused: bool,
+    },
+    Stopped {
+        id: u32,
+        output: PathBuf,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    /// Unix timestamp in milliseconds.
+    at: i64,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+pub(crate) fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Moves events off stdout, which is carrying the encoded video.
+pub(crate) fn divert_to_stderr() {
+    TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn emit(event: Event) {
+    if !enabled() {
+        return;
+    }
+    let line = Line {
+        at: Local::now().timestamp_millis(),
+        event: &event,
+    };
+    let Ok(json) = serde_json::to_string(&line) else {
+        return;
+    };
+    // A closed pipe on the consumer side must not take the recorder down.
+    if TO_STDERR.load(Ordering::Relaxed) {
+        let _ = writeln!(io::stderr(), "{}", json);
+    } else {
+        let mut stdout = io::stdout();
+        let _ = writeln!(stdout, "{}", json);
+        let _ = stdout.flush();
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Failures scripts and the tray host may want to tell apart. Each maps to
+/// its own exit code; every other error exits with 1.
+#[derive(Debug)]
+pub(crate) enum Failure {
+    /// Unknown action or malformed arguments.
+    Usage(String),
+    /// The region selection was dismissed.
+    SelectionCancelled,
+    /// Nothing was selected within `selection.timeout_secs`.
+    #[allow(
+        dead_code,
+        reason = "only the linux platform impl times out selections"
+    )]
+    SelectionTimedOut,
+    /// stop, pause or mark found no recording to act on.
+    NotRecording,
+    /// A required external program is not installed.
+    MissingTool(&'static str),
+    /// The encoder died right after starting; its log has the details.
+    EncoderFailed(PathBuf),
+    /// The output filesystem ran out of space.
+    DiskFull,
+    /// The finished file failed verification.
+    Corrupt(String),
+    /// The display, audio server or a directory the recording needs cannot
+    /// be accessed by this user.
+    Unavailable(String),
+    /// Another recording is already writing to the requested output.
+    AlreadyRecording(PathBuf),
+}
+
+impl Failure {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Usage(_) => 2,
+            Self::SelectionCancelled => 3,
+            Self::NotRecording => 4,
+            Self::MissingTool(_) => 5,
+            Self::EncoderFailed(_) => 6,
+            Self::DiskFull => 7,
+            Self::Corrupt(_) => 8,
+            Self::Unavailable(_) => 9,
+            Self::AlreadyRecording(_) => 10,
+            Self::SelectionTimedOut => 11,
+        }
+    }
+
+    /// Title and body of the notification shown when an action ends with
+    /// this failure, or `None` when there is nothing worth telling the user.
+    pub fn notification(&self) -> Option<(&'static str, String)> {
+        match self {
+            Self::Usage(_) | Self::SelectionCancelled => None,
+            Self::SelectionTimedOut => {
+                Some(("Selection timed out", "Nothing was recorded".to_string()))
+            }
+            Self::NotRecording => Some(("Not recording", "Nothing to act on".to_string())),
+            Self::MissingTool(tool) => Some(("Missing dependency", format!("Install {}", tool))),
+            Self::EncoderFailed(log) => {
+                Some(("Recording failed", format!("Check {}", log.display())))
+            }
+            Self::DiskFull => Some(("Recording failed", "No space left on device".to_string())),
+            Self::Corrupt(problem) => Some(("Recording may be corrupt", problem.clone())),
+            Self::Unavailable(problem) => Some(("Cannot record", problem.clone())),
+            Self::AlreadyRecording(path) => Some((
+                "Already recording",
+                format!("{} is being written", path.display()),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(message) => f.write_str(message),
+            Self::SelectionCancelled => f.write_str("selection cancelled"),
+            Self::SelectionTimedOut => f.write_str("selection timed out"),
+            Self::NotRecording => f.write_str("no recording in progress"),
+            Self::MissingTool(tool) => write!(f, "{} is not installed", tool),
+            Self::EncoderFailed(log) => {
+                write!(
+                    f,
+                    "capture process exited immediately; see {}",
+                    log.display()
+                )
+            }
+            Self::DiskFull => f.write_str("no space left on the output device"),
+            Self::Corrupt(problem) => write!(f, "recording failed verification: {}", problem),
+            Self::Unavailable(problem) => f.write_str(problem),
+            Self::AlreadyRecording(path) => {
+                write!(f, "a recording is already writing {}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Exit code for an error returned by an action.
+pub(crate) fn exit_code(error: &anyhow::Error) -> u8 {
+    error
+        .downcast_ref::<Failure>()
+        .map_or(1, Failure::exit_code)
+}
+
+/// Error for a tool that could not be launched, telling a missing install
+/// apart from other spawn failures.
+pub(crate) fn spawn_error(tool: &'static str, error: io::Error) -> anyhow::Error {
+    if error.kind() == io::ErrorKind::NotFound {
+        return Failure::MissingTool(tool).into();
+    }
+    anyhow::Error::new(error).context(format!("failed to run {}", tool))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{exit_code, spawn_error, Failure};
+    use std::io;
+    use std::path::PathBuf;
+
+    #[test]
+    fn missing_program_is_a_missing_tool() {
+        let error = spawn_error("slop", io::Error::from(io::ErrorKind::NotFound));
+        assert!(matches!(
+            error.downcast_ref::<Failure>(),
+            Some(Failure::MissingTool("slop"))
+        ));
+        assert_eq!(exit_code(&error), 5);
+
+        let error = spawn_error("slop", io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(error.downcast_ref::<Failure>().is_none());
+        assert_eq!(exit_code(&error), 1);
+    }
+
+    #[test]
+    fn failures_keep_their_kind_through_context() {
+        let error = anyhow::Error::from(Failure::DiskFull).context("while stopping");
+        assert!(matches!(
+            error.downcast_ref::<Failure>(),
+            Some(Failure::DiskFull)
+        ));
+        assert_eq!(exit_code(&error), 7);
+    }
+
+    #[test]
+    fn exit_codes_are_distinct() {
+        let failures = [
+            Failure::Usage(String::new()),
+            Failure::SelectionCancelled,
+            Failure::SelectionTimedOut,
+            Failure::NotRecording,
+            Failure::MissingTool("ffmpeg"),
+            Failure::EncoderFailed(PathBuf::new()),
+            Failure::DiskFull,
+            Failure::Corrupt(String::new()),
+            Failure::Unavailable(String::new()),
+            Failure::AlreadyRecording(PathBuf::new()),
+        ];
+        let mut codes: Vec<u8> = failures.iter().map(Failure::exit_code).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), failures.len());
+        assert!(!codes.contains(&1));
+    }
+
+    #[test]
+    fn cancelling_is_silent() {
+        assert!(Failure::SelectionCancelled.notification().is_none());
+        assert!(Failure::Usage("bad".to_string()).notification().is_none());
+        assert_eq!(
+            Failure::MissingTool("slop").notification(),
+            Some(("Missing dependency", "Install slop".to_string()))
+        );
+    }
+}
@@ -0,0 +1,169 @@
+/// One ffmpeg input: its demuxer, the options that go before it and the
+/// source it reads.
+#[derive(Debug, Clone)]
+struct Input {
+    options: Vec<String>,
+    format: String,
+    source: String,
+}
+
+/// An ffmpeg command line built up piece by piece and rendered in the order
+/// ffmpeg expects: inputs, the complex filter graph and stream maps, the
+/// video filter chain, output options and finally the output itself.
+#[derive(Debug, Clone, Default)]
+#[allow(dead_code, reason = "only the linux platform impl builds captures")]
+pub(crate) struct FfmpegCommand {
+    inputs: Vec<Input>,
+    filter_complex: Option<String>,
+    maps: Vec<String>,
+    video_filters: Vec<String>,
+    output_options: Vec<String>,
+    output: Option<String>,
+}
+
+#[allow(dead_code, reason = "only the linux platform impl builds captures")]
+impl FfmpegCommand {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an input read with the `format` demuxer, `options` (flag and
+    /// value pairs) placed in front of it.
+    pub fn input(
+        &mut self,
+        format: &str,
+        source: impl Into<String>,
+        options: &[(&str, String)],
+    ) -> &mut Self {
+        self.inputs.push(Input {
+            options: options
+                .iter()
+                .flat_map(|(flag, value)| [flag.to_string(), value.clone()])
+                .collect(),
+            format: format.to_string(),
+            source: source.into(),
+        });
+        self
+    }
+
+    pub fn filter_complex(&mut self, graph: impl Into<String>) -> &mut Self {
+        self.filter_complex = Some(graph.into());
+        self
+    }
+
+    /// Selects a stream for the output, such as `0:v` or a graph label.
+    pub fn map(&mut self, stream: impl Into<String>) -> &mut Self {
+        self.maps.push(stream.into());
+        self
+    }
+
+    /// Appends a filter to the `-vf` chain.
+    pub fn video_filter(&mut self, filter: impl Into<String>) -> &mut Self {
+        self.video_filters.push(filter.into());
+        self
+    }
+
+    pub fn video_filters(&mut self, filters: impl IntoIterator<Item = String>) -> &mut Self {
+        self.video_filters.extend(filters);
+        self
+    }
+
+    /// Appends output options: codecs, rates, muxer flags.
+    pub fn output_options<S: Into<String>>(
+        &mut self,
+        options: impl IntoIterator<Item = S>,
+    ) -> &mut Self {
+        self.output_options
+            .extend(options.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn output(&mut self, output: impl Into<String>) -> &mut Self {
+        self.output = Some(output.into());
+        self
+    }
+
+    /// The argv to pass to ffmpeg, without the program name.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for input in &self.inputs {
+            args.extend(input.options.iter().cloned());
+            args.extend(["-f".to_string(), input.format.clone()]);
+            args.extend(["-i".to_string(), input.source.clone()]);
+        }
+        if let Some(graph) = &self.filter_complex {
+            args.extend(["-filter_complex".to_string(), graph.clone()]);
+        }
+        for stream in &self.maps {
+            args.extend(["-map".to_string(), stream.clone()]);
+        }
+        if !self.video_filters.is_empty() {
+            args.extend(["-vf".to_string(), self.video_filters.join(",")]);
+        }
+        args.extend(self.output_options.iter().cloned());
+        args.extend(self.output.clone());
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FfmpegCommand;
+
+    #[test]
+    fn renders_inputs_filters_and_output_in_order() {
+        let mut command = FfmpegCommand::new();
+        command
+            .output("out.mkv")
+            .output_options(["-c:v", "libx264"])
+            .video_filter("scale=iw*2:ih*2")
+            .video_filter("transpose=1")
+            .map("0:v")
+            .map("[aout]")
+            .filter_complex("[1:a][2:a]amerge=inputs=2[aout]")
+            .input(
+                "x11grab",
+                ":0+10,20",
+                &[("-video_size", "640x480".to_string())],
+            )
+            .input("pulse", "mic", &[])
+            .input("pulse", "speakers.monitor", &[]);
+        assert_eq!(
+            command.to_args(),
+            [
+                "-video_size",
+                "640x480",
+                "-f",
+                "x11grab",
+                "-i",
+                ":0+10,20",
+                "-f",
+                "pulse",
+                "-i",
+                "mic",
+                "-f",
+                "pulse",
+                "-i",
+                "speakers.monitor",
+                "-filter_complex",
+                "[1:a][2:a]amerge=inputs=2[aout]",
+                "-map",
+                "0:v",
+                "-map",
+                "[aout]",
+                "-vf",
+                "scale=iw*2:ih*2,transpose=1",
+                "-c:v",
+                "libx264",
+                "out.mkv",
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_out_empty_parts() {
+        let mut command = FfmpegCommand::new();
+        command.input("lavfi", "testsrc", &[]).output("-");
+        assert_eq!(command.to_args(), ["-f", "lavfi", "-i", "testsrc", "-"]);
+    }
+}
@@ -0,0 +1,114 @@
+//! Turns `output.name_template` into a path below the output directory.
+//! Placeholder values come from the environment (monitor names, the clock)
+//! and are cleaned before use; the template itself may add subdirectories
+//! but never climb out of the output directory.
+
+use anyhow::Result;
+use std::path::{Component, Path, PathBuf};
+
+use crate::failure::Failure;
+
+/// Renders `template`, replacing each `{key}` with its value from `fields`.
+/// The result is relative and free of `..`, so joining it onto the output
+/// directory stays inside it.
+pub(crate) fn render(template: &str, fields: &[(&str, &str)]) -> Result<PathBuf> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        rendered.push_str(&rest[..open]);
+        let close = rest[open..]
+            .find('}')
+            .ok_or_else(|| invalid(template, "has an unclosed {"))?;
+        let key = &rest[open + 1..open + close];
+        let value = fields
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| *value)
+            .ok_or_else(|| invalid(template, &format!("has an unknown placeholder {{{}}}", key)))?;
+        rendered.push_str(&sanitize(value));
+        rest = &rest[open + close + 1..];
+    }
+    rendered.push_str(rest);
+    check_relative(template, &rendered)
+}
+
+/// `value` made safe to use as part of a single file name: separators and
+/// control characters become `_`, and it cannot start with a dot.
+pub(crate) fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c == '/' || c == '\\' || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect::<String>()
+        .trim_start_matches('.')
+        .to_string()
+}
+
+fn check_relative(template: &str, rendered: &str) -> Result<PathBuf> {
+    let path = Path::new(rendered);
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => clean.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                return Err(invalid(template, "leaves the output directory with .."))
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(invalid(
+                    template,
+                    "must be relative to the output directory",
+                ))
+            }
+        }
+    }
+    if clean.file_name().is_none() || rendered.ends_with('/') {
+        return Err(invalid(template, "does not end in a file name"));
+    }
+    Ok(clean)
+}
+
+fn invalid(template: &str, problem: &str) -> anyhow::Error {
+    Failure::Usage(format!("output.name_template \"{}\" {}", template, problem)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, sanitize};
+    use std::path::PathBuf;
+
+    #[test]
+    fn placeholders_are_filled_and_cleaned() {
+        let fields = [("date", "2026-10-16"), ("monitor", "../DP-1")];
+        assert_eq!(
+            render("{date}/recording-{monitor}", &fields).unwrap(),
+            PathBuf::from("2026-10-16/recording-_DP-1")
+        );
+        assert_eq!(sanitize("..hidden\nname"), "hidden_name");
+    }
+
+    #[test]
+    fn templates_cannot_leave_the_output_directory() {
+        for template in [
+            "../escape",
+            "a/../../b",
+            "/etc/passwd",
+            "clips/",
+            "",
+            "{nope}",
+            "{date",
+        ] {
+            assert!(
+                render(template, &[("date", "today")]).is_err(),
+                "{} was accepted",
+                template
+            );
+        }
+        assert_eq!(render("./a/./b", &[]).unwrap(), PathBuf::from("a/b"));
+    }
+}
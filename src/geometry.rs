@@ -0,0 +1,488 @@
+//! Pure rectangle and monitor math behind turning a raw selection into the
+//! area that gets recorded.
+
+use serde::{Deserialize, Serialize};
+
+/// A selection ending this close above the bottom of its monitor is
+/// stretched down to it, since a drag rarely lands on the last pixel.
+const SNAP_MARGIN_PX: i32 = 50;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Monitor {
+    /// Output name such as `DP-1`, when the platform reports one.
+    pub name: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+    /// Refresh rate of the active mode, when the platform reports one.
+    pub refresh_hz: Option<f64>,
+    /// xrandr rotation. Reflection mirrors the picture in place without
+    /// moving the monitor's edges, so it needs no modelling here.
+    pub rotation: Rotation,
+    /// Unrotated size of the active mode, when the platform reports one.
+    pub mode_size: Option<(i32, i32)>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Rotation {
+    #[default]
+    Normal,
+    Left,
+    Inverted,
+    Right,
+}
+
+impl Rotation {
+    #[allow(dead_code, reason = "only the linux platform impl reads rotations")]
+    pub fn parse(word: &str) -> Option<Self> {
+        match word {
+            "normal" => Some(Self::Normal),
+            "left" => Some(Self::Left),
+            "inverted" => Some(Self::Inverted),
+            "right" => Some(Self::Right),
+            _ => None,
+        }
+    }
+}
+
+impl Monitor {
+    /// The monitor's area in screen coordinates. A monitor turned a quarter
+    /// reported with its unrotated mode size has width and height swapped
+    /// to match what it covers on screen.
+    pub fn bounds(&self) -> Rect {
+        let quarter_turn = matches!(self.rotation, Rotation::Left | Rotation::Right);
+        let (w, h) = if quarter_turn && self.mode_size == Some((self.w, self.h)) && self.w != self.h
+        {
+            (self.h, self.w)
+        } else {
+            (self.w, self.h)
+        };
+        Rect {
+            x: self.x,
+            y: self.y,
+            w,
+            h,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+/// Parses an X geometry string such as `1920x1080+0+0`.
+pub(crate) fn parse_x_geometry(token: &str) -> Option<Monitor> {
+    let x_split = token.find('x')?;
+    let width = token[..x_split].parse::<i32>().ok()?;
+    let after_x = &token[x_split + 1..];
+    let first_sign = after_x.find(['+', '-'])?;
+    let height = after_x[..first_sign].parse::<i32>().ok()?;
+    let after_height = &after_x[first_sign..];
+    let second_sign = after_height[1..].find(['+', '-'])? + 1;
+    let x = after_height[..second_sign].parse::<i32>().ok()?;
+    let y = after_height[second_sign..].parse::<i32>().ok()?;
+    Some(Monitor {
+        name: None,
+        x,
+        y,
+        w: width,
+        h: height,
+        refresh_hz: None,
+        rotation: Rotation::Normal,
+        mode_size: None,
+    })
+}
+
+/// Splits `OUTPUT:X,Y,W,H` into the output name and the region on it.
+pub(crate) fn parse_relative_geometry(spec: &str) -> Option<(&str, Rect)> {
+    let (output, numbers) = spec.rsplit_once(':')?;
+    let values: Vec<i32> = numbers
+        .split(',')
+        .map(|value| value.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    let [x, y, w, h] = values[..] else {
+        return None;
+    };
+    (!output.is_empty() && w > 0 && h > 0).then_some((output, Rect { x, y, w, h }))
+}
+
+/// Monitor the selection belongs to: the one holding its centre. Where
+/// monitors overlap, as with mirrored outputs, the one covering most of the
+/// selection wins and the first listed breaks ties.
+pub(crate) fn monitor_for(monitors: &[Monitor], rect: Rect) -> Option<&Monitor> {
+    let center_x = rect.x + rect.w / 2;
+    let center_y = rect.y + rect.h / 2;
+    monitors
+        .iter()
+        .filter(|monitor| contains(&monitor.bounds(), center_x, center_y))
+        .fold(None, |best: Option<(&Monitor, i64)>, monitor| {
+            let overlap = overlap_area(&monitor.bounds(), &rect);
+            match best {
+                Some((_, best_overlap)) if best_overlap >= overlap => best,
+                _ => Some((monitor, overlap)),
+            }
+        })
+        .map(|(monitor, _)| monitor)
+}
+
+/// What happened to the monitors under `rect` between two layouts, when
+/// any of them went away or moved or changed size. Monitors are matched by
+/// output name, or by position when the platform reports no names.
+pub(crate) fn layout_change(before: &[Monitor], after: &[Monitor], rect: Rect) -> Option<String> {
+    before
+        .iter()
+        .filter(|monitor| overlap_area(&monitor.bounds(), &rect) > 0)
+        .find_map(|monitor| {
+            let label = monitor.name.as_deref().unwrap_or("The recorded monitor");
+            let now = after.iter().find(|candidate| match &monitor.name {
+                Some(_) => candidate.name == monitor.name,
+                None => candidate.bounds() == monitor.bounds(),
+            });
+            match now.map(Monitor::bounds) {
+                None => Some(format!("{} was disconnected", label)),
+                Some(bounds) if bounds != monitor.bounds() => Some(format!(
+                    "{} is now {}x{}+{}+{}",
+                    label, bounds.w, bounds.h, bounds.x, bounds.y
+                )),
+                Some(_) => None,
+            }
+        })
+}
+
+fn contains(bounds: &Rect, x: i32, y: i32) -> bool {
+    x >= bounds.x && x < bounds.x + bounds.w && y >= bounds.y && y < bounds.y + bounds.h
+}
+
+fn overlap_area(a: &Rect, b: &Rect) -> i64 {
+    let w = (a.x + a.w).min(b.x + b.w) - a.x.max(b.x);
+    let h = (a.y + a.h).min(b.y + b.h) - a.y.max(b.y);
+    i64::from(w.max(0)) * i64::from(h.max(0))
+}
+
+/// Cuts `rect` down to the part inside `bounds`. A rect entirely outside
+/// comes back with a zero size.
+pub(crate) fn clamp_to_bounds(mut rect: Rect, bounds: &Rect) -> Rect {
+    if rect.x < bounds.x {
+        rect.w -= bounds.x - rect.x;
+        rect.x = bounds.x;
+    }
+    if rect.y < bounds.y {
+        rect.h -= bounds.y - rect.y;
+        rect.y = bounds.y;
+    }
+    if rect.x + rect.w > bounds.x + bounds.w {
+        rect.w = bounds.x + bounds.w - rect.x;
+    }
+    if rect.y + rect.h > bounds.y + bounds.h {
+        rect.h = bounds.y + bounds.h - rect.y;
+    }
+    rect.w = rect.w.max(0);
+    rect.h = rect.h.max(0);
+    rect
+}
+
+/// `rect` stretched to the bottom of `bounds` when it ends within
+/// [`SNAP_MARGIN_PX`] above it; `None` when it already reaches the bottom or
+/// stops well short of it.
+pub(crate) fn snap_to_bottom(rect: Rect, bounds: &Rect) -> Option<Rect> {
+    let bottom = bounds.y + bounds.h;
+    let gap = bottom - (rect.y + rect.h);
+    (gap > 0 && gap <= SNAP_MARGIN_PX).then_some(Rect {
+        h: bottom - rect.y,
+        ..rect
+    })
+}
+
+/// `rect` with width and height rounded down to even numbers, which
+/// 4:2:0 encoders require.
+pub(crate) fn trim_to_even(rect: Rect) -> Rect {
+    Rect {
+        w: rect.w - rect.w % 2,
+        h: rect.h - rect.h % 2,
+        ..rect
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    fn monitor(name: &str, x: i32, y: i32, w: i32, h: i32) -> Monitor {
+        Monitor {
+            name: Some(name.to_string()),
+            x,
+            y,
+            w,
+            h,
+            refresh_hz: None,
+            rotation: Rotation::Normal,
+            mode_size: None,
+        }
+    }
+
+    #[test]
+    fn layout_change_only_counts_monitors_under_the_recording() {
+        let before = [
+            monitor("DP-1", 0, 0, 1920, 1080),
+            monitor("HDMI-1", 1920, 0, 1280, 1024),
+        ];
+        let area = rect(100, 100, 640, 480);
+        assert_eq!(layout_change(&before, &before, area), None);
+        assert_eq!(layout_change(&before, &before[..1], area), None);
+        assert_eq!(
+            layout_change(&before, &before[1..], area).as_deref(),
+            Some("DP-1 was disconnected")
+        );
+        let resized = [monitor("DP-1", 0, 0, 1280, 720)];
+        assert_eq!(
+            layout_change(&before, &resized, area).as_deref(),
+            Some("DP-1 is now 1280x720+0+0")
+        );
+        let spanning = rect(1800, 100, 400, 300);
+        assert_eq!(
+            layout_change(&before, &before[..1], spanning).as_deref(),
+            Some("HDMI-1 was disconnected")
+        );
+    }
+
+    #[test]
+    fn quarter_turned_monitor_bounds_follow_the_screen() {
+        let portrait = Monitor {
+            name: Some("DP-2".to_string()),
+            x: 1920,
+            y: 0,
+            w: 1920,
+            h: 1080,
+            refresh_hz: Some(60.0),
+            rotation: Rotation::Left,
+            mode_size: Some((1920, 1080)),
+        };
+        let bounds = portrait.bounds();
+        assert_eq!(
+            (bounds.x, bounds.y, bounds.w, bounds.h),
+            (1920, 0, 1080, 1920)
+        );
+
+        // Geometry already reported in screen space stays as it is.
+        let reported = Monitor {
+            w: 1080,
+            h: 1920,
+            ..portrait
+        };
+        let bounds = reported.bounds();
+        assert_eq!((bounds.w, bounds.h), (1080, 1920));
+    }
+
+    #[test]
+    fn relative_geometry_names_output_and_region() {
+        let (output, rect) = parse_relative_geometry("HDMI-1:100,100,1280,720").unwrap();
+        assert_eq!(output, "HDMI-1");
+        assert_eq!((rect.x, rect.y, rect.w, rect.h), (100, 100, 1280, 720));
+        assert!(parse_relative_geometry("HDMI-1:100,100,1280").is_none());
+        assert!(parse_relative_geometry("HDMI-1:0,0,0,720").is_none());
+        assert!(parse_relative_geometry("100,100,1280,720").is_none());
+    }
+
+    #[test]
+    fn x_geometry_accepts_negative_offsets() {
+        let parsed = parse_x_geometry("1920x1080+0+0").unwrap();
+        assert_eq!(parsed.bounds(), rect(0, 0, 1920, 1080));
+        let parsed = parse_x_geometry("1280x1024-1280+0").unwrap();
+        assert_eq!(parsed.bounds(), rect(-1280, 0, 1280, 1024));
+        let parsed = parse_x_geometry("800x600+100-600").unwrap();
+        assert_eq!(parsed.bounds(), rect(100, -600, 800, 600));
+        let parsed = parse_x_geometry("800x600-50-60").unwrap();
+        assert_eq!(parsed.bounds(), rect(-50, -60, 800, 600));
+        assert!(parse_x_geometry("1920x1080").is_none());
+        assert!(parse_x_geometry("1920x+0+0").is_none());
+        assert!(parse_x_geometry("garbage").is_none());
+    }
+
+    #[test]
+    fn monitor_for_follows_the_selection_centre() {
+        // A monitor left of and above the primary one.
+        let monitors = [
+            monitor("DP-1", 0, 0, 1920, 1080),
+            monitor("HDMI-1", -1280, -200, 1280, 1024),
+        ];
+        let name = |rect| monitor_for(&monitors, rect).and_then(|m| m.name.as_deref());
+        assert_eq!(name(rect(100, 100, 400, 300)), Some("DP-1"));
+        assert_eq!(name(rect(-900, -100, 400, 300)), Some("HDMI-1"));
+        // Straddling the edge: the centre decides.
+        assert_eq!(name(rect(-100, 100, 400, 300)), Some("DP-1"));
+        assert_eq!(name(rect(-300, 100, 400, 300)), Some("HDMI-1"));
+        // Centre in the gap above DP-1 and right of HDMI-1.
+        assert_eq!(name(rect(100, -300, 200, 200)), None);
+        // Right and bottom edges are exclusive.
+        assert_eq!(name(rect(1920, 0, 0, 0)), None);
+        assert_eq!(name(rect(1919, 1079, 0, 0)), Some("DP-1"));
+    }
+
+    #[test]
+    fn overlapping_monitors_prefer_the_one_covering_most() {
+        let mirrored = [
+            monitor("eDP-1", 0, 0, 1920, 1080),
+            monitor("HDMI-1", 0, 0, 1920, 1080),
+        ];
+        let found = monitor_for(&mirrored, rect(10, 10, 100, 100)).unwrap();
+        assert_eq!(found.name.as_deref(), Some("eDP-1"));
+
+        // A smaller output sharing the top-left corner of a larger one.
+        let nested = [
+            monitor("small", 0, 0, 1280, 720),
+            monitor("large", 0, 0, 2560, 1440),
+        ];
+        let found = monitor_for(&nested, rect(1000, 500, 800, 400)).unwrap();
+        assert_eq!(found.name.as_deref(), Some("large"));
+        let found = monitor_for(&nested, rect(100, 100, 200, 200)).unwrap();
+        assert_eq!(found.name.as_deref(), Some("small"));
+    }
+
+    #[test]
+    fn clamp_keeps_the_part_on_screen() {
+        let screen = rect(0, 0, 1920, 1080);
+        assert_eq!(
+            clamp_to_bounds(rect(100, 100, 200, 200), &screen),
+            rect(100, 100, 200, 200)
+        );
+        assert_eq!(
+            clamp_to_bounds(rect(-50, -20, 200, 200), &screen),
+            rect(0, 0, 150, 180)
+        );
+        assert_eq!(
+            clamp_to_bounds(rect(1800, 1000, 200, 200), &screen),
+            rect(1800, 1000, 120, 80)
+        );
+        assert_eq!(clamp_to_bounds(rect(-10, -10, 2000, 1200), &screen), screen);
+        let outside = clamp_to_bounds(rect(2000, 0, 100, 100), &screen);
+        assert_eq!(outside.w, 0);
+    }
+
+    #[test]
+    fn clamp_handles_monitors_at_negative_offsets() {
+        let left = rect(-1280, -200, 1280, 1024);
+        assert_eq!(
+            clamp_to_bounds(rect(-1400, -300, 300, 300), &left),
+            rect(-1280, -200, 180, 200)
+        );
+        assert_eq!(
+            clamp_to_bounds(rect(-100, 700, 300, 300), &left),
+            rect(-100, 700, 100, 124)
+        );
+    }
+
+    #[test]
+    fn snap_only_closes_small_gaps_to_the_bottom() {
+        let screen = rect(0, -1080, 1920, 1080);
+        assert_eq!(
+            snap_to_bottom(rect(0, -500, 100, 480), &screen),
+            Some(rect(0, -500, 100, 500))
+        );
+        assert_eq!(
+            snap_to_bottom(rect(0, -500, 100, 450), &screen),
+            Some(rect(0, -500, 100, 500))
+        );
+        assert_eq!(snap_to_bottom(rect(0, -500, 100, 449), &screen), None);
+        assert_eq!(snap_to_bottom(rect(0, -500, 100, 500), &screen), None);
+    }
+
+    #[test]
+    fn trim_to_even_rounds_sizes_down() {
+        assert_eq!(trim_to_even(rect(-3, -5, 101, 77)), rect(-3, -5, 100, 76));
+        assert_eq!(trim_to_even(rect(0, 0, 100, 76)), rect(0, 0, 100, 76));
+        assert_eq!(trim_to_even(rect(0, 0, 1, 1)), rect(0, 0, 0, 0));
+    }
+
+    fn any_rect() -> impl Strategy<Value = Rect> {
+        (-8000..8000, -8000..8000, 1..8000, 1..8000).prop_map(|(x, y, w, h)| rect(x, y, w, h))
+    }
+
+    fn any_layout() -> impl Strategy<Value = Vec<Monitor>> {
+        prop::collection::vec(any_rect(), 1..5).prop_map(|bounds| {
+            bounds
+                .iter()
+                .enumerate()
+                .map(|(index, b)| monitor(&format!("OUT-{}", index), b.x, b.y, b.w, b.h))
+                .collect()
+        })
+    }
+
+    fn inside(rect: &Rect, bounds: &Rect) -> bool {
+        rect.x >= bounds.x
+            && rect.y >= bounds.y
+            && rect.x + rect.w <= bounds.x + bounds.w
+            && rect.y + rect.h <= bounds.y + bounds.h
+    }
+
+    proptest! {
+        #[test]
+        fn x_geometry_round_trips(r in any_rect()) {
+            let token = format!("{}x{}{:+}{:+}", r.w, r.h, r.x, r.y);
+            let parsed = parse_x_geometry(&token);
+            prop_assert_eq!(parsed.map(|monitor| monitor.bounds()), Some(r));
+        }
+
+        #[test]
+        fn x_geometry_never_panics(token in any::<String>()) {
+            let _ = parse_x_geometry(&token);
+        }
+
+        #[test]
+        fn relative_geometry_round_trips(r in any_rect()) {
+            let spec = format!("HDMI-1:{},{},{},{}", r.x, r.y, r.w, r.h);
+            prop_assert_eq!(parse_relative_geometry(&spec), Some(("HDMI-1", r)));
+        }
+
+        #[test]
+        fn clamped_rects_stay_inside_and_non_negative(r in any_rect(), bounds in any_rect()) {
+            let clamped = clamp_to_bounds(r, &bounds);
+            prop_assert!(clamped.w >= 0 && clamped.h >= 0);
+            if clamped.w > 0 && clamped.h > 0 {
+                prop_assert!(inside(&clamped, &bounds));
+            }
+            if inside(&r, &bounds) {
+                prop_assert_eq!(clamped, r);
+            }
+        }
+
+        #[test]
+        fn adjusted_selections_are_even_and_on_screen(r in any_rect(), bounds in any_rect()) {
+            let mut adjusted = clamp_to_bounds(r, &bounds);
+            if let Some(snapped) = snap_to_bottom(adjusted, &bounds) {
+                prop_assert_eq!(snapped.y + snapped.h, bounds.y + bounds.h);
+                adjusted = snapped;
+            }
+            let even = trim_to_even(adjusted);
+            prop_assert!(even.w >= 0 && even.h >= 0);
+            prop_assert!(even.w % 2 == 0 && even.h % 2 == 0);
+            prop_assert!(adjusted.w - even.w <= 1 && adjusted.h - even.h <= 1);
+            if even.w > 0 && even.h > 0 {
+                prop_assert!(inside(&even, &bounds));
+            }
+        }
+
+        #[test]
+        fn chosen_monitor_holds_the_selection_centre(
+            monitors in any_layout(),
+            r in any_rect(),
+        ) {
+            let (x, y) = (r.x + r.w / 2, r.y + r.h / 2);
+            match monitor_for(&monitors, r) {
+                Some(found) => prop_assert!(contains(&found.bounds(), x, y)),
+                None => prop_assert!(monitors
+                    .iter()
+                    .all(|monitor| !contains(&monitor.bounds(), x, y))),
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Built-in notifiers that post "Recording finished: <link>" to a Slack
+//! webhook or a Matrix room once an upload completes.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use serde::Serialize;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::failure::spawn_error;
+use crate::platform;
+use crate::HooksConfig;
+
+#[derive(Serialize)]
+struct SlackMessage<'a> {
+    text: &'a str,
+}
+
+#[derive(Serialize)]
+struct MatrixMessage<'a> {
+    msgtype: &'a str,
+    body: &'a str,
+}
+
+/// Posts the finished recording's `link` to every configured notifier.
+/// A notifier that fails is reported and does not stop the others.
+pub(crate) fn announce(hooks: &HooksConfig, link: &str) {
+    let text = format!("Recording finished: {}", link);
+    if !hooks.slack_webhook.is_empty() {
+        if let Err(error) = post_slack(&hooks.slack_webhook, &text) {
+            platform::show_notification("Slack notification failed", &format!("{:#}", error), 4000);
+        }
+    }
+    if !hooks.matrix_homeserver.is_empty() && !hooks.matrix_room.is_empty() {
+        if let Err(error) = post_matrix(hooks, &text) {
+            platform::show_notification(
+                "Matrix notification failed",
+                &format!("{:#}", error),
+                4000,
+            );
+        }
+    }
+}
+
+fn post_slack(webhook: &str, text: &str) -> Result<()> {
+    let body = serde_json::to_string(&SlackMessage { text }).context("failed to encode message")?;
+    send("POST", webhook, &body, None)
+}
+
+fn post_matrix(hooks: &HooksConfig, text: &str) -> Result<()> {
+    let body = serde_json::to_string(&MatrixMessage {
+        msgtype: "m.text",
+        body: text,
+    })
+    .context("failed to encode message")?;
+    let url = matrix_send_url(
+        &hooks.matrix_homeserver,
+        &hooks.matrix_room,
+        &Local::now().timestamp_millis().to_string(),
+    );
+    send(
+        "PUT",
+        &url,
+        &body,
+        Some(&format!("Authorization: Bearer {}", hooks.matrix_token)),
+    )
+}
+
+/// Client-server API endpoint that sends one message into `room`; `txn`
+/// keeps a retried request from posting twice.
+fn matrix_send_url(homeserver: &str, room: &str, txn: &str) -> String {
+    format!(
+        "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+        homeserver.trim_end_matches('/'),
+        percent_encode(room),
+        percent_encode(txn)
+    )
+}
+
+/// Sends a JSON `body` with curl. The URL, which is the secret for a Slack
+/// webhook, and any `header` go through a curl config on stdin so neither
+/// shows up in the process list.
+fn send(method: &str, url: &str, body: &str, header: Option<&str>) -> Result<()> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time", "30"])
+        .args(["--request", method])
+        .args(["--header", "Content-Type: application/json"])
+        .args(["--data", body])
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| spawn_error("curl", error))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(curl_config(url, header).as_bytes())
+            .context("failed to pass the request to curl")?;
+    }
+    let output = child
+        .wait_with_output()
+        .context("failed to wait for curl")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("curl failed: {}", stderr.trim()));
+    }
+    Ok(())
+}
+
+/// curl config file lines setting `url` and an optional `header`.
+fn curl_config(url: &str, header: Option<&str>) -> String {
+    let quote = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut config = format!("url = \"{}\"\n", quote(url));
+    if let Some(header) = header {
+        config.push_str(&format!("header = \"{}\"\n", quote(header)));
+    }
+    config
+}
+
+/// Percent-encodes everything but RFC 3986 unreserved characters.
+pub(crate) fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{curl_config, matrix_send_url, percent_encode};
+
+    #[test]
+    fn matrix_room_ids_are_escaped_into_the_path() {
+        assert_eq!(percent_encode("clip 1.mkv"), "clip%201.mkv");
+        assert_eq!(
+            matrix_send_url("https://matrix.org/", "!abc:matrix.org", "17"),
+            "https://matrix.org/_matrix/client/v3/rooms/%21abc%3Amatrix.org/send/m.room.message/17"
+        );
+    }
+
+    #[test]
+    fn secrets_go_to_curl_as_quoted_config() {
+        assert_eq!(
+            curl_config("https://hooks.slack.com/services/T/B/x", None),
+            "url = \"https://hooks.slack.com/services/T/B/x\"\n"
+        );
+        assert_eq!(
+            curl_config("https://m.org/a\"b", Some("Authorization: Bearer t\\k")),
+            "url = \"https://m.org/a\\\"b\"\nheader = \"Authorization: Bearer t\\\\k\"\n"
+        );
+    }
+}
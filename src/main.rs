@@ -1,19 +1,68 @@
+mod completions;
+mod container;
+#[cfg(unix)]
+mod daemon;
+mod events;
+mod failure;
+mod ffmpeg;
+mod filename;
+mod geometry;
+mod hooks;
 mod platform;
+mod postprocess;
+mod preflight;
+mod recorder;
+#[cfg(unix)]
+mod remote;
+mod session;
+mod settings_gui;
+mod sidecar;
+mod signals;
+mod spotlight;
+mod state;
+mod supervisor;
+mod tray;
+mod upload;
+mod zoom;
 
 use anyhow::{anyhow, Context, Result};
 use chrono::Local;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-const PIDFILE: &str = "/tmp/record-region.pid";
-const SNAP_MARGIN_PX: i32 = 50;
+use events::Event;
+use failure::Failure;
+pub(crate) use geometry::{Monitor, Rect};
+use platform::NotificationAction;
+use state::RecordingState;
 
-#[derive(Debug, Clone, Deserialize, Default)]
+const FALLBACK_FRAMERATE: u32 = 60;
+/// How long the start notification stays up.
+const START_NOTICE_MS: u32 = 1200;
+/// Time a notification daemon takes to fade a toast out after it expires.
+const START_NOTICE_FADE: Duration = Duration::from_millis(400);
+/// Longest the start notification waits for the first frame.
+const FIRST_FRAME_TIMEOUT: Duration = Duration::from_secs(3);
+const X264_TUNES: &[&str] = &[
+    "film",
+    "animation",
+    "grain",
+    "stillimage",
+    "fastdecode",
+    "zerolatency",
+];
+const H264_LEVELS: &[&str] = &[
+    "1", "1b", "1.1", "1.2", "1.3", "2", "2.1", "2.2", "3", "3.1", "3.2", "4", "4.1", "4.2", "5",
+    "5.1", "5.2", "6", "6.1", "6.2",
+];
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[allow(
     dead_code,
     reason = "fields are consumed by the linux platform impl only"
@@ -23,9 +72,113 @@ pub(crate) struct Config {
     pub audio: AudioConfig,
     #[serde(default)]
     pub video: VideoConfig,
+    #[serde(default)]
+    pub record: RecordConfig,
+    #[serde(default)]
+    pub tray: TrayConfig,
+    #[serde(default)]
+    pub post: PostConfig,
+    #[serde(default)]
+    pub subtitles: SubtitleConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub capture: CaptureConfig,
+    #[serde(default)]
+    pub selection: SelectionConfig,
+    #[serde(default)]
+    pub zoom: ZoomConfig,
+    #[serde(default)]
+    pub spotlight: SpotlightConfig,
+    #[serde(default)]
+    pub upload: UploadConfig,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub remote: RemoteConfig,
+    #[serde(default)]
+    pub compress: CompressConfig,
+    #[serde(default)]
+    pub png_seq: PngSeqConfig,
+    #[serde(default)]
+    pub gif: GifConfig,
+    /// Named presets picked with `record --profile NAME`.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Window class → profile, applied when no profile was asked for and
+    /// that application's window covers most of the selection.
+    #[serde(default)]
+    pub app_profiles: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// This config with the named profile's overrides applied.
+    pub fn with_profile(&self, name: &str) -> Result<Config> {
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| Failure::Usage(format!("unknown profile: {}", name)))?;
+        let mut config = self.clone();
+        if let Some(format) = &profile.format {
+            config.video.format = format.clone();
+        }
+        if let Some(codec) = &profile.codec {
+            config.video.codec = codec.clone();
+        }
+        if let Some(framerate) = &profile.framerate {
+            config.video.framerate = framerate.clone();
+        }
+        if let Some(inputs) = &profile.audio_inputs {
+            config.audio.enabled = !inputs.is_empty();
+            config.audio.inputs = inputs.clone();
+        }
+        Ok(config)
+    }
+}
+
+/// A recording preset: what to capture plus the settings it overrides, so
+/// separate hotkeys can each run `record --profile NAME`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub(crate) struct Profile {
+    #[serde(default)]
+    pub mode: CaptureMode,
+    /// 1-based monitor number for `mode = "monitor"`, in xrandr order; 0
+    /// picks the monitor under the pointer.
+    #[serde(default)]
+    pub monitor: usize,
+    pub format: Option<String>,
+    pub codec: Option<String>,
+    pub framerate: Option<Framerate>,
+    /// Audio sources to record; an empty list records no audio.
+    pub audio_inputs: Option<Vec<String>>,
+    /// Window title, app id, class or mark for `mode = "window"`, workspace
+    /// name for `mode = "workspace"`; empty picks the focused one.
+    #[serde(default)]
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CaptureMode {
+    /// Drag out a region.
+    #[default]
+    Region,
+    /// One whole monitor.
+    Monitor,
+    /// Every monitor at once.
+    Screen,
+    /// One window or container, located through i3/sway IPC.
+    Window,
+    /// One workspace, located through i3/sway IPC.
+    Workspace,
+    /// A region relative to a named monitor, `OUTPUT:X,Y,W,H` in `target`,
+    /// so it stays put when monitors are rearranged.
+    Relative,
+    /// The area of the previous recording.
+    Last,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[allow(
     dead_code,
     reason = "fields are consumed by the linux platform impl only"
@@ -39,6 +192,117 @@ pub(crate) struct AudioConfig {
     pub mic_device: String,
     #[serde(default = "default_string_default")]
     pub system_device: String,
+    /// Keeps speaker output from bleeding into the mic when both are captured.
+    #[serde(default)]
+    pub echo_cancel: EchoCancel,
+    /// Capture system audio from this application only (matched against the
+    /// Pulse stream's application name or binary); empty captures everything.
+    #[serde(default)]
+    pub app: String,
+    /// Channel layout of the recorded track; `auto` keeps whatever the
+    /// inputs produce, one channel per merged source channel.
+    #[serde(default)]
+    pub channels: AudioChannels,
+    /// Sample rate of the recorded track, in Hz.
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+    /// What to do when the mic is a Bluetooth headset, whose mic only works
+    /// in a low-quality call profile that also degrades playback.
+    #[serde(default)]
+    pub bluetooth_mic: BluetoothMic,
+    /// Encoder for the audio track.
+    #[serde(default)]
+    pub codec: AudioCodec,
+    /// Target bitrate such as `96k`; empty picks the codec's own default.
+    #[serde(default)]
+    pub bitrate: String,
+    /// Opus tuning: `voip` favours speech intelligibility, `audio` fidelity.
+    #[serde(default)]
+    pub opus_application: OpusApplication,
+    /// Mute system audio quieter than this many dBFS, so idle hiss and
+    /// fans encode as silence; 0 disables the gate.
+    #[serde(default)]
+    pub system_gate_db: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AudioCodec {
+    #[default]
+    Aac,
+    Opus,
+    Mp3,
+    /// Lossless; fits mkv only.
+    Flac,
+    /// Uncompressed 16-bit PCM; fits mkv and mov.
+    Pcm,
+}
+
+impl AudioCodec {
+    /// ffmpeg encoder name, as `container::check_codecs` knows it.
+    pub fn encoder(self) -> &'static str {
+        match self {
+            AudioCodec::Aac => "aac",
+            AudioCodec::Opus => "libopus",
+            AudioCodec::Mp3 => "libmp3lame",
+            AudioCodec::Flac => "flac",
+            AudioCodec::Pcm => "pcm_s16le",
+        }
+    }
+
+    /// Bitrate used when `audio.bitrate` is left empty; `None` for the
+    /// lossless codecs, which take no bitrate.
+    fn default_bitrate(self) -> Option<&'static str> {
+        match self {
+            AudioCodec::Aac | AudioCodec::Mp3 => Some("192k"),
+            AudioCodec::Opus => Some("128k"),
+            AudioCodec::Flac | AudioCodec::Pcm => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OpusApplication {
+    #[default]
+    Audio,
+    Voip,
+    Lowdelay,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum BluetoothMic {
+    /// Record from it and say that audio drops to call quality.
+    #[default]
+    Warn,
+    /// Put the headset in its call profile for the recording and restore
+    /// the previous profile afterwards.
+    Switch,
+    /// Record from another mic so the headset keeps its high-quality profile.
+    Avoid,
+    /// Record from it without a word.
+    Allow,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AudioChannels {
+    #[default]
+    Auto,
+    Mono,
+    Stereo,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum EchoCancel {
+    #[default]
+    Off,
+    /// Route through PulseAudio's `module-echo-cancel` (webrtc) for the session.
+    Pulse,
+    /// Cancel inside ffmpeg with an adaptive `anlms` filter.
+    Filter,
 }
 
 impl Default for AudioConfig {
@@ -48,11 +312,56 @@ impl Default for AudioConfig {
             inputs: default_audio_inputs(),
             mic_device: default_string_default(),
             system_device: default_string_default(),
+            echo_cancel: EchoCancel::default(),
+            app: String::new(),
+            channels: AudioChannels::default(),
+            sample_rate: default_sample_rate(),
+            bluetooth_mic: BluetoothMic::default(),
+            codec: AudioCodec::default(),
+            bitrate: String::new(),
+            opus_application: OpusApplication::default(),
+            system_gate_db: 0.0,
+        }
+    }
+}
+
+impl AudioConfig {
+    /// ffmpeg options selecting the audio encoder and its quality.
+    pub fn encoder_args(&self) -> Vec<String> {
+        let mut args = vec!["-c:a".to_string(), self.codec.encoder().to_string()];
+        let bitrate = match self.bitrate.trim() {
+            "" => self.codec.default_bitrate(),
+            bitrate => self.codec.default_bitrate().map(|_| bitrate),
+        };
+        if let Some(bitrate) = bitrate {
+            args.extend(["-b:a".to_string(), bitrate.to_string()]);
+        }
+        if self.codec == AudioCodec::Opus {
+            let application = match self.opus_application {
+                OpusApplication::Audio => "audio",
+                OpusApplication::Voip => "voip",
+                OpusApplication::Lowdelay => "lowdelay",
+            };
+            args.extend(["-application".to_string(), application.to_string()]);
+        }
+        args
+    }
+
+    /// ffmpeg output options that fix the track's layout and rate. Sources
+    /// are downmixed rather than merged into one channel each.
+    #[allow(dead_code, reason = "only the linux platform impl builds captures")]
+    pub fn format_args(&self) -> Vec<String> {
+        let mut args = vec!["-ar".to_string(), self.sample_rate.to_string()];
+        match self.channels {
+            AudioChannels::Auto => {}
+            AudioChannels::Mono => args.extend(["-ac".to_string(), "1".to_string()]),
+            AudioChannels::Stereo => args.extend(["-ac".to_string(), "2".to_string()]),
         }
+        args
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[allow(
     dead_code,
     reason = "fields are consumed by the linux platform impl only"
@@ -63,9 +372,131 @@ pub(crate) struct VideoConfig {
     #[serde(default = "default_preset")]
     pub preset: String,
     #[serde(default = "default_framerate")]
-    pub framerate: u32,
+    pub framerate: Framerate,
     #[serde(default = "default_format")]
     pub format: String,
+    /// Seconds between keyframes; 0 leaves the encoder default.
+    #[serde(default)]
+    pub keyint_seconds: f64,
+    /// ffmpeg video encoder, e.g. libx264, libx265 or libsvtav1.
+    #[serde(default = "default_video_codec")]
+    pub codec: String,
+    /// 8 or 10 bits per channel.
+    #[serde(default = "default_bit_depth")]
+    pub bit_depth: u8,
+    #[serde(default)]
+    pub color_range: ColorRange,
+    #[serde(default)]
+    pub colorspace: Colorspace,
+    /// x264 tune, e.g. `stillimage` for slides or `animation` for flat UI.
+    #[serde(default = "default_tune")]
+    pub tune: String,
+    /// H.264 profile; `auto` lets x264 pick from the pixel format.
+    #[serde(default = "default_auto")]
+    pub profile: String,
+    /// H.264 level such as `4.1`; `auto` lets x264 pick from the stream.
+    #[serde(default = "default_auto")]
+    pub level: String,
+    /// Scales the captured region up this many times with nearest-neighbor,
+    /// so pixel art and tiny widgets stay legible; 1 records at native size.
+    #[serde(default = "default_magnify")]
+    pub magnify: u32,
+    /// Rotation or mirror applied to every frame, for monitors rotated
+    /// with an xrandr transform that x11grab still reads sideways.
+    #[serde(default)]
+    pub transpose: Transpose,
+    /// Drop frames that barely differ from the one before and write a
+    /// variable frame rate, so mostly static content such as terminals
+    /// makes far smaller files. Motion still plays at the full rate.
+    #[serde(default)]
+    pub adaptive_framerate: bool,
+    /// With `format = "auto"`, silent clips up to this many seconds become
+    /// GIFs; 0 never picks GIF.
+    #[serde(default = "default_auto_gif_secs")]
+    pub auto_gif_secs: u32,
+}
+
+/// Capture rate: a fixed number, or `auto` / `auto-half` to follow the
+/// refresh rate of the monitor being recorded.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum Framerate {
+    Fixed(u32),
+    Named(String),
+}
+
+impl Framerate {
+    /// Frames per second for a monitor refreshing at `refresh_hz`. An unknown
+    /// refresh rate makes `auto` fall back to 60 and `auto-half` to 30.
+    pub fn resolve(&self, refresh_hz: Option<f64>) -> Result<u32> {
+        let refresh_hz = refresh_hz.filter(|hz| *hz > 0.0);
+        let fps = match self {
+            Self::Fixed(fps) => *fps,
+            Self::Named(name) => match name.trim() {
+                "auto" => refresh_hz.map_or(FALLBACK_FRAMERATE, |hz| hz.round() as u32),
+                "auto-half" => {
+                    refresh_hz.map_or(FALLBACK_FRAMERATE / 2, |hz| (hz / 2.0).round() as u32)
+                }
+                other => other
+                    .parse()
+                    .with_context(|| format!("invalid video.framerate: {}", other))?,
+            },
+        };
+        if fps == 0 {
+            return Err(anyhow!("video.framerate must be positive"));
+        }
+        Ok(fps)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ColorRange {
+    /// 16-235 "TV" range, what browsers and players assume for untagged video.
+    #[default]
+    Limited,
+    /// 0-255 "PC" range, preserving every RGB level from the screen.
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Colorspace {
+    /// Convert with the BT.709 matrix and tag primaries, transfer and matrix.
+    #[default]
+    Bt709,
+    /// Leave ffmpeg's default conversion and write no color tags.
+    Untagged,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Transpose {
+    #[default]
+    None,
+    /// 90° clockwise.
+    #[serde(rename = "90")]
+    Rotate90,
+    #[serde(rename = "180")]
+    Rotate180,
+    /// 90° counter-clockwise.
+    #[serde(rename = "270")]
+    Rotate270,
+    /// Mirror left to right.
+    Flip,
+}
+
+impl Transpose {
+    #[allow(dead_code, reason = "only the linux platform impl captures frames")]
+    fn filter(self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Rotate90 => Some("transpose=clock"),
+            Self::Rotate180 => Some("hflip,vflip"),
+            Self::Rotate270 => Some("transpose=cclock"),
+            Self::Flip => Some("hflip"),
+        }
+    }
 }
 
 impl Default for VideoConfig {
@@ -75,200 +506,2743 @@ impl Default for VideoConfig {
             preset: default_preset(),
             framerate: default_framerate(),
             format: default_format(),
+            keyint_seconds: 0.0,
+            codec: default_video_codec(),
+            bit_depth: default_bit_depth(),
+            color_range: ColorRange::default(),
+            colorspace: Colorspace::default(),
+            tune: default_tune(),
+            profile: default_auto(),
+            level: default_auto(),
+            magnify: default_magnify(),
+            transpose: Transpose::default(),
+            adaptive_framerate: false,
+            auto_gif_secs: default_auto_gif_secs(),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub(crate) struct Monitor {
-    pub x: i32,
-    pub y: i32,
-    pub w: i32,
-    pub h: i32,
+impl VideoConfig {
+    /// Frames per second, taking `auto` as the fallback rate until
+    /// [`Self::pin_framerate`] has matched it to a monitor.
+    pub fn fps(&self) -> u32 {
+        self.framerate.resolve(None).unwrap_or(FALLBACK_FRAMERATE)
+    }
+
+    /// Resolves `auto` framerates against the recorded monitor and fixes the
+    /// result so later capture segments and re-encodes agree on it.
+    pub fn pin_framerate(&mut self, refresh_hz: Option<f64>) -> Result<u32> {
+        let fps = self.framerate.resolve(refresh_hz)?;
+        self.framerate = Framerate::Fixed(fps);
+        Ok(fps)
+    }
+
+    /// Keyframe interval in frames, if one is configured.
+    pub fn gop_frames(&self) -> Option<u32> {
+        if self.keyint_seconds <= 0.0 {
+            return None;
+        }
+        Some(((self.fps() as f64 * self.keyint_seconds).round() as u32).max(1))
+    }
+
+    pub fn pix_fmt(&self) -> Result<&'static str> {
+        match self.bit_depth {
+            8 => Ok("yuv420p"),
+            10 => Ok("yuv420p10le"),
+            other => Err(anyhow!(
+                "unsupported video.bit_depth {} (use 8 or 10)",
+                other
+            )),
+        }
+    }
+
+    /// Encoder options shared by the capture and every re-encoding pass.
+    pub fn encoder_args(&self) -> Result<Vec<String>> {
+        // SVT-AV1 numbers its presets instead of naming them like x264/x265.
+        let preset = if self.codec == "libsvtav1" {
+            svt_av1_preset(&self.preset).to_string()
+        } else {
+            self.preset.clone()
+        };
+        let mut args = vec![
+            "-c:v".to_string(),
+            self.codec.clone(),
+            "-crf".to_string(),
+            self.crf.to_string(),
+            "-preset".to_string(),
+            preset,
+            "-pix_fmt".to_string(),
+            self.pix_fmt()?.to_string(),
+        ];
+        if let Some(gop) = self.gop_frames() {
+            args.extend_from_slice(&["-g".to_string(), gop.to_string()]);
+        }
+        args.extend(self.x264_tuning_args()?);
+        args.extend(self.color_tags());
+        Ok(args)
+    }
+
+    fn x264_tuning_args(&self) -> Result<Vec<String>> {
+        let tune = (self.tune != "none").then_some(self.tune.as_str());
+        let profile = (self.profile != "auto").then_some(self.profile.as_str());
+        let level = (self.level != "auto").then_some(self.level.as_str());
+        if tune.is_none() && profile.is_none() && level.is_none() {
+            return Ok(Vec::new());
+        }
+        if self.codec != "libx264" {
+            return Err(anyhow!(
+                "video.tune, video.profile and video.level need video.codec = libx264, not {}",
+                self.codec
+            ));
+        }
+
+        let mut args = Vec::new();
+        if let Some(tune) = tune {
+            if !X264_TUNES.contains(&tune) {
+                return Err(anyhow!(
+                    "unsupported video.tune {} (use one of: none, {})",
+                    tune,
+                    X264_TUNES.join(", ")
+                ));
+            }
+            args.extend_from_slice(&["-tune".to_string(), tune.to_string()]);
+        }
+        if let Some(profile) = profile {
+            let supported: &[&str] = match self.bit_depth {
+                10 => &["high10"],
+                _ => &["baseline", "main", "high"],
+            };
+            if !supported.contains(&profile) {
+                return Err(anyhow!(
+                    "video.profile {} cannot encode {}-bit video (use auto or {})",
+                    profile,
+                    self.bit_depth,
+                    supported.join(", ")
+                ));
+            }
+            args.extend_from_slice(&["-profile:v".to_string(), profile.to_string()]);
+        }
+        if let Some(level) = level {
+            if !H264_LEVELS.contains(&level) {
+                return Err(anyhow!(
+                    "unsupported video.level {} (use auto or one of: {})",
+                    level,
+                    H264_LEVELS.join(", ")
+                ));
+            }
+            args.extend_from_slice(&["-level:v".to_string(), level.to_string()]);
+        }
+        Ok(args)
+    }
+
+    /// Filters applied to captured frames before encoding.
+    #[allow(dead_code, reason = "only the linux platform impl captures frames")]
+    pub fn capture_filters(&self) -> Vec<String> {
+        let mut filters = Vec::new();
+        if self.magnify > 1 {
+            filters.push(format!("scale=iw*{0}:ih*{0}:flags=neighbor", self.magnify));
+        }
+        if self.colorspace == Colorspace::Bt709 {
+            filters.push(format!(
+                "scale=out_color_matrix=bt709:out_range={}",
+                self.range_name()
+            ));
+        }
+        if self.adaptive_framerate {
+            filters.push("mpdecimate".to_string());
+        }
+        filters
+    }
+
+    fn color_tags(&self) -> Vec<String> {
+        if self.colorspace == Colorspace::Untagged {
+            return Vec::new();
+        }
+        [
+            "-color_primaries",
+            "bt709",
+            "-color_trc",
+            "bt709",
+            "-colorspace",
+            "bt709",
+            "-color_range",
+            self.range_name(),
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+    }
+
+    fn range_name(&self) -> &'static str {
+        match self.color_range {
+            ColorRange::Limited => "tv",
+            ColorRange::Full => "pc",
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub(crate) struct Rect {
-    pub x: i32,
-    pub y: i32,
-    pub w: i32,
-    pub h: i32,
+fn svt_av1_preset(x264_preset: &str) -> u8 {
+    match x264_preset {
+        "ultrafast" => 12,
+        "superfast" => 11,
+        "veryfast" => 10,
+        "faster" => 9,
+        "fast" => 8,
+        "slow" => 4,
+        "slower" => 3,
+        "veryslow" => 2,
+        _ => 6,
+    }
 }
 
-fn default_true() -> bool {
-    true
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct RecordConfig {
+    #[serde(default)]
+    pub toggle_behavior: ToggleBehavior,
+    /// Two presses within this many milliseconds discard the recording; 0 disables.
+    #[serde(default)]
+    pub cancel_window_ms: u64,
+    /// Minutes without keyboard or mouse input before `idle_action`; 0 disables.
+    #[serde(default)]
+    pub idle_timeout_mins: u64,
+    #[serde(default)]
+    pub idle_action: IdleAction,
+    /// When to show the final recording area before capture starts.
+    #[serde(default)]
+    pub preview: PreviewMode,
+    /// Seconds the preview stays up before recording begins.
+    #[serde(default = "default_preview_secs")]
+    pub preview_secs: u64,
+    /// Ask for tags when a recording is stopped without `--tag`.
+    #[serde(default)]
+    pub prompt_tags: bool,
+    /// In workspace capture, pause while another workspace is shown.
+    #[serde(default = "default_true")]
+    pub workspace_auto_pause: bool,
+    /// Keep a background process watching the encoder so a crash is
+    /// reported as it happens rather than found out when stopping, and the
+    /// monitor layout so a recording ends cleanly when it changes.
+    #[serde(default = "default_true")]
+    pub watchdog: bool,
+    /// Float a small Stop button beside the recorded area while recording.
+    #[serde(default)]
+    pub stop_button: bool,
+    /// Which notifications are shown.
+    #[serde(default)]
+    pub notifications: NotificationLevel,
+    /// When "Recording started" is shown relative to the capture.
+    #[serde(default)]
+    pub start_notice: StartNotice,
+    /// dmenu-style command `record --pick-profile` offers the profiles in.
+    #[serde(default = "default_menu_command")]
+    pub menu_command: String,
+    /// Window classes that must not end up in a recording, such as a
+    /// password manager.
+    #[serde(default)]
+    pub never_record: Vec<String>,
+    /// Ask before recording a selection that holds one of those windows,
+    /// instead of only warning.
+    #[serde(default = "default_true")]
+    pub never_record_confirm: bool,
 }
 
-fn default_audio_inputs() -> Vec<String> {
-    vec!["mic".to_string()]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PreviewMode {
+    Off,
+    /// Only when clamping, snapping or rounding changed the selection.
+    #[default]
+    Adjusted,
+    Always,
 }
 
-fn default_string_default() -> String {
-    "default".to_string()
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum IdleAction {
+    #[default]
+    Stop,
+    Pause,
 }
 
-fn default_crf() -> i32 {
-    18
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum NotificationLevel {
+    /// Confirmations such as "Recording started" as well as problems.
+    #[default]
+    All,
+    /// Failures and warnings only, for kiosks and shared screens.
+    Important,
+    /// Nothing.
+    Silent,
 }
 
-fn default_preset() -> String {
-    "veryfast".to_string()
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum StartNotice {
+    /// As soon as the capture is running; it may show in the first second.
+    #[default]
+    After,
+    /// Once the first frame has been written.
+    Frame,
+    /// Before the capture starts, which then waits for the toast to go
+    /// away so it never lands in the footage.
+    Before,
+    /// No start notification.
+    Off,
 }
 
-fn default_framerate() -> u32 {
-    60
+impl Default for RecordConfig {
+    fn default() -> Self {
+        Self {
+            toggle_behavior: ToggleBehavior::default(),
+            cancel_window_ms: 0,
+            idle_timeout_mins: 0,
+            idle_action: IdleAction::default(),
+            preview: PreviewMode::default(),
+            preview_secs: default_preview_secs(),
+            prompt_tags: false,
+            workspace_auto_pause: true,
+            watchdog: true,
+            stop_button: false,
+            notifications: NotificationLevel::default(),
+            start_notice: StartNotice::default(),
+            menu_command: default_menu_command(),
+            never_record: Vec::new(),
+            never_record_confirm: true,
+        }
+    }
 }
 
-fn default_format() -> String {
-    "mkv".to_string()
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub(crate) struct TrayConfig {
+    /// Prefer the settings served by the running qol-tray over the local file.
+    #[serde(default)]
+    pub live_settings: bool,
+    /// Base URL of qol-tray when it does not run on the default port. The
+    /// `QOL_TRAY_URL` the tray host exports takes precedence.
+    #[serde(default)]
+    pub url: String,
 }
 
-fn main() -> ExitCode {
-    let action = env::args().nth(1).unwrap_or_else(|| "record".to_string());
-    let result = match action.as_str() {
-        "record" => run_record_action(),
-        "settings" => platform::open_settings(),
-        _ => Err(anyhow!("Unknown action: {}", action)),
-    };
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct ZoomConfig {
+    /// Route the capture through a zmq-controlled crop; needs an ffmpeg built
+    /// with libzmq and the zmqsend tool.
+    #[serde(default)]
+    pub enabled: bool,
+    /// How much each `zoom-in` magnifies.
+    #[serde(default = "default_zoom_factor")]
+    pub factor: f64,
+    /// Length of the zoom animation.
+    #[serde(default = "default_zoom_duration_ms")]
+    pub duration_ms: u64,
+}
 
-    match result {
-        Ok(()) => ExitCode::SUCCESS,
-        Err(error) => {
-            eprintln!("{:#}", error);
-            ExitCode::from(1)
+impl Default for ZoomConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            factor: default_zoom_factor(),
+            duration_ms: default_zoom_duration_ms(),
         }
     }
 }
 
-fn run_record_action() -> Result<()> {
-    if let Some(pid) = read_pid() {
-        if platform::process_alive(pid) {
-            platform::stop_capture(pid)?;
-            thread::sleep(Duration::from_millis(250));
-            remove_pidfile();
-            platform::show_notification("Recording stopped", "Saved to ~/Videos", 2000);
-            return Ok(());
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct SpotlightConfig {
+    /// Dim the recording outside a circle that follows the pointer. Needs
+    /// the same zmq-enabled ffmpeg and zmqsend as zoom.
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_spotlight_radius")]
+    pub radius: u32,
+    /// Opacity of the dimming outside the circle, from 0 to 1.
+    #[serde(default = "default_spotlight_dim")]
+    pub dim: f64,
+}
+
+impl Default for SpotlightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: default_spotlight_radius(),
+            dim: default_spotlight_dim(),
         }
-        remove_pidfile();
     }
+}
 
-    let config: Config = qol_config::load_plugin_config(&["plugin-screen-recorder"]);
-    let mut rect = match platform::select_region()? {
-        Some(region) => region,
-        None => return Ok(()),
-    };
+/// Limits for `video.format = "png-seq"`, which writes one PNG per frame and
+/// fills a disk quickly.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[allow(
+    dead_code,
+    reason = "fields are consumed by the linux platform impl only"
+)]
+pub(crate) struct PngSeqConfig {
+    /// Frames written per second, independent of the capture framerate.
+    #[serde(default = "default_png_seq_fps")]
+    pub fps: u32,
+    /// The capture stops by itself after this many frames.
+    #[serde(default = "default_png_seq_max_frames")]
+    pub max_frames: u32,
+}
 
-    let screen_bottom = match monitor_for_selection(rect) {
-        Some(monitor) => {
-            rect = clamp_to_bounds(rect, monitor);
-            Some(monitor.y + monitor.h)
-        }
-        None => {
-            let virtual_monitor = platform::full_screen_bounds()?;
-            rect = clamp_to_bounds(rect, virtual_monitor);
-            Some(virtual_monitor.y + virtual_monitor.h)
+impl Default for PngSeqConfig {
+    fn default() -> Self {
+        Self {
+            fps: default_png_seq_fps(),
+            max_frames: default_png_seq_max_frames(),
         }
-    };
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct CompressConfig {
+    /// Size `compress-last` aims for; the default fits Discord's upload limit.
+    #[serde(default = "default_compress_target_mb")]
+    pub target_mb: f64,
+}
 
-    if let Some(bottom) = screen_bottom {
-        let gap = bottom - (rect.y + rect.h);
-        if gap > 0 && gap <= SNAP_MARGIN_PX {
-            rect.h = bottom - rect.y;
+impl Default for CompressConfig {
+    fn default() -> Self {
+        Self {
+            target_mb: default_compress_target_mb(),
         }
     }
+}
 
-    if rect.w <= 0 || rect.h <= 0 {
-        platform::show_notification(
-            "Recording failed",
-            &format!("Invalid area: {}x{}", rect.w, rect.h),
-            1200,
-        );
-        return Err(anyhow!("invalid recording area {}x{}", rect.w, rect.h));
-    }
+/// Size/quality trade-offs for `convert --to gif`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct GifConfig {
+    #[serde(default = "default_gif_fps")]
+    pub fps: u32,
+    /// Palette size; fewer colors shrink the file at the cost of banding.
+    #[serde(default = "default_gif_colors")]
+    pub colors: u32,
+    #[serde(default)]
+    pub dither: GifDither,
+    /// Runs gifsicle over the exported file; raw ffmpeg GIFs are usually
+    /// several times larger than they need to be.
+    #[serde(default)]
+    pub gifsicle: bool,
+    #[serde(default = "default_gifsicle_path")]
+    pub gifsicle_path: String,
+    /// gifsicle `-O` level, 1 to 3.
+    #[serde(default = "default_gifsicle_level")]
+    pub gifsicle_level: u8,
+    /// gifsicle `--lossy` strength; 0 keeps the export lossless.
+    #[serde(default)]
+    pub lossy: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum GifDither {
+    /// Ordered pattern; compresses best.
+    #[default]
+    Bayer,
+    Sierra,
+    Floyd,
+    None,
+}
 
-    if rect.w % 2 != 0 {
-        rect.w -= 1;
+impl GifDither {
+    pub fn filter_name(self) -> &'static str {
+        match self {
+            Self::Bayer => "bayer",
+            Self::Sierra => "sierra2_4a",
+            Self::Floyd => "floyd_steinberg",
+            Self::None => "none",
+        }
     }
-    if rect.h % 2 != 0 {
-        rect.h -= 1;
+}
+
+impl Default for GifConfig {
+    fn default() -> Self {
+        Self {
+            fps: default_gif_fps(),
+            colors: default_gif_colors(),
+            dither: GifDither::default(),
+            gifsicle: false,
+            gifsicle_path: default_gifsicle_path(),
+            gifsicle_level: default_gifsicle_level(),
+            lossy: 0,
+        }
     }
+}
 
-    let output_file = output_file_path(&config.video.format)?;
-    let pid = platform::start_capture(&rect, &config, &output_file)?;
+/// Chat rooms told about each recording once its upload has finished.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub(crate) struct HooksConfig {
+    /// Slack incoming webhook URL. It is a secret, so like the tokens it is
+    /// left out of session snapshots.
+    #[serde(default, skip_serializing)]
+    pub slack_webhook: String,
+    /// Matrix homeserver, such as `https://matrix.org`.
+    #[serde(default)]
+    pub matrix_homeserver: String,
+    /// Matrix room ID (`!abc:example.org`) the bot account has joined.
+    #[serde(default)]
+    pub matrix_room: String,
+    /// Access token of the account that posts to `matrix_room`.
+    #[serde(default, skip_serializing)]
+    pub matrix_token: String,
+}
 
-    fs::write(PIDFILE, pid.to_string()).context("failed to write pid file")?;
-    thread::sleep(Duration::from_millis(500));
+/// HTTP control listener the daemon opens for remote start and stop.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct RemoteConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address and port to listen on.
+    #[serde(default = "default_remote_listen")]
+    pub listen: String,
+    /// Bearer token every request must carry.
+    #[serde(default, skip_serializing)]
+    pub token: String,
+}
 
-    if platform::process_alive(pid) {
-        platform::show_notification("Recording started", "Press your hotkey to stop", 1200);
-    } else {
-        remove_pidfile();
-        platform::show_notification(
-            "Recording failed",
-            &format!("Check {}", platform::CAPTURE_LOG),
-            1600,
-        );
-        return Err(anyhow!("capture process exited immediately"));
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: default_remote_listen(),
+            token: String::new(),
+        }
     }
-
-    Ok(())
 }
 
-fn monitor_for_selection(rect: Rect) -> Option<Monitor> {
-    let center_x = rect.x + rect.w / 2;
-    let center_y = rect.y + rect.h / 2;
-    let monitors = platform::get_monitors().ok()?;
-    monitors.into_iter().find(|monitor| {
-        center_x >= monitor.x
-            && center_x < monitor.x + monitor.w
-            && center_y >= monitor.y
-            && center_y < monitor.y + monitor.h
-    })
+/// Sends each finished recording to a server with `curl --upload-file`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct UploadConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Target of the HTTP PUT; a trailing `/` appends the file name.
+    #[serde(default)]
+    pub url: String,
+    /// Extra attempts after a network failure or server error.
+    #[serde(default = "default_upload_retries")]
+    pub retries: u32,
+    /// Files at least this large get progress notifications.
+    #[serde(default = "default_upload_progress_min_mb")]
+    pub progress_min_mb: u64,
 }
 
-fn clamp_to_bounds(mut rect: Rect, bounds: Monitor) -> Rect {
-    if rect.x < bounds.x {
-        rect.w -= bounds.x - rect.x;
-        rect.x = bounds.x;
-    }
-    if rect.y < bounds.y {
-        rect.h -= bounds.y - rect.y;
-        rect.y = bounds.y;
-    }
-    if rect.x + rect.w > bounds.x + bounds.w {
-        rect.w = bounds.x + bounds.w - rect.x;
-    }
-    if rect.y + rect.h > bounds.y + bounds.h {
-        rect.h = bounds.y + bounds.h - rect.y;
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            retries: default_upload_retries(),
+            progress_min_mb: default_upload_progress_min_mb(),
+        }
     }
-    rect
 }
 
-fn read_pid() -> Option<u32> {
-    let content = fs::read_to_string(PIDFILE).ok()?;
-    content.trim().parse::<u32>().ok()
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub(crate) struct CaptureConfig {
+    /// X display to record, e.g. `:1` or `localhost:10.0`; empty uses `$DISPLAY`.
+    #[serde(default)]
+    pub display: String,
 }
 
-fn remove_pidfile() {
-    let _ = fs::remove_file(PIDFILE);
+impl CaptureConfig {
+    pub fn resolved_display(&self) -> String {
+        if !self.display.is_empty() {
+            return self.display.clone();
+        }
+        env::var("DISPLAY")
+            .ok()
+            .filter(|display| !display.is_empty())
+            .unwrap_or_else(|| ":0.0".to_string())
+    }
 }
 
-fn output_file_path(format: &str) -> Result<PathBuf> {
-    let home = env::var("HOME").context("HOME is not set")?;
-    let mut videos = PathBuf::from(home);
-    videos.push("Videos");
-    fs::create_dir_all(&videos).context("failed to create output directory")?;
-    let timestamp = Local::now().format("%F_%H-%M-%S").to_string();
-    videos.push(format!("recording-{}.{}", timestamp, format));
-    Ok(videos)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct SelectionConfig {
+    /// Areas narrower or shorter than this many pixels count as a stray
+    /// click and cancel the recording.
+    #[serde(default = "default_min_selection_size")]
+    pub min_size: i32,
+    /// Seconds an open selection waits before giving up; 0 waits forever.
+    #[serde(default = "default_selection_timeout_secs")]
+    pub timeout_secs: u64,
 }
 
-#[cfg(test)]
-mod tests {
-    use qol_tray::plugins::manifest::PluginManifest;
+impl Default for SelectionConfig {
+    fn default() -> Self {
+        Self {
+            min_size: default_min_selection_size(),
+            timeout_secs: default_selection_timeout_secs(),
+        }
+    }
+}
 
-    #[test]
-    fn validate_plugin_contract() {
-        PluginManifest::load_and_validate("plugin.toml").expect("plugin.toml invalid");
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct OutputConfig {
+    /// Write MP4/MOV as fragments so a crash mid-recording stays playable.
+    #[serde(default = "default_true")]
+    pub crash_safe: bool,
+    /// Remux crash-safe MP4/MOV into a regular file once recording succeeds.
+    #[serde(default = "default_true")]
+    pub defragment: bool,
+    /// Folder recordings are saved to; empty uses `~/Videos`.
+    #[serde(default)]
+    pub directory: String,
+    /// Where the capture writes while recording.
+    #[serde(default)]
+    pub staging: Staging,
+    /// Write a `.json` file describing how each recording was made next to it.
+    #[serde(default)]
+    pub sidecar: bool,
+    /// Append stop-time tags to the file name.
+    #[serde(default)]
+    pub tags_in_filename: bool,
+    /// File name for new recordings, without extension. `{timestamp}`,
+    /// `{date}`, `{time}`, `{monitor}`, `{session}` and `{take}` are filled
+    /// in; `/` makes subdirectories of `directory`.
+    #[serde(default = "default_name_template")]
+    pub name_template: String,
+    /// Also write the audio track to its own file next to the recording.
+    #[serde(default)]
+    pub split_audio: bool,
+    /// Extra copies written next to the recording, each `FORMAT` or
+    /// `FORMAT:HEIGHT`, such as `mp4:720` or `gif:360`.
+    #[serde(default)]
+    pub renditions: Vec<String>,
+    /// Format of the split-off audio file.
+    #[serde(default)]
+    pub split_audio_format: SplitAudioFormat,
+    /// Put the first frame on the clipboard as a PNG once a recording is
+    /// saved, so pasting into chat shows a preview.
+    #[serde(default)]
+    pub clipboard_image: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Staging {
+    /// Write straight to the output directory.
+    #[default]
+    Off,
+    /// Write to RAM and move the finished file to the output directory, so
+    /// a slow network or USB drive cannot make the encoder drop frames.
+    Tmpfs,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SplitAudioFormat {
+    /// Lossless, for editing.
+    #[default]
+    Flac,
+    /// Small, for sharing.
+    Opus,
+}
+
+impl SplitAudioFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Flac => "flac",
+            Self::Opus => "opus",
+        }
+    }
+
+    pub fn encoder_args(self) -> &'static [&'static str] {
+        match self {
+            Self::Flac => &["-c:a", "flac"],
+            Self::Opus => &["-c:a", "libopus", "-b:a", "128k"],
+        }
+    }
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            crash_safe: true,
+            defragment: true,
+            directory: String::new(),
+            staging: Staging::default(),
+            sidecar: false,
+            tags_in_filename: false,
+            name_template: default_name_template(),
+            split_audio: false,
+            renditions: Vec::new(),
+            split_audio_format: SplitAudioFormat::default(),
+            clipboard_image: false,
+        }
+    }
+}
+
+impl OutputConfig {
+    pub fn resolved_directory(&self) -> Result<PathBuf> {
+        if !self.directory.is_empty() {
+            return Ok(PathBuf::from(&self.directory));
+        }
+        let home = env::var("HOME").context("HOME is not set")?;
+        Ok(PathBuf::from(home).join("Videos"))
+    }
+
+    /// RAM-backed folder staged recordings are written to: `/dev/shm`, else
+    /// `$XDG_RUNTIME_DIR`, else the system temp dir.
+    pub fn staging_directory(&self) -> PathBuf {
+        let shm = Path::new("/dev/shm");
+        let base = if shm.is_dir() {
+            shm.to_path_buf()
+        } else {
+            env::var_os("XDG_RUNTIME_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(env::temp_dir)
+        };
+        base.join("screen-recorder")
+    }
+}
+
+/// Processing applied to the finished file after the capture stops.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct PostConfig {
+    /// Run an EBU R128 loudness normalization pass over the audio.
+    #[serde(default)]
+    pub loudnorm: bool,
+    /// Integrated loudness target in LUFS.
+    #[serde(default = "default_loudnorm_target")]
+    pub loudnorm_target: f64,
+    /// Drop this many frames from the start, where the start notification
+    /// and its fade-out can show up.
+    #[serde(default)]
+    pub trim_first_frames: u32,
+    /// Drop this many frames from the end, where the stop hotkey's effects
+    /// can show up.
+    #[serde(default)]
+    pub trim_last_frames: u32,
+    /// Cut leading and trailing silence from the finished file.
+    #[serde(default)]
+    pub trim_silence: bool,
+    #[serde(default = "default_silence_threshold_db")]
+    pub silence_threshold_db: f64,
+    /// Shortest quiet stretch that counts as silence, in seconds.
+    #[serde(default = "default_min_silence_secs")]
+    pub min_silence_secs: f64,
+    /// Also cut silences in the middle at least this long; 0 keeps them.
+    #[serde(default)]
+    pub trim_mid_silence_secs: f64,
+    /// Check the finished file with ffprobe and try to repair it if damaged.
+    #[serde(default = "default_true")]
+    pub verify: bool,
+    /// Mix a short click into the audio at every mouse button press.
+    #[serde(default)]
+    pub click_sounds: bool,
+    /// Peak amplitude of the click, from 0 to 1.
+    #[serde(default = "default_click_volume")]
+    pub click_volume: f64,
+}
+
+impl Default for PostConfig {
+    fn default() -> Self {
+        Self {
+            loudnorm: false,
+            loudnorm_target: default_loudnorm_target(),
+            trim_first_frames: 0,
+            trim_last_frames: 0,
+            trim_silence: false,
+            silence_threshold_db: default_silence_threshold_db(),
+            min_silence_secs: default_min_silence_secs(),
+            trim_mid_silence_secs: 0.0,
+            verify: true,
+            click_sounds: false,
+            click_volume: default_click_volume(),
+        }
+    }
+}
+
+/// Speech-to-text subtitles generated with whisper.cpp after recording.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct SubtitleConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_whisper_binary")]
+    pub binary: String,
+    /// Path to the ggml model file whisper.cpp should load.
+    #[serde(default)]
+    pub model: String,
+    #[serde(default = "default_subtitle_language")]
+    pub language: String,
+    /// Embed the subtitles as a stream instead of leaving an `.srt` beside the file.
+    #[serde(default)]
+    pub mux: bool,
+}
+
+impl Default for SubtitleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            binary: default_whisper_binary(),
+            model: String::new(),
+            language: default_subtitle_language(),
+            mux: false,
+        }
+    }
+}
+
+/// What the record hotkey does while a recording is already running.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ToggleBehavior {
+    #[default]
+    Stop,
+    Pause,
+    Mark,
+}
+
+/// `display` pointed at X screen `screen`, e.g. `:0` on screen 1 becomes
+/// `:0.1`. `None` when it already names that screen.
+fn display_on_screen(display: &str, screen: u32) -> Option<String> {
+    let colon = display.rfind(':')?;
+    let (base, current) = match display[colon..].split_once('.') {
+        Some((number, current)) => (&display[..colon + number.len()], current.parse().ok()?),
+        None => (display, 0),
+    };
+    (current != screen).then(|| format!("{}.{}", base, screen))
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_audio_inputs() -> Vec<String> {
+    vec!["mic".to_string()]
+}
+
+fn default_string_default() -> String {
+    "default".to_string()
+}
+
+fn default_min_selection_size() -> i32 {
+    16
+}
+
+fn default_selection_timeout_secs() -> u64 {
+    60
+}
+
+fn default_name_template() -> String {
+    "recording-{timestamp}".to_string()
+}
+
+fn default_menu_command() -> String {
+    "rofi -dmenu -i -p Profile".to_string()
+}
+
+fn default_preview_secs() -> u64 {
+    1
+}
+
+fn default_zoom_factor() -> f64 {
+    2.0
+}
+
+fn default_zoom_duration_ms() -> u64 {
+    300
+}
+
+fn default_spotlight_radius() -> u32 {
+    120
+}
+
+fn default_spotlight_dim() -> f64 {
+    0.6
+}
+
+fn default_crf() -> i32 {
+    18
+}
+
+fn default_preset() -> String {
+    "veryfast".to_string()
+}
+
+fn default_framerate() -> Framerate {
+    Framerate::Fixed(FALLBACK_FRAMERATE)
+}
+
+fn default_video_codec() -> String {
+    "libx264".to_string()
+}
+
+fn default_tune() -> String {
+    "none".to_string()
+}
+
+fn default_auto() -> String {
+    "auto".to_string()
+}
+
+fn default_bit_depth() -> u8 {
+    8
+}
+
+fn default_format() -> String {
+    "mkv".to_string()
+}
+
+fn default_loudnorm_target() -> f64 {
+    -16.0
+}
+
+fn default_sample_rate() -> u32 {
+    48_000
+}
+
+fn default_png_seq_fps() -> u32 {
+    10
+}
+
+fn default_png_seq_max_frames() -> u32 {
+    3000
+}
+
+fn default_compress_target_mb() -> f64 {
+    10.0
+}
+
+fn default_magnify() -> u32 {
+    1
+}
+
+fn default_auto_gif_secs() -> u32 {
+    8
+}
+
+fn default_gif_fps() -> u32 {
+    15
+}
+
+fn default_gif_colors() -> u32 {
+    256
+}
+
+fn default_gifsicle_path() -> String {
+    "gifsicle".to_string()
+}
+
+fn default_gifsicle_level() -> u8 {
+    3
+}
+
+fn default_remote_listen() -> String {
+    "0.0.0.0:47800".to_string()
+}
+
+fn default_upload_retries() -> u32 {
+    3
+}
+
+fn default_upload_progress_min_mb() -> u64 {
+    50
+}
+
+fn default_click_volume() -> f64 {
+    0.3
+}
+
+fn default_whisper_binary() -> String {
+    "whisper-cli".to_string()
+}
+
+fn default_subtitle_language() -> String {
+    "auto".to_string()
+}
+
+fn default_silence_threshold_db() -> f64 {
+    -45.0
+}
+
+fn default_min_silence_secs() -> f64 {
+    0.5
+}
+
+struct Action {
+    name: &'static str,
+    /// Arguments the tray passes when it invokes this action, or `None` for
+    /// commands that are only meant for the terminal.
+    tray_args: Option<&'static [&'static str]>,
+    /// Options and fixed arguments offered by shell completion.
+    flags: &'static [&'static str],
+    /// Whether running the action can change the set of active recordings.
+    changes_state: bool,
+    run: fn(&[String]) -> Result<()>,
+}
+
+/// Every CLI action. `plugin.toml` declares the tray-facing subset; the
+/// `manifest` action renders it so the two cannot drift.
+const ACTIONS: &[Action] = &[
+    Action {
+        name: "record",
+        tray_args: Some(&["record"]),
+        flags: &[
+            "-o",
+            "--output",
+            "--profile",
+            "--pick-profile",
+            "--append",
+            "--window",
+            "--workspace",
+            "--geometry-relative",
+            "--last-region",
+        ],
+        changes_state: true,
+        run: run_record_action,
+    },
+    Action {
+        name: "start",
+        tray_args: Some(&["start"]),
+        flags: &[
+            "-o",
+            "--output",
+            "--profile",
+            "--pick-profile",
+            "--append",
+            "--window",
+            "--workspace",
+            "--geometry-relative",
+            "--last-region",
+        ],
+        changes_state: true,
+        run: run_start_action,
+    },
+    Action {
+        name: "stop",
+        tray_args: Some(&["stop", "--all"]),
+        flags: &["--all", "--tag"],
+        changes_state: true,
+        run: run_stop_action,
+    },
+    Action {
+        name: "pause",
+        tray_args: Some(&["pause"]),
+        flags: &[],
+        changes_state: true,
+        run: run_pause_action,
+    },
+    Action {
+        name: "mark",
+        tray_args: Some(&["mark"]),
+        flags: &[],
+        changes_state: true,
+        run: run_mark_action,
+    },
+    Action {
+        name: "zoom-in",
+        tray_args: Some(&["zoom-in"]),
+        flags: &[],
+        changes_state: false,
+        run: run_zoom_in_action,
+    },
+    Action {
+        name: "zoom-out",
+        tray_args: Some(&["zoom-out"]),
+        flags: &[],
+        changes_state: false,
+        run: run_zoom_out_action,
+    },
+    Action {
+        name: "annotate",
+        tray_args: Some(&["annotate"]),
+        flags: &["--clear"],
+        changes_state: false,
+        run: run_annotate_action,
+    },
+    Action {
+        name: "delete-last",
+        tray_args: Some(&["delete-last"]),
+        flags: &[],
+        changes_state: false,
+        run: run_delete_last_action,
+    },
+    Action {
+        name: "compress-last",
+        tray_args: Some(&["compress-last"]),
+        flags: &["--size"],
+        changes_state: false,
+        run: run_compress_last_action,
+    },
+    Action {
+        name: "session",
+        tray_args: None,
+        flags: &["start", "end"],
+        changes_state: false,
+        run: run_session_action,
+    },
+    Action {
+        name: "status",
+        tray_args: Some(&["status"]),
+        flags: &[],
+        changes_state: false,
+        run: run_status_action,
+    },
+    Action {
+        name: "settings",
+        tray_args: Some(&["settings"]),
+        flags: &[],
+        changes_state: false,
+        run: run_settings_action,
+    },
+    Action {
+        name: "settings-gui",
+        tray_args: None,
+        flags: &[],
+        changes_state: false,
+        run: run_settings_gui_action,
+    },
+    Action {
+        name: "headless",
+        tray_args: None,
+        flags: &["--display", "--geometry", "--duration", "--output"],
+        changes_state: false,
+        run: run_headless_action,
+    },
+    Action {
+        name: "daemon",
+        tray_args: None,
+        flags: &[],
+        changes_state: false,
+        run: run_daemon_action,
+    },
+    Action {
+        name: "supervise",
+        tray_args: None,
+        flags: &[],
+        changes_state: false,
+        run: run_supervise_action,
+    },
+    Action {
+        name: "upload-worker",
+        tray_args: None,
+        flags: &[],
+        changes_state: false,
+        run: run_upload_worker_action,
+    },
+    Action {
+        name: "test-audio",
+        tray_args: None,
+        flags: &["--no-play"],
+        changes_state: false,
+        run: run_test_audio_action,
+    },
+    Action {
+        name: "trim-silence",
+        tray_args: None,
+        flags: &["--mid"],
+        changes_state: false,
+        run: run_trim_silence_action,
+    },
+    Action {
+        name: "concat",
+        tray_args: None,
+        flags: &["-o", "--output"],
+        changes_state: false,
+        run: run_concat_action,
+    },
+    Action {
+        name: "convert",
+        tray_args: None,
+        flags: &["--to", "--scale"],
+        changes_state: false,
+        run: run_convert_action,
+    },
+    Action {
+        name: "retime",
+        tray_args: None,
+        flags: &["--speed", "--from", "--to", "-o", "--output"],
+        changes_state: false,
+        run: run_retime_action,
+    },
+    Action {
+        name: "frames",
+        tray_args: None,
+        flags: &["--every", "--at", "-o", "--output"],
+        changes_state: false,
+        run: run_frames_action,
+    },
+    Action {
+        name: "completions",
+        tray_args: None,
+        flags: &["bash", "zsh", "fish"],
+        changes_state: false,
+        run: run_completions_action,
+    },
+    Action {
+        name: "manifest",
+        tray_args: None,
+        flags: &[],
+        changes_state: false,
+        run: run_manifest_action,
+    },
+];
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--json-events") {
+        args.remove(index);
+        events::enable();
+    }
+    let name = match args.first().map(String::as_str) {
+        Some("--daemon") => "daemon",
+        Some(name) => name,
+        None => "record",
+    };
+    let rest = args.get(1..).unwrap_or_default();
+    let action = ACTIONS.iter().find(|action| action.name == name);
+    let result = match action {
+        Some(action) => (action.run)(rest),
+        None => Err(Failure::Usage(format!("Unknown action: {}", name)).into()),
+    };
+    if action.is_some_and(|action| action.changes_state) {
+        tray::publish_status(&recorder::status_report());
+    }
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            events::emit(Event::Failed {
+                error: format!("{:#}", error),
+            });
+            eprintln!("{:#}", error);
+            if let Some((title, body)) = error
+                .downcast_ref::<Failure>()
+                .and_then(Failure::notification)
+            {
+                platform::show_notification_with_actions(
+                    title,
+                    &body,
+                    4000,
+                    &[(
+                        "Settings",
+                        NotificationAction::OpenUrl(tray::settings_url()),
+                    )],
+                );
+            }
+            ExitCode::from(failure::exit_code(&error))
+        }
+    }
+}
+
+/// Settings for this run: those locked by an open session, else the
+/// config file, overridden by the running qol-tray's copy when allowed.
+fn load_config() -> Config {
+    let config = if let Some(mut locked) = session::locked_config() {
+        tray::use_tray_url(&locked.tray.url);
+        // The snapshot leaves secrets out; they always come from the file.
+        let file: Config = qol_config::load_plugin_config(&["plugin-screen-recorder"]);
+        locked.hooks.slack_webhook = file.hooks.slack_webhook;
+        locked.hooks.matrix_token = file.hooks.matrix_token;
+        locked.remote.token = file.remote.token;
+        locked
+    } else {
+        let config: Config = qol_config::load_plugin_config(&["plugin-screen-recorder"]);
+        tray::use_tray_url(&config.tray.url);
+        if config.tray.live_settings {
+            tray::fetch_config().unwrap_or_else(|error| {
+                eprintln!("{:#}; using the config file", error);
+                config
+            })
+        } else {
+            config
+        }
+    };
+    platform::set_notification_level(config.record.notifications);
+    config
+}
+
+fn run_record_action(args: &[String]) -> Result<()> {
+    let active = recorder::active_recordings();
+    if active.is_empty() && state::finalizing() {
+        return queue_start(args);
+    }
+    if active.is_empty() {
+        return start_recording(
+            parse_output_arg(args)?,
+            parse_profile_arg(args)?,
+            parse_target_arg(args),
+            args.iter().any(|arg| arg == "--append"),
+        );
+    }
+    let config = load_config();
+    if config.record.cancel_window_ms > 0 && !await_second_press(config.record.cancel_window_ms)? {
+        return Ok(());
+    }
+    for recording in &active {
+        match config.record.toggle_behavior {
+            ToggleBehavior::Stop => stop_with_tags(recording, Vec::new(), &config)?,
+            ToggleBehavior::Pause => recorder::toggle_pause(recording, &config)?,
+            ToggleBehavior::Mark => recorder::mark(recording, None)?,
+        }
+    }
+    Ok(())
+}
+
+/// Holds a start pressed while the previous recording is still being
+/// finalized; the stopping process launches it once the file is saved.
+/// Pressing again withdraws it.
+fn queue_start(args: &[String]) -> Result<()> {
+    if state::take_queued_start().is_some() {
+        platform::show_status("Queued recording cancelled", "Nothing will start", 1500);
+        return Ok(());
+    }
+    state::queue_start(args)?;
+    platform::show_status(
+        "Recording queued",
+        "Starts once the last recording is saved",
+        1500,
+    );
+    // The stop may have finished between the check and the queueing.
+    if !state::finalizing() {
+        recorder::launch_queued_start();
+    }
+    Ok(())
+}
+
+/// Arbitrates the double-press gesture. A press arriving within the window of
+/// a pending press cancels every active recording; otherwise the press waits
+/// out the window and returns `true` when no second press claimed it.
+fn await_second_press(window_ms: u64) -> Result<bool> {
+    let now = Local::now().timestamp_millis();
+    if let Some(pending) = state::pending_press() {
+        if now - pending <= window_ms as i64 {
+            state::clear_pending_press();
+            for recording in &recorder::active_recordings() {
+                recorder::cancel(recording)?;
+            }
+            return Ok(false);
+        }
+    }
+
+    state::set_pending_press(now)?;
+    thread::sleep(Duration::from_millis(window_ms));
+    if state::pending_press() != Some(now) {
+        return Ok(false);
+    }
+    state::clear_pending_press();
+    Ok(true)
+}
+
+fn run_start_action(args: &[String]) -> Result<()> {
+    if recorder::active_recordings().is_empty() && state::finalizing() {
+        return queue_start(args);
+    }
+    start_recording(
+        parse_output_arg(args)?,
+        parse_profile_arg(args)?,
+        parse_target_arg(args),
+        args.iter().any(|arg| arg == "--append"),
+    )
+}
+
+/// Reads `--profile NAME`, or asks for one from the menu with
+/// `--pick-profile`.
+fn parse_profile_arg(args: &[String]) -> Result<Option<String>> {
+    if args.iter().any(|arg| arg == "--pick-profile") {
+        return pick_profile(&load_config()).map(Some);
+    }
+    let Some(index) = args.iter().position(|arg| arg == "--profile") else {
+        return Ok(None);
+    };
+    let name = args
+        .get(index + 1)
+        .ok_or_else(|| Failure::Usage("--profile needs a value".to_string()))?;
+    Ok(Some(name.clone()))
+}
+
+/// Shows the configured profiles in `record.menu_command`. Dismissing the
+/// menu cancels like dismissing the selection.
+fn pick_profile(config: &Config) -> Result<String> {
+    let names: Vec<String> = config.profiles.keys().cloned().collect();
+    if names.is_empty() {
+        return Err(Failure::Usage("no profiles configured to pick from".to_string()).into());
+    }
+    platform::pick_from_menu(&config.record.menu_command, &names)?
+        .ok_or_else(|| Failure::SelectionCancelled.into())
+}
+
+/// Reads `--window [NAME]`, `--workspace [NAME]`, `--geometry-relative
+/// OUTPUT:X,Y,W,H` or `--last-region`; without a name the focused window or
+/// workspace is recorded.
+fn parse_target_arg(args: &[String]) -> Option<(CaptureMode, String)> {
+    if args.iter().any(|arg| arg == "--last-region") {
+        return Some((CaptureMode::Last, String::new()));
+    }
+    let index = args.iter().position(|arg| {
+        arg == "--window" || arg == "--workspace" || arg == "--geometry-relative"
+    })?;
+    let mode = match args[index].as_str() {
+        "--window" => CaptureMode::Window,
+        "--workspace" => CaptureMode::Workspace,
+        _ => CaptureMode::Relative,
+    };
+    let name = args
+        .get(index + 1)
+        .filter(|name| !name.starts_with('-'))
+        .cloned()
+        .unwrap_or_default();
+    Some((mode, name))
+}
+
+/// Reads `-o PATH` / `--output PATH`. `-` streams the encoded video to
+/// stdout and keeps the invocation in the foreground until it stops.
+fn parse_output_arg(args: &[String]) -> Result<Option<PathBuf>> {
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        if arg == "-o" || arg == "--output" {
+            let raw = rest
+                .next()
+                .ok_or_else(|| anyhow!("{} needs a value", arg))?;
+            if raw == "-" {
+                return Ok(Some(PathBuf::from(raw)));
+            }
+            // Later invocations post-process the file from other directories.
+            let cwd = env::current_dir().context("failed to read working directory")?;
+            return Ok(Some(cwd.join(raw)));
+        }
+    }
+    Ok(None)
+}
+
+/// Selects an area and starts capturing it. With `append`, the take is
+/// joined onto the most recent recording once it stops.
+fn start_recording(
+    output: Option<PathBuf>,
+    mut profile: Option<String>,
+    target: Option<(CaptureMode, String)>,
+    append: bool,
+) -> Result<()> {
+    let append_to = if append {
+        let last = state::last_recording()
+            .filter(|path| path.exists())
+            .ok_or_else(|| anyhow!("no finished recording to append to"))?;
+        Some(last)
+    } else {
+        None
+    };
+    let mut config = load_config();
+    let (mut mode, mut monitor_number, mut target_name) = (CaptureMode::Region, 0, String::new());
+    if let Some(name) = &profile {
+        config = config.with_profile(name)?;
+        let preset = &config.profiles[name.as_str()];
+        (mode, monitor_number) = (preset.mode, preset.monitor);
+        target_name = preset.target.clone();
+    }
+    if let Some((target_mode, name)) = target {
+        (mode, target_name) = (target_mode, name);
+    }
+    if container::is_image_sequence(&config.video.format) {
+        // Frames have nowhere to carry sound.
+        config.audio.enabled = false;
+    }
+    check_codecs(&config, output.as_deref())?;
+    if let Some(output) = &output {
+        if let Some(busy) = recorder::active_recordings()
+            .iter()
+            .find(|recording| recording.destination() == output)
+        {
+            return Err(Failure::AlreadyRecording(busy.destination().to_path_buf()).into());
+        }
+    }
+    if !config.capture.display.is_empty() {
+        // slop, xrandr and xdpyinfo must look at the display being recorded.
+        env::set_var("DISPLAY", &config.capture.display);
+    }
+    let display = config.capture.resolved_display();
+    if let Some(display) =
+        platform::pointer_screen().and_then(|screen| display_on_screen(&display, screen))
+    {
+        // Separate X screens each have their own coordinates; select and
+        // grab on the one the pointer is on, not the display's default.
+        env::set_var("DISPLAY", &display);
+        config.capture.display = display;
+    }
+    preflight::check(&config, output.as_deref())?;
+    let mut rect = match capture_area(mode, monitor_number, &target_name, &config.selection)? {
+        Some(region) => region,
+        None => return Err(Failure::SelectionCancelled.into()),
+    };
+
+    if profile.is_none() {
+        if let Some(name) = app_profile(&config, rect) {
+            config = config.with_profile(&name)?;
+            check_codecs(&config, output.as_deref())?;
+            profile = Some(name);
+        }
+    }
+
+    let selected = rect;
+    let mut adjustments = Vec::new();
+    let monitor = monitor_for_selection(rect);
+    config
+        .video
+        .pin_framerate(monitor.as_ref().and_then(|monitor| monitor.refresh_hz))?;
+    let monitor_name = monitor.as_ref().and_then(|monitor| monitor.name.clone());
+    let bounds = match monitor {
+        Some(monitor) => monitor.bounds(),
+        None => platform::full_screen_bounds()?.bounds(),
+    };
+    rect = geometry::clamp_to_bounds(rect, &bounds);
+    if rect != selected {
+        adjustments.push("clamped to the screen");
+    }
+    if let Some(snapped) = geometry::snap_to_bottom(rect, &bounds) {
+        rect = snapped;
+        adjustments.push("snapped to the screen bottom");
+    }
+
+    let min_size = config.selection.min_size;
+    if rect.w < min_size || rect.h < min_size {
+        platform::show_notification(
+            "Selection too small",
+            &format!(
+                "{}x{} is under {}x{}; drag out a larger area",
+                rect.w, rect.h, min_size, min_size
+            ),
+            1500,
+        );
+        return Err(Failure::SelectionCancelled.into());
+    }
+
+    if rect.w <= 0 || rect.h <= 0 {
+        platform::show_notification(
+            "Recording failed",
+            &format!("Invalid area: {}x{}", rect.w, rect.h),
+            1200,
+        );
+        return Err(anyhow!("invalid recording area {}x{}", rect.w, rect.h));
+    }
+
+    let even = geometry::trim_to_even(rect);
+    if even != rect {
+        rect = even;
+        adjustments.push("trimmed to an even size");
+    }
+
+    check_never_record(&config, rect)?;
+
+    let show_preview = match config.record.preview {
+        PreviewMode::Off => false,
+        PreviewMode::Adjusted => !adjustments.is_empty(),
+        PreviewMode::Always => true,
+    };
+    if show_preview && !platform::preview_selection(rect, &adjustments, config.record.preview_secs)?
+    {
+        return Err(Failure::SelectionCancelled.into());
+    }
+
+    // An explicit output names its own container.
+    let auto_format = output.is_none() && config.video.format == container::AUTO;
+    let take = session::claim_take()?;
+    let output_file = match output {
+        Some(path) => path,
+        None => output_file_path(
+            &config,
+            monitor_name
+                .as_deref()
+                .filter(|_| mode == CaptureMode::Monitor),
+            take.as_ref(),
+        )?,
+    };
+    let to_stdout = output_file == Path::new("-");
+    if to_stdout {
+        events::divert_to_stderr();
+    }
+    events::emit(Event::Selected { rect });
+    if config.record.start_notice == StartNotice::Before && platform::routine_notifications_shown()
+    {
+        platform::show_status(
+            "Recording starts now",
+            "Press your hotkey to stop",
+            START_NOTICE_MS,
+        );
+        thread::sleep(Duration::from_millis(u64::from(START_NOTICE_MS)) + START_NOTICE_FADE);
+    }
+    let mut recording = recorder::start(rect, &config, output_file, profile, monitor_name)?;
+    let workspace = if mode == CaptureMode::Workspace && config.record.workspace_auto_pause {
+        Some(match target_name.as_str() {
+            "" => platform::focused_workspace()?,
+            name => name.to_string(),
+        })
+    } else {
+        None
+    };
+    if append_to.is_some() || workspace.is_some() || auto_format || take.is_some() {
+        recording.append_to = append_to;
+        recording.workspace = workspace;
+        recording.auto_format = auto_format;
+        recording.take = take;
+        state::save(&recording)?;
+    }
+    thread::sleep(Duration::from_millis(500));
+    if config.record.start_notice == StartNotice::Frame {
+        wait_for_first_frame(&recording);
+    }
+
+    if platform::process_alive(recording.pid) {
+        if matches!(
+            config.record.start_notice,
+            StartNotice::After | StartNotice::Frame
+        ) {
+            platform::show_status(
+                "Recording started",
+                "Press your hotkey to stop",
+                START_NOTICE_MS,
+            );
+        }
+        if config.record.watchdog
+            || config.record.stop_button
+            || recording.workspace.is_some()
+            || config.record.idle_timeout_mins > 0
+            || config.spotlight.enabled
+            || config.post.click_sounds
+        {
+            supervisor::spawn(recording.id)?;
+        }
+    } else {
+        state::clear(recording.id);
+        let log_path = platform::capture_log(recording.id);
+        let log = fs::read_to_string(&log_path).unwrap_or_default();
+        if log.contains("No space left on device") {
+            return Err(Failure::DiskFull.into());
+        }
+        return Err(Failure::EncoderFailed(log_path).into());
+    }
+
+    events::emit(Event::Started {
+        id: recording.id,
+        output: recording.destination().to_path_buf(),
+    });
+    if to_stdout || events::enabled() {
+        follow_recording(&recording);
+    }
+    Ok(())
+}
+
+/// Keeps the invocation attached until the recording ends, whether another
+/// invocation stops it or the capture dies, reporting progress every second.
+fn follow_recording(recording: &RecordingState) {
+    tray::publish_status(&recorder::status_report());
+    while let Some(current) = recorder::active_recordings()
+        .into_iter()
+        .find(|active| active.id == recording.id)
+    {
+        events::emit(Event::Progress {
+            id: current.id,
+            elapsed_secs: recorder::footage_secs(&current),
+            paused: current.paused,
+        });
+        thread::sleep(Duration::from_secs(1));
+    }
+    events::emit(Event::Stopped {
+        id: recording.id,
+        output: recording.destination().to_path_buf(),
+    });
+}
+
+/// Result of a `headless` run, printed as JSON on stdout.
+#[derive(Debug, Serialize)]
+struct HeadlessReport {
+    output: PathBuf,
+    display: String,
+    rect: Rect,
+    duration_secs: f64,
+}
+
+/// Records a fixed region of a display for a fixed time without any user
+/// interaction, e.g. an Xvfb session driven by a browser test suite.
+fn run_headless_action(args: &[String]) -> Result<()> {
+    const USAGE: &str =
+        "usage: headless --display DISPLAY --geometry WxH+X+Y --duration SECS [--output FILE]";
+    let mut display = None;
+    let mut geometry = None;
+    let mut duration = None;
+    let mut output = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || rest.next().ok_or_else(|| anyhow!("{} needs a value", arg));
+        match arg.as_str() {
+            "--display" => display = Some(value()?.clone()),
+            "--geometry" => geometry = Some(value()?.clone()),
+            "--duration" => {
+                let raw = value()?;
+                let secs: f64 = raw
+                    .parse()
+                    .with_context(|| format!("invalid --duration value: {}", raw))?;
+                duration = Some(secs);
+            }
+            "--output" => output = Some(PathBuf::from(value()?)),
+            other => {
+                return Err(Failure::Usage(format!(
+                    "unknown headless option: {}\n{}",
+                    other, USAGE
+                ))
+                .into())
+            }
+        }
+    }
+    let geometry = geometry.ok_or_else(|| Failure::Usage(USAGE.to_string()))?;
+    let duration = duration
+        .filter(|secs| *secs > 0.0)
+        .ok_or_else(|| Failure::Usage(USAGE.to_string()))?;
+    let monitor = geometry::parse_x_geometry(&geometry)
+        .ok_or_else(|| anyhow!("invalid --geometry value: {}", geometry))?;
+    let rect = geometry::trim_to_even(monitor.bounds());
+    if rect.w <= 0 || rect.h <= 0 {
+        return Err(anyhow!("invalid recording area {}x{}", rect.w, rect.h));
+    }
+
+    platform::mute_notifications();
+    let mut config = load_config();
+    if let Some(display) = display {
+        // xrandr and the other display tools must see the headless server,
+        // not the desktop this was started from.
+        env::set_var("DISPLAY", &display);
+        config.capture.display = display;
+    }
+    if container::is_image_sequence(&config.video.format) {
+        config.audio.enabled = false;
+    }
+    config
+        .video
+        .pin_framerate(monitor_for_selection(rect).and_then(|monitor| monitor.refresh_hz))?;
+    let output = match output {
+        Some(path) if path == Path::new("-") => {
+            return Err(anyhow!(
+                "headless prints its report on stdout; stream through a FIFO instead"
+            ));
+        }
+        Some(path) => path,
+        None => output_file_path(&config, None, None)?,
+    };
+    check_codecs(&config, Some(&output))?;
+
+    let recording = recorder::start(rect, &config, output, None, None)?;
+    let started = Instant::now();
+    let deadline = Duration::from_secs_f64(duration);
+    while started.elapsed() < deadline {
+        if !platform::process_alive(recording.pid) {
+            state::clear(recording.id);
+            platform::release_audio(&recording.audio);
+            return Err(anyhow!(
+                "capture process exited early; see {}",
+                platform::capture_log(recording.id).display()
+            ));
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    let duration_secs = started.elapsed().as_secs_f64();
+    recorder::stop(&recording, &config)?;
+
+    let report = HeadlessReport {
+        output: recording.destination().to_path_buf(),
+        display: config.capture.resolved_display(),
+        rect,
+        duration_secs,
+    };
+    let json = serde_json::to_string(&report).context("failed to encode headless report")?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn run_stop_action(args: &[String]) -> Result<()> {
+    const USAGE: &str = "usage: stop [--all | ID] [--tag TAG]...";
+    let mut all = false;
+    let mut id = None;
+    let mut tags = Vec::new();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--all" => all = true,
+            "--tag" => {
+                let raw = rest
+                    .next()
+                    .ok_or_else(|| Failure::Usage(USAGE.to_string()))?;
+                tags.extend(recorder::parse_tags(raw));
+            }
+            raw => {
+                id = Some(
+                    raw.parse::<u32>()
+                        .with_context(|| format!("invalid recording ID: {}", raw))?,
+                )
+            }
+        }
+    }
+
+    let active = recorder::active_recordings();
+    let targets: Vec<&RecordingState> = match id {
+        _ if all => active.iter().collect(),
+        Some(id) => {
+            let recording = active
+                .iter()
+                .find(|recording| recording.id == id)
+                .ok_or_else(|| anyhow!("no recording with ID {}", id))?;
+            vec![recording]
+        }
+        None => active
+            .iter()
+            .max_by_key(|recording| recording.started_at)
+            .into_iter()
+            .collect(),
+    };
+    if targets.is_empty() {
+        return Err(Failure::NotRecording.into());
+    }
+    let config = load_config();
+    for recording in targets {
+        stop_with_tags(recording, tags.clone(), &config)?;
+    }
+    Ok(())
+}
+
+/// Stops a recording the user asked to stop, tagging it with `tags` or, when
+/// none were given and `record.prompt_tags` is on, with tags typed into a
+/// prompt.
+fn stop_with_tags(recording: &RecordingState, tags: Vec<String>, config: &Config) -> Result<()> {
+    let mut recording = recording.clone();
+    recording.tags = tags;
+    if recording.tags.is_empty() && config.record.prompt_tags {
+        if let Some(raw) = platform::prompt_text("Tag recording", "Tags, e.g. a ticket ID:")? {
+            recording.tags = recorder::parse_tags(&raw);
+        }
+    }
+    recorder::stop(&recording, config)
+}
+
+fn run_pause_action(_args: &[String]) -> Result<()> {
+    let active = recorder::active_recordings();
+    if active.is_empty() {
+        return Err(Failure::NotRecording.into());
+    }
+    let config = load_config();
+    for recording in &active {
+        recorder::toggle_pause(recording, &config)?;
+    }
+    Ok(())
+}
+
+/// Marks a chapter in every active recording; `mark TEXT` attaches a note
+/// that titles the chapter and shows in the `.notes.srt` subtitles.
+fn run_mark_action(args: &[String]) -> Result<()> {
+    let active = recorder::active_recordings();
+    if active.is_empty() {
+        return Err(Failure::NotRecording.into());
+    }
+    let text = args.join(" ");
+    let text = Some(text.trim()).filter(|text| !text.is_empty());
+    for recording in &active {
+        recorder::mark(recording, text)?;
+    }
+    Ok(())
+}
+
+fn run_zoom_in_action(_args: &[String]) -> Result<()> {
+    run_zoom(zoom::zoom_in)
+}
+
+fn run_zoom_out_action(_args: &[String]) -> Result<()> {
+    run_zoom(zoom::zoom_out)
+}
+
+fn run_zoom(apply: fn(&RecordingState, &ZoomConfig) -> Result<()>) -> Result<()> {
+    let config = load_config();
+    if !config.zoom.enabled {
+        return Err(anyhow!("zoom.enabled is off; turn it on before recording"));
+    }
+    let recording = recorder::active_recordings()
+        .into_iter()
+        .max_by_key(|recording| recording.started_at)
+        .ok_or(Failure::NotRecording)?;
+    apply(&recording, &config.zoom)
+}
+
+/// Toggles drawing on screen. Annotations are part of the screen, so the
+/// capture picks them up like any other window.
+fn run_annotate_action(args: &[String]) -> Result<()> {
+    match args.first().map(String::as_str) {
+        Some("--clear") => platform::clear_annotations(),
+        Some(other) => Err(Failure::Usage(format!("unknown annotate option: {}", other)).into()),
+        None => platform::toggle_annotation(),
+    }
+}
+
+/// Moves the most recent recording and its subtitle and sidecar files to
+/// the trash, so a bad take is gone without losing the chance to undo.
+fn run_delete_last_action(_args: &[String]) -> Result<()> {
+    let last = state::last_recording()
+        .filter(|path| path.exists())
+        .ok_or_else(|| anyhow!("no finished recording to delete"))?;
+    platform::trash(&last)?;
+    for companion in [
+        last.with_extension("srt"),
+        recorder::notes_path(&last),
+        sidecar::path_for(&last),
+    ] {
+        if companion.exists() {
+            platform::trash(&companion)?;
+        }
+    }
+    state::clear_last_recording();
+    platform::show_notification(
+        "Recording deleted",
+        &format!("Moved {} to the trash", last.display()),
+        2000,
+    );
+    Ok(())
+}
+
+/// Writes `<name>-compressed.mp4` next to the most recent recording, sized
+/// to fit `--size MB` or `compress.target_mb`.
+fn run_compress_last_action(args: &[String]) -> Result<()> {
+    let mut target_mb = load_config().compress.target_mb;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--size" => {
+                let raw = rest.next().ok_or_else(|| {
+                    Failure::Usage("usage: compress-last [--size MB]".to_string())
+                })?;
+                target_mb = raw
+                    .parse()
+                    .with_context(|| format!("invalid --size value: {}", raw))?;
+            }
+            other => {
+                return Err(
+                    Failure::Usage(format!("unknown compress-last option: {}", other)).into(),
+                )
+            }
+        }
+    }
+    let last = state::last_recording()
+        .filter(|path| path.exists())
+        .ok_or_else(|| anyhow!("no finished recording to compress"))?;
+    let stem = last
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let directory = last.parent().unwrap_or(Path::new("."));
+    let output = unique_path(directory, &format!("{}-compressed", stem), "mp4");
+    platform::show_status(
+        "Compressing recording",
+        &format!("Fitting {} into {} MB", last.display(), target_mb),
+        2000,
+    );
+    postprocess::compress_to_size(&last, &output, target_mb)?;
+    platform::show_notification(
+        "Recording compressed",
+        &format!("Saved to {}", output.display()),
+        2000,
+    );
+    println!("{}", output.display());
+    Ok(())
+}
+
+/// `session start NAME` numbers the following recordings as takes of NAME;
+/// `session end` closes it and lists the takes. Without arguments, shows
+/// the open session.
+fn run_session_action(args: &[String]) -> Result<()> {
+    const USAGE: &str = "usage: session [start NAME | end]";
+    match args.first().map(String::as_str) {
+        Some("start") => {
+            let name = args.get(1..).unwrap_or_default().join(" ");
+            let name = filename::sanitize(name.trim());
+            if name.is_empty() {
+                return Err(Failure::Usage(USAGE.to_string()).into());
+            }
+            session::begin(&name, &load_config())?;
+            platform::show_status(
+                "Session started",
+                &format!(
+                    "Recordings are takes of {}; settings are locked until it ends",
+                    name
+                ),
+                1500,
+            );
+        }
+        Some("end") => {
+            let ended = session::end()?;
+            let summary = session::summary(&ended);
+            println!("{}", summary);
+            platform::show_notification("Session ended", &summary, 6000);
+        }
+        Some(_) => return Err(Failure::Usage(USAGE.to_string()).into()),
+        None => match session::current() {
+            Some(open) => println!("{}", session::summary(&open)),
+            None => println!("No session is open"),
+        },
+    }
+    Ok(())
+}
+
+fn run_status_action(_args: &[String]) -> Result<()> {
+    let report = recorder::status_report();
+    let json = serde_json::to_string_pretty(&report).context("failed to encode status")?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn run_settings_action(_args: &[String]) -> Result<()> {
+    platform::open_settings()
+}
+
+#[cfg(unix)]
+fn run_daemon_action(_args: &[String]) -> Result<()> {
+    daemon::run()
+}
+
+#[cfg(not(unix))]
+fn run_daemon_action(_args: &[String]) -> Result<()> {
+    Err(anyhow!(
+        "plugin-screen-recorder: the daemon needs Unix domain sockets"
+    ))
+}
+
+fn run_supervise_action(args: &[String]) -> Result<()> {
+    let raw = args
+        .first()
+        .ok_or_else(|| Failure::Usage("usage: supervise <id>".to_string()))?;
+    let id = raw
+        .parse::<u32>()
+        .with_context(|| format!("invalid recording ID: {}", raw))?;
+    supervisor::run(id, &load_config())
+}
+
+fn run_upload_worker_action(_args: &[String]) -> Result<()> {
+    let config = load_config();
+    upload::run_worker(&config.upload, &config.hooks)
+}
+
+fn run_settings_gui_action(_args: &[String]) -> Result<()> {
+    settings_gui::run(&load_config())
+}
+
+/// Records a few seconds from the configured audio inputs, reports their
+/// levels and plays each one back, so device names can be checked before a
+/// long session.
+fn run_test_audio_action(args: &[String]) -> Result<()> {
+    const SECONDS: u32 = 3;
+    // A peak this low means the source is muted, idle or the wrong device.
+    const SILENT_DB: f64 = -60.0;
+    let play = !args.iter().any(|arg| arg == "--no-play");
+    let config = load_config();
+    let devices = platform::route_audio(&config.audio)?;
+    println!("Recording {}s of audio...", SECONDS);
+    let recorded = platform::record_audio_sample(&devices, SECONDS, &env::temp_dir());
+    platform::release_audio(&devices);
+    let recorded = recorded?;
+
+    let mut report = Vec::new();
+    for (label, file) in &recorded {
+        let (mean, peak) = postprocess::audio_levels(file)?;
+        let verdict = if peak < SILENT_DB { ", silent" } else { "" };
+        report.push(format!(
+            "{}: mean {:.1} dB, peak {:.1} dB{}",
+            label, mean, peak, verdict
+        ));
+    }
+    println!("{}", report.join("\n"));
+    platform::show_notification("Audio test", &report.join("\n"), 4000);
+
+    let playback = if play {
+        recorded.iter().try_for_each(|(label, file)| {
+            println!("Playing back {}...", label);
+            platform::play_audio(file)
+        })
+    } else {
+        Ok(())
+    };
+    for (_, file) in &recorded {
+        let _ = fs::remove_file(file);
+    }
+    playback
+}
+
+fn run_trim_silence_action(args: &[String]) -> Result<()> {
+    let mut config = load_config();
+    let mut file = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--mid" => {
+                let raw = rest.next().ok_or_else(|| anyhow!("--mid needs a value"))?;
+                config.post.trim_mid_silence_secs = raw
+                    .parse()
+                    .with_context(|| format!("invalid --mid value: {}", raw))?;
+            }
+            _ => file = Some(PathBuf::from(arg)),
+        }
+    }
+    let file =
+        file.ok_or_else(|| Failure::Usage("usage: trim-silence <file> [--mid SECS]".to_string()))?;
+    if postprocess::trim_silence(&file, &config.post, &config.video, &config.audio)? {
+        println!("Trimmed silence from {}", file.display());
+    } else {
+        println!("No silence to trim in {}", file.display());
+    }
+    Ok(())
+}
+
+/// Joins recordings in the given order without re-encoding.
+fn run_concat_action(args: &[String]) -> Result<()> {
+    const USAGE: &str = "usage: concat <file> <file>... [-o FILE]";
+    let output = parse_output_arg(args)?;
+    // The concat list resolves relative entries against its own folder.
+    let cwd = env::current_dir().context("failed to read working directory")?;
+    let mut files = Vec::new();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        if arg == "-o" || arg == "--output" {
+            rest.next();
+            continue;
+        }
+        files.push(cwd.join(arg));
+    }
+    if files.len() < 2 {
+        return Err(Failure::Usage(USAGE.to_string()).into());
+    }
+    if let Some(missing) = files.iter().find(|file| !file.is_file()) {
+        return Err(anyhow!("no such recording: {}", missing.display()));
+    }
+    postprocess::check_joinable(&files)?;
+
+    let output = match output {
+        Some(path) if path == Path::new("-") => {
+            return Err(Failure::Usage("concat cannot write to stdout".to_string()).into())
+        }
+        Some(path) => path,
+        None => {
+            let first = &files[0];
+            let stem = first
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let extension = first
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let directory = first.parent().unwrap_or(Path::new("."));
+            unique_path(directory, &format!("{}-joined", stem), &extension)
+        }
+    };
+    postprocess::concat(&files, &output)?;
+    println!("{}", output.display());
+    Ok(())
+}
+
+fn run_convert_action(args: &[String]) -> Result<()> {
+    const USAGE: &str = "usage: convert <file> --to apng|gif|mp4|webm [--scale WIDTH]";
+    let mut file = None;
+    let mut target = None;
+    let mut width = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || {
+            rest.next()
+                .ok_or_else(|| Failure::Usage(format!("{} needs a value\n{}", arg, USAGE)))
+        };
+        match arg.as_str() {
+            "--to" => {
+                let raw = value()?;
+                target = Some(postprocess::ConvertTarget::parse(raw).ok_or_else(|| {
+                    Failure::Usage(format!("cannot convert to {}\n{}", raw, USAGE))
+                })?);
+            }
+            "--scale" => {
+                let raw = value()?;
+                width = Some(
+                    raw.parse::<u32>()
+                        .with_context(|| format!("invalid --scale width: {}", raw))?,
+                );
+            }
+            _ => file = Some(PathBuf::from(arg)),
+        }
+    }
+    let (Some(file), Some(target)) = (file, target) else {
+        return Err(Failure::Usage(USAGE.to_string()).into());
+    };
+    if !file.is_file() {
+        return Err(anyhow!("no such recording: {}", file.display()));
+    }
+
+    let stem = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let directory = file.parent().unwrap_or(Path::new("."));
+    let output = unique_path(directory, &stem, target.extension());
+    let config = load_config();
+    postprocess::convert(&file, &output, target, width, &config.video, &config.gif)?;
+    println!("{}", output.display());
+    Ok(())
+}
+
+fn run_retime_action(args: &[String]) -> Result<()> {
+    const USAGE: &str = "usage: retime <file> --speed FACTOR [--from SECS] [--to SECS] [-o FILE]";
+    let output = parse_output_arg(args)?;
+    let mut file = None;
+    let mut speed = None;
+    let mut from = None;
+    let mut to = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let mut number = || -> Result<f64> {
+            let raw = rest
+                .next()
+                .ok_or_else(|| Failure::Usage(format!("{} needs a value\n{}", arg, USAGE)))?;
+            // `inf` and `nan` parse too, and would never settle into an
+            // atempo chain.
+            raw.parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| {
+                    Failure::Usage(format!("invalid {} value: {}\n{}", arg, raw, USAGE)).into()
+                })
+        };
+        match arg.as_str() {
+            "--speed" => speed = Some(number()?),
+            "--from" => from = Some(number()?),
+            "--to" => to = Some(number()?),
+            "-o" | "--output" => {
+                rest.next();
+            }
+            _ => file = Some(PathBuf::from(arg)),
+        }
+    }
+    let (Some(file), Some(speed)) = (file, speed) else {
+        return Err(Failure::Usage(USAGE.to_string()).into());
+    };
+    if speed <= 0.0 {
+        return Err(Failure::Usage(format!("--speed must be positive\n{}", USAGE)).into());
+    }
+    if let (Some(from), Some(to)) = (from, to) {
+        if to <= from {
+            return Err(Failure::Usage(format!("--to must come after --from\n{}", USAGE)).into());
+        }
+    }
+    if !file.is_file() {
+        return Err(anyhow!("no such recording: {}", file.display()));
+    }
+
+    let output = match output {
+        Some(path) if path == Path::new("-") => {
+            return Err(Failure::Usage("retime cannot write to stdout".to_string()).into())
+        }
+        Some(path) => path,
+        None => {
+            let stem = file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let extension = file
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let directory = file.parent().unwrap_or(Path::new("."));
+            unique_path(directory, &format!("{}-{}x", stem, speed), &extension)
+        }
+    };
+    let config = load_config();
+    postprocess::retime(
+        &file,
+        &output,
+        speed,
+        from,
+        to,
+        &config.video,
+        &config.audio,
+    )?;
+    println!("{}", output.display());
+    Ok(())
+}
+
+/// Dumps PNG stills from a recording, either at a fixed interval or at
+/// given timestamps, into `-o DIR` or a folder next to the recording.
+fn run_frames_action(args: &[String]) -> Result<()> {
+    const USAGE: &str = "usage: frames <file> (--every 1s | --at 00:01:23...) [-o DIR]";
+    let directory = parse_output_arg(args)?;
+    let mut file = None;
+    let mut every = None;
+    let mut at = Vec::new();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || {
+            rest.next()
+                .ok_or_else(|| Failure::Usage(format!("{} needs a value\n{}", arg, USAGE)))
+        };
+        match arg.as_str() {
+            "--every" => {
+                let raw = value()?;
+                every = Some(
+                    parse_interval(raw)
+                        .filter(|secs| *secs > 0.0)
+                        .ok_or_else(|| anyhow!("invalid --every value: {}", raw))?,
+                );
+            }
+            "--at" => {
+                let raw = value()?;
+                at.push(
+                    parse_timestamp(raw).ok_or_else(|| anyhow!("invalid --at value: {}", raw))?,
+                );
+            }
+            "-o" | "--output" => {
+                value()?;
+            }
+            _ => file = Some(PathBuf::from(arg)),
+        }
+    }
+    let Some(file) = file else {
+        return Err(Failure::Usage(USAGE.to_string()).into());
+    };
+    if every.is_none() && at.is_empty() {
+        return Err(Failure::Usage(USAGE.to_string()).into());
+    }
+    if !file.is_file() {
+        return Err(anyhow!("no such recording: {}", file.display()));
+    }
+
+    let stem = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let directory = directory.unwrap_or_else(|| {
+        file.parent()
+            .unwrap_or(Path::new("."))
+            .join(format!("{}-frames", stem))
+    });
+    if let Some(interval) = every {
+        postprocess::extract_frames_every(&file, &directory, interval)?;
+    }
+    if !at.is_empty() {
+        fs::create_dir_all(&directory).context("failed to create frames directory")?;
+    }
+    for secs in at {
+        let whole = secs as u64;
+        let name = format!(
+            "{}-{:02}-{:02}-{:02}",
+            stem,
+            whole / 3600,
+            whole % 3600 / 60,
+            whole % 60
+        );
+        let output = unique_path(&directory, &name, "png");
+        postprocess::extract_frame_at(&file, secs, &output)?;
+    }
+    println!("{}", directory.display());
+    Ok(())
+}
+
+/// Reads an interval like `1s`, `500ms`, `2m` or a bare number of seconds.
+fn parse_interval(raw: &str) -> Option<f64> {
+    let (number, scale) = if let Some(ms) = raw.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(secs) = raw.strip_suffix('s') {
+        (secs, 1.0)
+    } else if let Some(mins) = raw.strip_suffix('m') {
+        (mins, 60.0)
+    } else {
+        (raw, 1.0)
+    };
+    number.parse::<f64>().ok().map(|value| value * scale)
+}
+
+/// Reads `HH:MM:SS`, `MM:SS` or plain seconds, each allowing a fraction.
+fn parse_timestamp(raw: &str) -> Option<f64> {
+    raw.split(':').try_fold(0.0, |total, part| {
+        let value = part.parse::<f64>().ok().filter(|value| *value >= 0.0)?;
+        Some(total * 60.0 + value)
+    })
+}
+
+fn run_completions_action(args: &[String]) -> Result<()> {
+    let shell = args
+        .first()
+        .ok_or_else(|| Failure::Usage("usage: completions <bash|zsh|fish>".to_string()))?;
+    print!("{}", completions::script(shell, ACTIONS)?);
+    Ok(())
+}
+
+fn run_manifest_action(_args: &[String]) -> Result<()> {
+    println!("{}", manifest_actions());
+    Ok(())
+}
+
+/// The `[runtime] actions` line of `plugin.toml`.
+fn manifest_actions() -> String {
+    let entries: Vec<String> = ACTIONS
+        .iter()
+        .filter_map(|action| {
+            let args = action.tray_args?;
+            let quoted: Vec<String> = args.iter().map(|arg| format!("\"{}\"", arg)).collect();
+            Some(format!("{} = [{}]", action.name, quoted.join(", ")))
+        })
+        .collect();
+    format!("actions = {{ {} }}", entries.join(", "))
+}
+
+/// The area a recording covers: a dragged-out region, or a whole monitor or
+/// screen for profiles that ask for one. `None` when selection is cancelled.
+fn capture_area(
+    mode: CaptureMode,
+    monitor_number: usize,
+    target: &str,
+    selection: &SelectionConfig,
+) -> Result<Option<Rect>> {
+    let bounds = match mode {
+        CaptureMode::Region => {
+            let timeout =
+                (selection.timeout_secs > 0).then(|| Duration::from_secs(selection.timeout_secs));
+            return platform::select_region(timeout);
+        }
+        CaptureMode::Window => return platform::window_rect(target).map(Some),
+        CaptureMode::Workspace => return platform::workspace_rect(target).map(Some),
+        CaptureMode::Relative => return relative_area(target).map(Some),
+        CaptureMode::Last => {
+            return state::last_region()
+                .map(|region| Some(region.rect))
+                .ok_or_else(|| anyhow!("no earlier recording area to reuse"))
+        }
+        CaptureMode::Screen => platform::full_screen_bounds()?,
+        CaptureMode::Monitor if monitor_number == 0 => {
+            let (x, y) = platform::pointer_position()?;
+            monitor_for_selection(Rect { x, y, w: 0, h: 0 })
+                .ok_or_else(|| anyhow!("no monitor under the pointer"))?
+        }
+        CaptureMode::Monitor => platform::get_monitors()?
+            .get(monitor_number - 1)
+            .cloned()
+            .ok_or_else(|| anyhow!("monitor {} is not connected", monitor_number))?,
+    };
+    Ok(Some(bounds.bounds()))
+}
+
+/// Resolves `OUTPUT:X,Y,W,H` to virtual-desktop coordinates using where
+/// that monitor currently sits.
+fn relative_area(spec: &str) -> Result<Rect> {
+    let (output, region) = geometry::parse_relative_geometry(spec).ok_or_else(|| {
+        Failure::Usage(format!(
+            "invalid relative geometry {:?}; expected OUTPUT:X,Y,W,H",
+            spec
+        ))
+    })?;
+    let monitor = platform::get_monitors()?
+        .into_iter()
+        .find(|monitor| monitor.name.as_deref() == Some(output))
+        .ok_or_else(|| anyhow!("monitor {} is not connected", output))?;
+    let origin = monitor.bounds();
+    Ok(Rect {
+        x: origin.x + region.x,
+        y: origin.y + region.y,
+        ..region
+    })
+}
+
+/// Profile `app_profiles` assigns to the application whose window covers
+/// most of `rect`.
+fn app_profile(config: &Config, rect: Rect) -> Option<String> {
+    if config.app_profiles.is_empty() {
+        return None;
+    }
+    let windows = platform::stacked_windows().ok()?;
+    let class = dominant_window(&windows, rect)?;
+    config
+        .app_profiles
+        .iter()
+        .find(|(pattern, _)| class_matches(class, pattern))
+        .map(|(_, name)| name.clone())
+}
+
+/// Whether a WM_CLASS, or either half of it, is `pattern`.
+fn class_matches(class: &str, pattern: &str) -> bool {
+    class.eq_ignore_ascii_case(pattern)
+        || class
+            .split('.')
+            .any(|part| part.eq_ignore_ascii_case(pattern))
+}
+
+/// Warns about, or asks before recording, a selection that fully contains
+/// a window listed in `record.never_record`.
+fn check_never_record(config: &Config, rect: Rect) -> Result<()> {
+    if config.record.never_record.is_empty() {
+        return Ok(());
+    }
+    let Ok(windows) = platform::stacked_windows() else {
+        return Ok(());
+    };
+    let found = never_record_windows(&windows, rect, &config.record.never_record);
+    if found.is_empty() {
+        return Ok(());
+    }
+    let message = format!("The selection contains {}", found.join(", "));
+    if !config.record.never_record_confirm {
+        platform::show_notification("Sensitive window in view", &message, 3000);
+        return Ok(());
+    }
+    if !platform::confirm("Record anyway?", &message)? {
+        return Err(Failure::SelectionCancelled.into());
+    }
+    Ok(())
+}
+
+/// Classes of the `windows` lying entirely inside `rect` that match one of
+/// `classes`, each listed once.
+fn never_record_windows<'a>(
+    windows: &'a [(String, Rect)],
+    rect: Rect,
+    classes: &[String],
+) -> Vec<&'a str> {
+    let mut found: Vec<&str> = Vec::new();
+    for (class, window) in windows {
+        let inside = window.x >= rect.x
+            && window.y >= rect.y
+            && window.x + window.w <= rect.x + rect.w
+            && window.y + window.h <= rect.y + rect.h;
+        if inside
+            && classes.iter().any(|pattern| class_matches(class, pattern))
+            && !found.contains(&class.as_str())
+        {
+            found.push(class);
+        }
+    }
+    found
+}
+
+/// WM_CLASS of the window seen over most of `rect`: the topmost window at
+/// each point of a grid across it, counted. `windows` is bottom first.
+fn dominant_window(windows: &[(String, Rect)], rect: Rect) -> Option<&str> {
+    const GRID: i32 = 16;
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for row in 0..GRID {
+        for column in 0..GRID {
+            let x = rect.x + rect.w * (2 * column + 1) / (2 * GRID);
+            let y = rect.y + rect.h * (2 * row + 1) / (2 * GRID);
+            let Some((class, _)) = windows.iter().rev().find(|(_, window)| {
+                x >= window.x && x < window.x + window.w && y >= window.y && y < window.y + window.h
+            }) else {
+                continue;
+            };
+            match counts.iter_mut().find(|(seen, _)| *seen == class) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((class, 1)),
+            }
+        }
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(class, _)| class)
+}
+
+fn monitor_for_selection(rect: Rect) -> Option<Monitor> {
+    let monitors = platform::get_monitors().ok()?;
+    geometry::monitor_for(&monitors, rect).cloned()
+}
+
+/// Fails early when the configured encoders cannot be muxed into the file
+/// the recording would be written to.
+fn check_codecs(config: &Config, output: Option<&Path>) -> Result<()> {
+    if container::is_image_sequence(&config.video.format) {
+        if output.is_some_and(container::is_stream) {
+            return Err(anyhow!("a png-seq recording cannot be streamed"));
+        }
+        return Ok(());
+    }
+    let audio = state::AudioDevices::from_config(&config.audio).has_any();
+    container::check_codecs(
+        &container::target_container(output, &config.video.format),
+        &config.video.codec,
+        audio.then_some(config.audio.codec.encoder()),
+    )
+    .context("the video settings do not fit the output format")
+}
+
+/// Waits, up to `FIRST_FRAME_TIMEOUT`, until the capture has written
+/// something: muxers hold the header back until every stream has its first
+/// packet, so a non-empty file means the first frame is in.
+fn wait_for_first_frame(recording: &RecordingState) {
+    let started = Instant::now();
+    while started.elapsed() < FIRST_FRAME_TIMEOUT && platform::process_alive(recording.pid) {
+        let written = fs::metadata(&recording.capture_file)
+            .map(|meta| meta.is_dir() || meta.len() > 0)
+            .unwrap_or(false);
+        if written {
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Default file for a new recording, rendered from `output.name_template`.
+/// When a whole monitor is recorded and the template does not place the
+/// monitor itself, its name is appended so takes from each screen are told
+/// apart. A session take whose template does not place the session is
+/// named `SESSION-take-NN` instead.
+fn output_file_path(
+    config: &Config,
+    monitor: Option<&str>,
+    take: Option<&session::TakeRef>,
+) -> Result<PathBuf> {
+    let videos = config.output.resolved_directory()?;
+    let now = Local::now();
+    let (timestamp, date, time) = (
+        now.format("%F_%H-%M-%S").to_string(),
+        now.format("%F").to_string(),
+        now.format("%H-%M-%S").to_string(),
+    );
+    let take_label = take.map(|take| session::take_label(take.number));
+    let mut template = config.output.name_template.clone();
+    if take.is_some() && !template.contains("{session}") {
+        template = "{session}-{take}".to_string();
+    }
+    let mut relative = filename::render(
+        &template,
+        &[
+            ("timestamp", &timestamp),
+            ("date", &date),
+            ("time", &time),
+            ("monitor", monitor.unwrap_or_default()),
+            ("session", take.map_or("", |take| take.session.as_str())),
+            ("take", take_label.as_deref().unwrap_or_default()),
+        ],
+    )?;
+    if let Some(monitor) = monitor.filter(|_| !template.contains("{monitor}")) {
+        let name = relative.file_name().unwrap_or_default().to_string_lossy();
+        relative.set_file_name(format!("{}-{}", name, filename::sanitize(monitor)));
+    }
+    let path = videos.join(&relative);
+    let directory = path.parent().unwrap_or(&videos);
+    fs::create_dir_all(directory).context("failed to create output directory")?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let extension = if container::is_image_sequence(&config.video.format) {
+        ""
+    } else {
+        container::recording_format(&config.video.format)
+    };
+    Ok(unique_path(directory, &name, extension))
+}
+
+/// `directory/name.extension`, or the first free `name-N.extension` when
+/// that file exists or a running recording is headed for it. An empty
+/// extension names a directory.
+fn unique_path(directory: &Path, name: &str, extension: &str) -> PathBuf {
+    let reserved: Vec<PathBuf> = state::load_all()
+        .iter()
+        .map(|recording| recording.destination().to_path_buf())
+        .collect();
+    let taken = |path: &PathBuf| path.exists() || reserved.contains(path);
+    let file_name = |name: String| {
+        if extension.is_empty() {
+            name
+        } else {
+            format!("{}.{}", name, extension)
+        }
+    };
+    let candidate = directory.join(file_name(name.to_string()));
+    if !taken(&candidate) {
+        return candidate;
+    }
+    (1..)
+        .map(|n| directory.join(file_name(format!("{}-{}", name, n))))
+        .find(|candidate| !taken(candidate))
+        .unwrap_or(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use qol_tray::plugins::manifest::PluginManifest;
+
+    #[test]
+    fn validate_plugin_contract() {
+        PluginManifest::load_and_validate("plugin.toml").expect("plugin.toml invalid");
+        let manifest = std::fs::read_to_string("plugin.toml").expect("plugin.toml unreadable");
+        assert!(
+            manifest
+                .lines()
+                .any(|line| line == super::manifest_actions()),
+            "plugin.toml actions drifted; regenerate with `screen-recorder manifest`"
+        );
+    }
+
+    #[test]
+    fn display_follows_the_pointer_screen() {
+        assert_eq!(super::display_on_screen(":0", 1).as_deref(), Some(":0.1"));
+        assert_eq!(super::display_on_screen(":0.1", 0).as_deref(), Some(":0.0"));
+        assert_eq!(
+            super::display_on_screen("host:1.0", 2).as_deref(),
+            Some("host:1.2")
+        );
+        assert_eq!(super::display_on_screen(":0", 0), None);
+        assert_eq!(super::display_on_screen(":0.1", 1), None);
+    }
+
+    #[test]
+    fn lossless_audio_is_kept_out_of_mp4() {
+        let flac = super::AudioCodec::Flac.encoder();
+        assert!(crate::container::check_codecs("mp4", "libx264", Some(flac)).is_err());
+        assert!(crate::container::check_codecs("mkv", "libx264", Some(flac)).is_ok());
+        let pcm = super::AudioCodec::Pcm.encoder();
+        assert!(crate::container::check_codecs("mov", "libx264", Some(pcm)).is_ok());
+        let audio = super::AudioConfig {
+            codec: super::AudioCodec::Flac,
+            bitrate: "320k".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(audio.encoder_args(), ["-c:a", "flac"]);
+    }
+
+    #[test]
+    fn auto_format_picks_by_length_and_streams() {
+        use crate::container::auto_container;
+        assert_eq!(auto_container(5, 800, "libx264", None, 8), "gif");
+        assert_eq!(auto_container(5, 800, "libx264", Some("aac"), 8), "mp4");
+        assert_eq!(auto_container(30, 800, "libx264", None, 8), "mp4");
+        assert_eq!(auto_container(5, 1920, "libx264", None, 8), "mp4");
+        assert_eq!(auto_container(5, 800, "libx264", None, 0), "mp4");
+        assert_eq!(
+            auto_container(60, 800, "libvpx-vp9", Some("libvorbis"), 8),
+            "webm"
+        );
+        assert_eq!(auto_container(60, 800, "libx264", Some("flac"), 8), "mkv");
+    }
+
+    #[test]
+    fn dominant_window_is_the_one_seen_most() {
+        let rect = |x, y, w, h| super::Rect { x, y, w, h };
+        let windows = vec![
+            ("Navigator.firefox".to_string(), rect(0, 0, 1920, 1080)),
+            ("kitty.kitty".to_string(), rect(0, 0, 600, 400)),
+        ];
+        let selection = rect(0, 0, 1000, 1000);
+        assert_eq!(
+            super::dominant_window(&windows, selection),
+            Some("Navigator.firefox")
+        );
+        // The terminal sits on top, so it wins where it covers the selection.
+        assert_eq!(
+            super::dominant_window(&windows, rect(0, 0, 500, 300)),
+            Some("kitty.kitty")
+        );
+        assert_eq!(
+            super::dominant_window(&windows, rect(2000, 0, 100, 100)),
+            None
+        );
+    }
+
+    #[test]
+    fn never_record_needs_the_whole_window_inside() {
+        let rect = |x, y, w, h| super::Rect { x, y, w, h };
+        let windows = vec![
+            ("Navigator.firefox".to_string(), rect(0, 0, 1920, 1080)),
+            ("keepassxc.KeePassXC".to_string(), rect(100, 100, 400, 300)),
+        ];
+        let classes = vec!["keepassxc".to_string()];
+        assert_eq!(
+            super::never_record_windows(&windows, rect(0, 0, 800, 600), &classes),
+            vec!["keepassxc.KeePassXC"]
+        );
+        assert!(super::never_record_windows(&windows, rect(200, 0, 800, 600), &classes).is_empty());
+        assert!(super::never_record_windows(&windows, rect(0, 0, 800, 600), &[]).is_empty());
     }
 }
@@ -0,0 +1,189 @@
+//! Window and workspace geometry from the i3 or sway IPC socket, exact to
+//! the pixel where dragging out a region on a tiling layout is guesswork.
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use std::env;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::process::Command;
+
+use crate::Rect;
+
+const MAGIC: &[u8] = b"i3-ipc";
+const GET_WORKSPACES: u32 = 1;
+const GET_TREE: u32 = 4;
+
+/// Client area of the window whose title, app id, class or mark matches
+/// `target`, or of the focused window when `target` is empty. A match on a
+/// split container yields the whole container.
+pub fn window_rect(target: &str) -> Result<Rect> {
+    let tree = request(GET_TREE)?;
+    let needle = target.to_lowercase();
+    let node = if target.is_empty() {
+        find_node(&tree, &|node| node["focused"].as_bool() == Some(true))
+    } else {
+        find_node(&tree, &|node| marks(node).any(|mark| mark == target)).or_else(|| {
+            find_node(&tree, &|node| {
+                labels(node).any(|label| label.contains(&needle))
+            })
+        })
+    };
+    let node = node.ok_or_else(|| match target {
+        "" => anyhow!("no window is focused"),
+        _ => anyhow!("no window matches {}", target),
+    })?;
+    let outer = rect(&node["rect"])?;
+    match rect(&node["window_rect"]) {
+        // window_rect is relative to the container and leaves out borders and
+        // the title bar.
+        Ok(inner) if inner.w > 0 && inner.h > 0 => Ok(Rect {
+            x: outer.x + inner.x,
+            y: outer.y + inner.y,
+            w: inner.w,
+            h: inner.h,
+        }),
+        _ => Ok(outer),
+    }
+}
+
+/// Area of the workspace called `name`, or of the focused one when `name`
+/// is empty.
+pub fn workspace_rect(name: &str) -> Result<Rect> {
+    let workspaces = request(GET_WORKSPACES)?;
+    let workspace = workspaces
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|workspace| match name {
+            "" => workspace["focused"].as_bool() == Some(true),
+            name => workspace["name"].as_str() == Some(name),
+        })
+        .ok_or_else(|| match name {
+            "" => anyhow!("no workspace is focused"),
+            name => anyhow!("no workspace named {}", name),
+        })?;
+    rect(&workspace["rect"])
+}
+
+pub fn focused_workspace() -> Result<String> {
+    request(GET_WORKSPACES)?
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|workspace| workspace["focused"].as_bool() == Some(true))
+        .and_then(|workspace| workspace["name"].as_str())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("no workspace is focused"))
+}
+
+/// Whether the workspace called `name` is shown on any output.
+pub fn workspace_visible(name: &str) -> Result<bool> {
+    Ok(request(GET_WORKSPACES)?
+        .as_array()
+        .into_iter()
+        .flatten()
+        .any(|workspace| {
+            workspace["name"].as_str() == Some(name) && workspace["visible"].as_bool() == Some(true)
+        }))
+}
+
+/// Depth-first search over tiled and floating containers, skipping the
+/// root, outputs and workspaces themselves.
+fn find_node<'a>(node: &'a Value, matches: &dyn Fn(&Value) -> bool) -> Option<&'a Value> {
+    let kind = node["type"].as_str().unwrap_or_default();
+    if matches!(kind, "con" | "floating_con") && matches(node) {
+        return Some(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[*key].as_array())
+        .flatten()
+        .find_map(|child| find_node(child, matches))
+}
+
+fn marks(node: &Value) -> impl Iterator<Item = &str> {
+    node["marks"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+}
+
+/// Lowercased title, sway app id and X11 class of a container.
+fn labels(node: &Value) -> impl Iterator<Item = String> + '_ {
+    [
+        &node["name"],
+        &node["app_id"],
+        &node["window_properties"]["class"],
+        &node["window_properties"]["instance"],
+    ]
+    .into_iter()
+    .filter_map(Value::as_str)
+    .map(str::to_lowercase)
+}
+
+fn rect(value: &Value) -> Result<Rect> {
+    let field = |key: &str| {
+        value[key]
+            .as_i64()
+            .map(|number| number as i32)
+            .ok_or_else(|| anyhow!("IPC reply has no {} in its rect", key))
+    };
+    Ok(Rect {
+        x: field("x")?,
+        y: field("y")?,
+        w: field("width")?,
+        h: field("height")?,
+    })
+}
+
+/// Sends one empty-payload message and returns the parsed JSON reply.
+fn request(message_type: u32) -> Result<Value> {
+    let mut stream = UnixStream::connect(socket_path()?)
+        .context("failed to connect to the window manager IPC socket")?;
+    let mut message = MAGIC.to_vec();
+    message.extend_from_slice(&0u32.to_le_bytes());
+    message.extend_from_slice(&message_type.to_le_bytes());
+    stream
+        .write_all(&message)
+        .context("failed to send IPC request")?;
+
+    let mut header = [0u8; 14];
+    stream
+        .read_exact(&mut header)
+        .context("failed to read IPC reply")?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(anyhow!("unexpected IPC reply"));
+    }
+    let length = u32::from_le_bytes([header[6], header[7], header[8], header[9]]) as usize;
+    let mut payload = vec![0u8; length];
+    stream
+        .read_exact(&mut payload)
+        .context("failed to read IPC reply")?;
+    serde_json::from_slice(&payload).context("failed to parse IPC reply")
+}
+
+/// `$SWAYSOCK` or `$I3SOCK`, else whatever the running window manager
+/// reports.
+fn socket_path() -> Result<String> {
+    for variable in ["SWAYSOCK", "I3SOCK"] {
+        if let Ok(path) = env::var(variable) {
+            if !path.is_empty() {
+                return Ok(path);
+            }
+        }
+    }
+    for wm in ["i3", "sway"] {
+        let Ok(output) = Command::new(wm).arg("--get-socketpath").output() else {
+            continue;
+        };
+        let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !path.is_empty() {
+            return Ok(path);
+        }
+    }
+    Err(anyhow!(
+        "no i3 or sway IPC socket found; window and workspace capture need one of them"
+    ))
+}
@@ -1,27 +1,102 @@
 use anyhow::{anyhow, Context, Result};
-use std::fs::File;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::{Config, Monitor, Rect};
+use super::runner::{Runner, System};
+use super::NotificationAction;
+use crate::failure::{spawn_error, Failure};
+use crate::ffmpeg::FfmpegCommand;
+use crate::geometry::{parse_x_geometry, Rotation};
+use crate::settings_gui::SettingsForm;
+use crate::state::AudioDevices;
+use crate::{
+    container, spotlight, tray, zoom, AudioConfig, BluetoothMic, Config, EchoCancel, Monitor, Rect,
+};
 
-const SETTINGS_URL: &str = "http://127.0.0.1:42700/plugins/plugin-screen-recorder/";
+const FILTER_COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
+const APP_SINK: &str = "qol_rec_app";
+const EC_SOURCE: &str = "qol_rec_ec_mic";
+const EC_SINK: &str = "qol_rec_ec_out";
+/// Adaptive echo canceller: predicts the mic (input 1) from the system
+/// audio (input 2) and keeps the residual, so speaker bleed drops out of the
+/// voice before both are merged.
+const ECHO_FILTER_GRAPH: &str =
+    "[2:a]asplit[ref][sys];[ref][1:a]anlms=order=2048:mu=0.05:out=e[voice];[voice][sys]amerge=inputs=2[aout]";
 
-pub fn select_region() -> Result<Option<Rect>> {
-    let output = Command::new("slop")
-        .args([
-            "--highlight",
-            "--color=1,0,0,0.65",
-            "-b",
-            "0",
-            "-f",
-            "%x,%y,%w,%h",
-        ])
-        .output()
-        .context("failed to run slop")?;
+/// Noise gate that fully mutes system audio below `threshold_db` dBFS, or
+/// `None` when the gate is off.
+fn system_gate(threshold_db: f64) -> Option<String> {
+    (threshold_db < 0.0).then(|| {
+        format!(
+            "agate=threshold={:.6}:range=0:attack=5:release=300",
+            10f64.powf(threshold_db / 20.0)
+        )
+    })
+}
+
+/// Lets the user drag out a region with slop. `None` when the selection was
+/// cancelled with Escape or a right-click; running past `timeout` fails with
+/// [`Failure::SelectionTimedOut`].
+pub fn select_region(timeout: Option<Duration>) -> Result<Option<Rect>> {
+    select_region_with(&System, timeout)
+}
+
+fn select_region_with(runner: &dyn Runner, timeout: Option<Duration>) -> Result<Option<Rect>> {
+    let mut slop = Command::new("slop");
+    slop.args([
+        "--highlight",
+        "--color=1,0,0,0.65",
+        "-b",
+        "0",
+        "-f",
+        "%x,%y,%w,%h",
+    ])
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped());
+    let mut child = runner
+        .spawn(&mut slop)
+        .map_err(|error| spawn_error("slop", error))?;
+
+    let started = Instant::now();
+    while child
+        .try_wait()
+        .context("failed to wait for slop")?
+        .is_none()
+    {
+        if timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+            let _ = child.kill();
+            return Err(Failure::SelectionTimedOut.into());
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    let output = child
+        .wait_with_output()
+        .context("failed to read slop output")?;
 
     if !output.status.success() {
-        return Ok(None);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // slop exits 1 with this message when the user backs out.
+        if stderr.contains("cancelled") {
+            return Ok(None);
+        }
+        return Err(anyhow!(
+            "slop failed ({}): {}",
+            output
+                .status
+                .code()
+                .map_or("killed by a signal".to_string(), |code| format!(
+                    "exit {}",
+                    code
+                )),
+            stderr.trim()
+        ));
     }
 
     let raw = String::from_utf8_lossy(&output.stdout).trim().to_string();
@@ -32,16 +107,56 @@ pub fn select_region() -> Result<Option<Rect>> {
     parse_selection_geometry(&raw).map(Some)
 }
 
+/// Offers `options` one per line to a dmenu-style `command`, such as rofi
+/// or fuzzel, and returns the line it printed. `None` when dismissed.
+pub fn pick_from_menu(command: &str, options: &[String]) -> Result<Option<String>> {
+    pick_from_menu_with(&System, command, options)
+}
+
+fn pick_from_menu_with(
+    runner: &dyn Runner,
+    command: &str,
+    options: &[String],
+) -> Result<Option<String>> {
+    let script = format!("printf '%s\\n' \"$@\" | {}", command);
+    let output = runner
+        .output(
+            Command::new("sh")
+                .args(["-c", &script, "sh"])
+                .args(options)
+                .stdin(Stdio::null()),
+        )
+        .context("failed to run the menu command")?;
+    if output.status.code() == Some(127) {
+        return Err(Failure::Unavailable(format!("menu command not found: {}", command)).into());
+    }
+    let choice = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || choice.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(choice))
+}
+
 pub fn get_monitors() -> Result<Vec<Monitor>> {
-    let output = Command::new("xrandr")
-        .args(["--query"])
-        .output()
+    get_monitors_with(&System, "--query")
+}
+
+/// The monitor layout as the X server last saw it. Unlike
+/// [`get_monitors`] it does not re-probe the outputs, which stalls the
+/// server and drops frames, so it is safe to poll while recording.
+pub fn current_monitors() -> Result<Vec<Monitor>> {
+    get_monitors_with(&System, "--current")
+}
+
+fn get_monitors_with(runner: &dyn Runner, mode: &str) -> Result<Vec<Monitor>> {
+    let output = runner
+        .output(Command::new("xrandr").arg(mode))
         .context("failed to run xrandr")?;
     if !output.status.success() {
         return Err(anyhow!("xrandr failed"));
     }
     let stdout = String::from_utf8_lossy(&output.stdout);
-    let monitors: Vec<Monitor> = stdout.lines().filter_map(parse_xrandr_line).collect();
+    let monitors = parse_xrandr_monitors(&stdout);
     if monitors.is_empty() {
         return Err(anyhow!("no monitors found from xrandr"));
     }
@@ -76,111 +191,622 @@ pub fn full_screen_bounds() -> Result<Monitor> {
     let h = dimensions[split + 1..]
         .parse::<i32>()
         .context("invalid height from xdpyinfo")?;
-    Ok(Monitor { x: 0, y: 0, w, h })
-}
-
-pub fn start_capture(rect: &Rect, config: &Config, output_file: &Path) -> Result<u32> {
-    let mut args = vec![
-        "-thread_queue_size".to_string(),
-        "512".to_string(),
-        "-f".to_string(),
-        "x11grab".to_string(),
-        "-video_size".to_string(),
-        format!("{}x{}", rect.w, rect.h),
-        "-framerate".to_string(),
-        config.video.framerate.to_string(),
-        "-i".to_string(),
-        format!(":0.0+{},{}", rect.x, rect.y),
-    ];
+    Ok(Monitor {
+        name: None,
+        x: 0,
+        y: 0,
+        w,
+        h,
+        refresh_hz: None,
+        rotation: Rotation::Normal,
+        mode_size: None,
+    })
+}
 
-    if config.audio.enabled {
-        let has_mic = config.audio.inputs.iter().any(|input| input == "mic");
-        let has_system = config.audio.inputs.iter().any(|input| input == "system");
-        if has_mic && has_system {
-            args.extend_from_slice(&[
-                "-thread_queue_size".to_string(),
-                "128".to_string(),
-                "-f".to_string(),
-                "pulse".to_string(),
-                "-i".to_string(),
-                config.audio.mic_device.clone(),
-                "-thread_queue_size".to_string(),
-                "128".to_string(),
-                "-f".to_string(),
-                "pulse".to_string(),
-                "-i".to_string(),
-                format!("{}.monitor", config.audio.system_device),
-                "-filter_complex".to_string(),
-                "[1:a][2:a]amerge=inputs=2[aout]".to_string(),
-                "-map".to_string(),
-                "0:v".to_string(),
-                "-map".to_string(),
-                "[aout]".to_string(),
-                "-c:a".to_string(),
-                "aac".to_string(),
-                "-b:a".to_string(),
-                "192k".to_string(),
-            ]);
-        } else if has_mic {
-            args.extend_from_slice(&[
-                "-thread_queue_size".to_string(),
-                "128".to_string(),
-                "-f".to_string(),
-                "pulse".to_string(),
-                "-i".to_string(),
-                config.audio.mic_device.clone(),
-                "-c:a".to_string(),
-                "aac".to_string(),
-                "-b:a".to_string(),
-                "192k".to_string(),
-            ]);
-        } else if has_system {
-            args.extend_from_slice(&[
-                "-thread_queue_size".to_string(),
-                "128".to_string(),
-                "-f".to_string(),
-                "pulse".to_string(),
-                "-i".to_string(),
-                format!("{}.monitor", config.audio.system_device),
-                "-c:a".to_string(),
-                "aac".to_string(),
-                "-b:a".to_string(),
-                "192k".to_string(),
-            ]);
+/// Covers the final recording area with a borderless window labelled with
+/// its size for `secs` seconds. Returns `false` when the user cancels.
+pub fn preview_selection(rect: Rect, adjustments: &[&str], secs: u64) -> Result<bool> {
+    let mut text = format!("<big><b>{}x{}</b></big>", rect.w, rect.h);
+    if !adjustments.is_empty() {
+        text.push_str(&format!("\n{}", adjustments.join(", ")));
+    }
+    let status = Command::new("yad")
+        .args([
+            "--undecorated",
+            "--on-top",
+            "--skip-taskbar",
+            "--text-align=center",
+            "--timeout-indicator=bottom",
+            "--button=Cancel:1",
+        ])
+        .arg(format!(
+            "--geometry={}x{}+{}+{}",
+            rect.w, rect.h, rect.x, rect.y
+        ))
+        .arg(format!("--timeout={}", secs.max(1)))
+        .arg(format!("--text={}", text))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|error| spawn_error("yad", error))?;
+    // yad exits with 70 when the timeout runs out untouched.
+    Ok(matches!(status.code(), Some(0) | Some(70)))
+}
+
+/// Asks a yes/no question. Returns `false` when the user declines or closes
+/// the dialog.
+pub fn confirm(title: &str, text: &str) -> Result<bool> {
+    let status = Command::new("yad")
+        .args([
+            "--on-top",
+            "--center",
+            "--image=dialog-warning",
+            "--button=Cancel:1",
+            "--button=Record anyway:0",
+        ])
+        .arg(format!("--title={}", title))
+        .arg(format!("--text={}", text))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|error| spawn_error("yad", error))?;
+    Ok(status.success())
+}
+
+/// Floats an undecorated Stop button at `x`,`y` that stays above other
+/// windows. The returned yad process exits successfully once it is pressed.
+pub fn show_stop_button(x: i32, y: i32, (w, h): (i32, i32)) -> Result<Child> {
+    Command::new("yad")
+        .args([
+            "--undecorated",
+            "--on-top",
+            "--skip-taskbar",
+            "--sticky",
+            "--no-focus",
+            "--no-escape",
+            "--borders=0",
+            "--buttons-layout=center",
+            "--button=Stop:0",
+        ])
+        .arg(format!("--geometry={}x{}+{}+{}", w, h, x, y))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| spawn_error("yad", error))
+}
+
+pub fn start_capture(
+    rect: &Rect,
+    config: &Config,
+    audio: &AudioDevices,
+    output_file: &Path,
+    id: u32,
+) -> Result<u32> {
+    start_capture_with(&System, rect, config, audio, output_file, id)
+}
+
+fn start_capture_with(
+    runner: &dyn Runner,
+    rect: &Rect,
+    config: &Config,
+    audio: &AudioDevices,
+    output_file: &Path,
+    id: u32,
+) -> Result<u32> {
+    let mut command = FfmpegCommand::new();
+    command.input(
+        "x11grab",
+        format!(
+            "{}+{},{}",
+            config.capture.resolved_display(),
+            rect.x,
+            rect.y
+        ),
+        &[
+            ("-thread_queue_size", "512".to_string()),
+            ("-video_size", format!("{}x{}", rect.w, rect.h)),
+            ("-framerate", config.video.fps().to_string()),
+        ],
+    );
+
+    let pulse_options = [("-thread_queue_size", "128".to_string())];
+    let mut audio_codec = config.audio.encoder_args();
+    audio_codec.extend(config.audio.format_args());
+    let gate = system_gate(config.audio.system_gate_db);
+    match (&audio.mic, &audio.system) {
+        (Some(mic), Some(system)) => {
+            let mut filter = if config.audio.echo_cancel == EchoCancel::Filter {
+                ECHO_FILTER_GRAPH.to_string()
+            } else {
+                "[1:a][2:a]amerge=inputs=2[aout]".to_string()
+            };
+            if let Some(gate) = &gate {
+                filter = format!(
+                    "[2:a]{}[gated];{}",
+                    gate,
+                    filter.replacen("[2:a]", "[gated]", 1)
+                );
+            }
+            command
+                .input("pulse", mic.as_str(), &pulse_options)
+                .input("pulse", system.as_str(), &pulse_options)
+                .filter_complex(filter)
+                .map("0:v")
+                .map("[aout]")
+                .output_options(audio_codec);
+        }
+        (None, Some(system)) if gate.is_some() => {
+            command
+                .input("pulse", system.as_str(), &pulse_options)
+                .filter_complex(format!("[1:a]{}[aout]", gate.unwrap_or_default()))
+                .map("0:v")
+                .map("[aout]")
+                .output_options(audio_codec);
+        }
+        (Some(source), None) | (None, Some(source)) => {
+            command
+                .input("pulse", source.as_str(), &pulse_options)
+                .output_options(audio_codec);
         }
+        (None, None) => {}
     }
 
-    args.extend_from_slice(&[
-        "-c:v".to_string(),
-        "libx264".to_string(),
+    if config.spotlight.enabled {
+        command.video_filter(spotlight::overlay_filter(rect, &config.spotlight, id)?);
+    }
+    if config.zoom.enabled || config.spotlight.enabled {
+        // Lets `zoom-in`, `zoom-out` and the spotlight supervisor retarget
+        // filters while the capture runs.
+        command.video_filter(format!(
+            "zmq=bind_address=tcp\\\\://127.0.0.1\\\\:{}",
+            super::filter_control_port(id)
+        ));
+    }
+    if config.zoom.enabled {
+        // A crop scaled back to the full frame, animated by the zoom actions.
+        command
+            .video_filter(format!(
+                "crop@{}=w={}:h={}:x=0:y=0",
+                zoom::CROP_FILTER,
+                rect.w,
+                rect.h
+            ))
+            .video_filter(format!("scale={}:{}", rect.w, rect.h));
+    }
+    command.video_filters(config.video.capture_filters());
+    if container::is_image_sequence(&config.video.format) {
+        let (options, pattern) = image_sequence_output(config, output_file)?;
+        command.output_options(options).output(pattern);
+        return spawn_capture(runner, &command.to_args(), output_file, id);
+    }
+    if config.video.adaptive_framerate {
+        // A fixed output rate would duplicate the frames mpdecimate dropped.
+        command.output_options(["-fps_mode", "vfr"]);
+    } else {
+        command.output_options(["-r".to_string(), config.video.fps().to_string()]);
+    }
+    command.output_options(config.video.encoder_args()?);
+    let stream = container::is_stream(output_file);
+    let mp4_family = if stream {
+        matches!(config.video.format.as_str(), "mp4" | "mov")
+    } else {
+        container::is_mp4_family(output_file)
+    };
+    if stream {
+        command.output_options(["-f", container::muxer(&config.video.format)]);
+    }
+    if mp4_family && (config.output.crash_safe || stream) {
+        // Fragments carry their own index, so a killed encoder still leaves
+        // everything up to the last keyframe playable, and a pipe never has
+        // to be seeked back to write the index.
+        command.output_options(["-movflags", "+frag_keyframe+empty_moov+default_base_moof"]);
+    }
+    command.output(output_file.to_string_lossy());
+    spawn_capture(runner, &command.to_args(), output_file, id)
+}
+
+/// Output options and file pattern for a png-seq capture into the
+/// `directory`. A resumed capture carries on numbering after the frames
+/// already there, and the frame budget is shared across segments.
+fn image_sequence_output(config: &Config, directory: &Path) -> Result<(Vec<String>, String)> {
+    fs::create_dir_all(directory).context("failed to create frame directory")?;
+    let existing = fs::read_dir(directory)
+        .context("failed to read frame directory")?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "png"))
+        .count() as u32;
+    let remaining = config.png_seq.max_frames.saturating_sub(existing);
+    if remaining == 0 {
+        return Err(anyhow!(
+            "png_seq.max_frames ({}) already reached",
+            config.png_seq.max_frames
+        ));
+    }
+    let options = vec![
         "-r".to_string(),
-        config.video.framerate.to_string(),
-        "-crf".to_string(),
-        config.video.crf.to_string(),
-        "-preset".to_string(),
-        config.video.preset.clone(),
-        "-pix_fmt".to_string(),
-        "yuv420p".to_string(),
-        output_file.to_string_lossy().to_string(),
-    ]);
+        config.png_seq.fps.max(1).to_string(),
+        "-c:v".to_string(),
+        "png".to_string(),
+        "-frames:v".to_string(),
+        remaining.to_string(),
+        "-start_number".to_string(),
+        (existing + 1).to_string(),
+    ];
+    let pattern = directory.join("frame-%06d.png");
+    Ok((options, pattern.to_string_lossy().to_string()))
+}
 
+fn spawn_capture(runner: &dyn Runner, args: &[String], output_file: &Path, id: u32) -> Result<u32> {
     let log_file =
-        File::create(super::CAPTURE_LOG).context("failed to create recording log file")?;
-    let stdout_log = log_file
-        .try_clone()
-        .context("failed to clone recording log file")?;
+        File::create(super::capture_log(id)).context("failed to create recording log file")?;
+    let stdout = if output_file == Path::new("-") {
+        Stdio::inherit()
+    } else {
+        Stdio::from(
+            log_file
+                .try_clone()
+                .context("failed to clone recording log file")?,
+        )
+    };
 
-    let child = Command::new("ffmpeg")
-        .args(&args)
+    let mut ffmpeg = Command::new("ffmpeg");
+    ffmpeg
+        .args(args)
         .stdin(Stdio::null())
-        .stdout(Stdio::from(stdout_log))
-        .stderr(Stdio::from(log_file))
-        .spawn()
-        .context("failed to start ffmpeg")?;
+        .stdout(stdout)
+        .stderr(Stdio::from(log_file));
+    let child = runner
+        .spawn(&mut ffmpeg)
+        .map_err(|error| spawn_error("ffmpeg", error))?;
 
     Ok(child.id())
 }
 
+/// Resolves the Pulse sources to record from, loading `module-echo-cancel`
+/// in front of the mic when configured. Anything set up here is recorded in
+/// the returned devices so `release_audio` can undo it.
+pub fn route_audio(audio: &AudioConfig) -> Result<AudioDevices> {
+    let audio = &with_available_devices(audio);
+    let mut devices = AudioDevices::from_config(audio);
+    if devices.system.is_some() && audio.system_device == "default" {
+        devices.system = Some(default_monitor_source());
+    }
+    handle_bluetooth_mic(audio, &mut devices);
+    if devices.system.is_some() && !audio.app.is_empty() {
+        if let Err(error) = route_app_audio(audio, &mut devices) {
+            release_audio(&devices);
+            return Err(error);
+        }
+        return Ok(devices);
+    }
+    if audio.echo_cancel != EchoCancel::Pulse || devices.mic.is_none() || devices.system.is_none() {
+        return Ok(devices);
+    }
+
+    let previous_sink = pactl(&["get-default-sink"])?;
+    let module = pactl(&[
+        "load-module",
+        "module-echo-cancel",
+        "aec_method=webrtc",
+        "use_master_format=1",
+        &format!(
+            "source_master={}",
+            pulse_name(&audio.mic_device, "@DEFAULT_SOURCE@")
+        ),
+        &format!(
+            "sink_master={}",
+            pulse_name(&audio.system_device, "@DEFAULT_SINK@")
+        ),
+        &format!("source_name={}", EC_SOURCE),
+        &format!("sink_name={}", EC_SINK),
+    ])?
+    .parse::<u32>()
+    .context("unexpected module index from pactl")?;
+    devices.pulse_modules.push(module);
+    devices.previous_default_sink = Some(previous_sink);
+
+    // Playback has to flow through the canceller's sink for it to see the
+    // reference signal.
+    pactl(&["set-default-sink", EC_SINK])?;
+    for input in pactl(&["list", "short", "sink-inputs"])?.lines() {
+        if let Some(index) = input.split_whitespace().next() {
+            let _ = pactl(&["move-sink-input", index, EC_SINK]);
+        }
+    }
+    devices.mic = Some(EC_SOURCE.to_string());
+    devices.system = Some(format!("{}.monitor", EC_SINK));
+    Ok(devices)
+}
+
+/// Swaps configured devices Pulse doesn't know about, such as an unplugged
+/// USB mic, for the defaults and says which device is used instead; ffmpeg
+/// would otherwise fail to open the source.
+fn with_available_devices(audio: &AudioConfig) -> AudioConfig {
+    let mut audio = audio.clone();
+    let names = |kind: &str| -> Option<Vec<String>> {
+        let listing = pactl(&["list", "short", kind]).ok()?;
+        Some(
+            listing
+                .lines()
+                .filter_map(|line| line.split_whitespace().nth(1))
+                .map(str::to_string)
+                .collect(),
+        )
+    };
+    let wants = |name: &str| audio.enabled && audio.inputs.iter().any(|input| input == name);
+    let mut notes = Vec::new();
+    if wants("mic") && audio.mic_device != "default" {
+        if let Some(sources) = names("sources") {
+            if !sources.contains(&audio.mic_device) {
+                let fallback = pactl(&["get-default-source"]).unwrap_or_default();
+                notes.push(format!(
+                    "Mic {} not found, using {}",
+                    audio.mic_device,
+                    default_label(&fallback)
+                ));
+                audio.mic_device = "default".to_string();
+            }
+        }
+    }
+    if wants("system") && audio.system_device != "default" {
+        if let Some(sinks) = names("sinks") {
+            if !sinks.contains(&audio.system_device) {
+                let fallback = pactl(&["get-default-sink"]).unwrap_or_default();
+                notes.push(format!(
+                    "Output {} not found, using {}",
+                    audio.system_device,
+                    default_label(&fallback)
+                ));
+                audio.system_device = "default".to_string();
+            }
+        }
+    }
+    if !notes.is_empty() {
+        show_notification("Audio device missing", &notes.join("\n"), 4000);
+    }
+    audio
+}
+
+/// Applies `audio.bluetooth_mic` when the mic is a Bluetooth headset.
+fn handle_bluetooth_mic(audio: &AudioConfig, devices: &mut AudioDevices) {
+    let Some(mic) = devices.mic.clone() else {
+        return;
+    };
+    let source = if mic == "default" {
+        pactl(&["get-default-source"]).unwrap_or_default()
+    } else {
+        mic
+    };
+    let Some(address) = bluetooth_address(&source) else {
+        return;
+    };
+    match audio.bluetooth_mic {
+        BluetoothMic::Allow => {}
+        BluetoothMic::Warn => show_notification(
+            "Bluetooth mic",
+            "The headset switches to call quality while it records",
+            4000,
+        ),
+        BluetoothMic::Avoid => match other_mic() {
+            Some(other) => {
+                show_notification(
+                    "Bluetooth mic skipped",
+                    &format!("Recording from {} instead", other),
+                    3000,
+                );
+                devices.mic = Some(other);
+            }
+            None => show_notification(
+                "Bluetooth mic",
+                "No other mic found; the headset switches to call quality while it records",
+                4000,
+            ),
+        },
+        BluetoothMic::Switch => {
+            let card = format!("bluez_card.{}", address);
+            let listing = pactl(&["list", "cards"]).unwrap_or_default();
+            let Some((active, profiles)) = card_profiles(&listing, &card) else {
+                return;
+            };
+            let is_headset =
+                |profile: &str| profile.contains("head-unit") || profile.contains("head_unit");
+            let Some(headset) = profiles.iter().find(|profile| is_headset(profile)) else {
+                return;
+            };
+            // A card already in its call profile goes back to A2DP.
+            let restore = if is_headset(&active) {
+                profiles
+                    .iter()
+                    .find(|profile| profile.starts_with("a2dp"))
+                    .cloned()
+            } else {
+                Some(active)
+            };
+            if pactl(&["set-card-profile", &card, headset]).is_err() {
+                show_notification(
+                    "Bluetooth mic",
+                    "Could not switch the headset profile; recording as is",
+                    3000,
+                );
+                return;
+            }
+            devices.previous_card_profile = restore.map(|profile| (card, profile));
+        }
+    }
+}
+
+/// Device address of a Bluetooth source, as used in its card's name:
+/// `bluez_input.AA_BB….0` on PipeWire, `bluez_source.AA_BB….headset_head_unit`
+/// on PulseAudio.
+fn bluetooth_address(source: &str) -> Option<&str> {
+    let rest = source
+        .strip_prefix("bluez_input.")
+        .or_else(|| source.strip_prefix("bluez_source."))?;
+    rest.split('.').next().filter(|address| !address.is_empty())
+}
+
+/// First capture source that is neither Bluetooth nor a sink monitor.
+fn other_mic() -> Option<String> {
+    pactl(&["list", "short", "sources"])
+        .ok()?
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .find(|name| !name.starts_with("bluez_") && !name.ends_with(".monitor"))
+        .map(str::to_string)
+}
+
+/// Active profile and profile names of `card` in a `pactl list cards` listing.
+fn card_profiles(listing: &str, card: &str) -> Option<(String, Vec<String>)> {
+    let block = listing.split("Card #").find(|block| {
+        block
+            .lines()
+            .any(|line| line.trim() == format!("Name: {}", card))
+    })?;
+    let mut profiles = Vec::new();
+    let mut in_profiles = false;
+    for line in block.lines() {
+        let trimmed = line.trim();
+        if let Some(active) = trimmed.strip_prefix("Active Profile: ") {
+            return Some((active.to_string(), profiles));
+        }
+        if trimmed == "Profiles:" {
+            in_profiles = true;
+        } else if in_profiles && line.starts_with("\t\t") {
+            if let Some((name, _)) = trimmed.split_once(": ") {
+                profiles.push(name.to_string());
+            }
+        } else {
+            in_profiles = false;
+        }
+    }
+    None
+}
+
+/// Monitor source of the current default sink. `default.monitor` is not a
+/// real source name on every Pulse/PipeWire setup, so ask for the sink.
+fn default_monitor_source() -> String {
+    match pactl(&["get-default-sink"]) {
+        Ok(sink) if !sink.is_empty() => format!("{}.monitor", sink),
+        _ => "@DEFAULT_MONITOR@".to_string(),
+    }
+}
+
+fn default_label(name: &str) -> String {
+    if name.is_empty() {
+        "the default device".to_string()
+    } else {
+        format!("the default ({})", name)
+    }
+}
+
+/// Moves the configured application's streams onto a private null sink and
+/// loops that sink back to the speakers, so the recording hears only the app
+/// while the user still hears everything.
+fn route_app_audio(audio: &AudioConfig, devices: &mut AudioDevices) -> Result<()> {
+    let null_sink = pactl(&[
+        "load-module",
+        "module-null-sink",
+        &format!("sink_name={}", APP_SINK),
+        "sink_properties=device.description=Screen-Recorder-App",
+    ])?
+    .parse::<u32>()
+    .context("unexpected module index from pactl")?;
+    devices.pulse_modules.push(null_sink);
+
+    let loopback = pactl(&[
+        "load-module",
+        "module-loopback",
+        &format!("source={}.monitor", APP_SINK),
+        &format!(
+            "sink={}",
+            pulse_name(&audio.system_device, "@DEFAULT_SINK@")
+        ),
+        "latency_msec=20",
+    ])?
+    .parse::<u32>()
+    .context("unexpected module index from pactl")?;
+    devices.pulse_modules.push(loopback);
+
+    let listing = pactl(&["list", "sink-inputs"])?;
+    let inputs = app_sink_inputs(&listing, &audio.app);
+    if inputs.is_empty() {
+        show_notification(
+            "No app audio yet",
+            &format!(
+                "{} is not playing anything; its audio will be missing",
+                audio.app
+            ),
+            2000,
+        );
+    }
+    for index in inputs {
+        pactl(&["move-sink-input", &index.to_string(), APP_SINK])?;
+    }
+    devices.system = Some(format!("{}.monitor", APP_SINK));
+    Ok(())
+}
+
+/// Indexes of sink inputs whose application name or binary contains `app`.
+fn app_sink_inputs(listing: &str, app: &str) -> Vec<u32> {
+    let needle = app.to_lowercase();
+    let mut matches = Vec::new();
+    let mut current = None;
+    for line in listing.lines() {
+        let line = line.trim();
+        if let Some(index) = line.strip_prefix("Sink Input #") {
+            current = index.trim().parse::<u32>().ok();
+            continue;
+        }
+        let is_app_property =
+            line.starts_with("application.name") || line.starts_with("application.process.binary");
+        if !is_app_property || !line.to_lowercase().contains(&needle) {
+            continue;
+        }
+        if let Some(index) = current.take() {
+            matches.push(index);
+        }
+    }
+    matches
+}
+
+pub fn release_audio(devices: &AudioDevices) {
+    if let Some((card, profile)) = &devices.previous_card_profile {
+        let _ = pactl(&["set-card-profile", card, profile]);
+    }
+    if let Some(sink) = &devices.previous_default_sink {
+        let _ = pactl(&["set-default-sink", sink]);
+    }
+    for module in devices.pulse_modules.iter().rev() {
+        let _ = pactl(&["unload-module", &module.to_string()]);
+    }
+}
+
+fn pactl(args: &[&str]) -> Result<String> {
+    let output = Command::new("pactl")
+        .args(args)
+        .output()
+        .context("failed to run pactl")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "pactl {} failed: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn pulse_name<'a>(device: &'a str, default_alias: &'a str) -> &'a str {
+    if device == "default" {
+        default_alias
+    } else {
+        device
+    }
+}
+
 pub fn stop_capture(pid: u32) -> Result<()> {
     Command::new("kill")
         .args(["-INT", &pid.to_string()])
@@ -189,26 +815,554 @@ pub fn stop_capture(pid: u32) -> Result<()> {
     Ok(())
 }
 
+/// Confirms the X server accepts connections from this user.
+pub fn check_display() -> Result<()> {
+    let display = env::var("DISPLAY").unwrap_or_default();
+    if display.is_empty() {
+        return Err(anyhow!(
+            "DISPLAY is not set; start from the desktop session or set capture.display"
+        ));
+    }
+    let output = Command::new("xdpyinfo")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error("xdpyinfo", error))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "cannot open X display {}: {}; run as the desktop user or grant access with xhost",
+            display,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Confirms the Pulse or PipeWire server answers this user.
+pub fn check_audio_server() -> Result<()> {
+    let output = Command::new("pactl")
+        .arg("info")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error("pactl", error))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "cannot reach the audio server: {}; run as the desktop user so XDG_RUNTIME_DIR points at its socket, or disable audio",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
 pub fn process_alive(pid: u32) -> bool {
-    Path::new(&format!("/proc/{pid}")).exists()
+    // A capture spawned by this very process stays a zombie until it exits,
+    // so an existing /proc entry alone does not mean it is still running.
+    let Ok(stat) = fs::read_to_string(format!("/proc/{pid}/stat")) else {
+        return false;
+    };
+    stat.rsplit_once(')')
+        .is_some_and(|(_, fields)| !fields.trim_start().starts_with('Z'))
+}
+
+/// Seconds since the last keyboard or mouse input, via xprintidle.
+pub fn idle_secs() -> Result<u64> {
+    let output = Command::new("xprintidle")
+        .output()
+        .context("failed to run xprintidle")?;
+    if !output.status.success() {
+        return Err(anyhow!("xprintidle failed"));
+    }
+    let idle_ms: u64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .context("invalid xprintidle output")?;
+    Ok(idle_ms / 1000)
 }
 
 pub fn show_notification(title: &str, message: &str, timeout_ms: u32) {
-    let _ = Command::new("notify-send")
-        .args([
-            "-u",
-            "normal",
-            "-t",
-            &timeout_ms.to_string(),
+    if super::notifications_muted() {
+        return;
+    }
+    let shown = notification_daemon_running()
+        && Command::new("notify-send")
+            .args([
+                "-u",
+                "normal",
+                "-t",
+                &timeout_ms.to_string(),
+                title,
+                message,
+            ])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+    if !shown {
+        show_fallback_notification(title, message, timeout_ms);
+    }
+}
+
+/// Whether something owns the desktop notification bus name. Without a
+/// daemon notify-send has nowhere to send to. Asked once per process; a
+/// session without dbus-send is assumed to have one.
+fn notification_daemon_running() -> bool {
+    static RUNNING: OnceLock<bool> = OnceLock::new();
+    *RUNNING.get_or_init(|| {
+        let Ok(output) = Command::new("dbus-send")
+            .args([
+                "--session",
+                "--print-reply",
+                "--dest=org.freedesktop.DBus",
+                "/org/freedesktop/DBus",
+                "org.freedesktop.DBus.NameHasOwner",
+                "string:org.freedesktop.Notifications",
+            ])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+        else {
+            return true;
+        };
+        output.status.success() && reply_is_true(&String::from_utf8_lossy(&output.stdout))
+    })
+}
+
+fn reply_is_true(reply: &str) -> bool {
+    reply
+        .lines()
+        .any(|line| line.split_whitespace().eq(["boolean", "true"]))
+}
+
+/// Feedback for sessions without a notification daemon: a line in the
+/// notification log and on stderr, and an xmessage box when X is there to
+/// show it.
+fn show_fallback_notification(title: &str, message: &str, timeout_ms: u32) {
+    eprintln!("{}: {}", title, message);
+    let log_path = super::notification_log();
+    if let Some(dir) = log_path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if let Ok(mut log) = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+    {
+        let _ = writeln!(
+            log,
+            "{} {}: {}",
+            chrono::Local::now().format("%F %T"),
             title,
-            message,
+            message
+        );
+    }
+    let spawned = Command::new("xmessage")
+        .args(["-center", "-buttons", "", "-timeout"])
+        .arg(timeout_ms.div_ceil(1000).max(1).to_string())
+        .arg(format!("{}\n\n{}", title, message))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Ok(mut child) = spawned {
+        thread::spawn(move || child.wait());
+    }
+}
+
+/// A notification with a button per action. notify-send waits for the
+/// click in the background; without action support it falls back to a
+/// plain notification.
+pub fn show_notification_with_actions(
+    title: &str,
+    message: &str,
+    timeout_ms: u32,
+    actions: &[(&str, NotificationAction)],
+) {
+    if super::notifications_muted() {
+        return;
+    }
+    if !notification_daemon_running() {
+        show_fallback_notification(title, message, timeout_ms);
+        return;
+    }
+    let spawned = Command::new("sh")
+        .args(["-c", &action_script(actions), "sh"])
+        .args([&timeout_ms.to_string(), title, message])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Ok(mut child) = spawned {
+        // The daemon outlives many notifications; reap each one.
+        thread::spawn(move || child.wait());
+    }
+}
+
+/// Shell script that shows the notification from `$1` (timeout), `$2` and
+/// `$3`, then runs whichever action was clicked.
+fn action_script(actions: &[(&str, NotificationAction)]) -> String {
+    let mut buttons = String::new();
+    let mut cases = String::new();
+    for (index, (label, action)) in actions.iter().enumerate() {
+        buttons.push_str(&format!(
+            " --action={}",
+            shell_quote(&format!("{}={}", index, label))
+        ));
+        let command = match action {
+            NotificationAction::OpenUrl(url) => vec!["xdg-open".to_string(), url.clone()],
+            NotificationAction::Run(command) => command.clone(),
+        };
+        let words: Vec<String> = command.iter().map(|word| shell_quote(word)).collect();
+        cases.push_str(&format!("  {}) exec {} ;;\n", index, words.join(" ")));
+    }
+    format!(
+        "choice=$(notify-send --wait -u normal -t \"$1\"{} \"$2\" \"$3\") \\\n\
+         \x20   || exec notify-send -u normal -t \"$1\" \"$2\" \"$3\"\n\
+         case \"$choice\" in\n{}esac",
+        buttons, cases
+    )
+}
+
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Asks for a line of text in a small dialog. `None` when dismissed.
+pub fn prompt_text(title: &str, text: &str) -> Result<Option<String>> {
+    let output = Command::new("yad")
+        .args(["--entry", "--on-top", "--center"])
+        .arg(format!("--title={}", title))
+        .arg(format!("--text={}", text))
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error("yad", error))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(
+        String::from_utf8_lossy(&output.stdout).trim().to_string(),
+    ))
+}
+
+/// Shows the settings form in a yad dialog. Returns `None` when the user
+/// cancels it.
+pub fn edit_settings(form: &SettingsForm) -> Result<Option<SettingsForm>> {
+    let formats: Vec<String> = ["auto", "mkv", "mp4", "mov", "webm", "png-seq"]
+        .iter()
+        .map(|format| {
+            if *format == form.format {
+                format!("^{}", format)
+            } else {
+                format.to_string()
+            }
+        })
+        .collect();
+    let check = |value: bool| if value { "TRUE" } else { "FALSE" }.to_string();
+    let output = Command::new("yad")
+        .args([
+            "--form",
+            "--title=Screen Recorder Settings",
+            "--field=Record audio:CHK",
+            "--field=Microphone:CHK",
+            "--field=System audio:CHK",
+            "--field=Format:CB",
+            "--field=Framerate (number, auto or auto-half)",
+            "--field=Output folder:DIR",
+        ])
+        .args([
+            check(form.audio_enabled),
+            check(form.mic),
+            check(form.system),
+            formats.join("!"),
+            form.framerate.clone(),
+            form.directory.to_string_lossy().to_string(),
         ])
-        .status();
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error("yad", error))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    // yad prints the values joined and terminated by `|`.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.trim_end_matches('\n');
+    let values: Vec<&str> = line.strip_suffix('|').unwrap_or(line).split('|').collect();
+    let [audio_enabled, mic, system, format, framerate, directory] = values[..] else {
+        return Err(anyhow!(
+            "unexpected settings form output: {}",
+            stdout.trim()
+        ));
+    };
+    Ok(Some(SettingsForm {
+        audio_enabled: audio_enabled == "TRUE",
+        mic: mic == "TRUE",
+        system: system == "TRUE",
+        format: format.to_string(),
+        framerate: framerate.to_string(),
+        directory: directory.into(),
+    }))
+}
+
+/// Starts gromit-mpx on first use and toggles its drawing mode.
+pub fn toggle_annotation() -> Result<()> {
+    let running = Command::new("pgrep")
+        .args(["-x", "gromit-mpx"])
+        .stdout(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !running {
+        Command::new("gromit-mpx")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| spawn_error("gromit-mpx", error))?;
+        thread::sleep(Duration::from_millis(300));
+    }
+    gromit(&["--toggle"])
+}
+
+/// Wipes everything drawn so far, if gromit-mpx is running.
+pub fn clear_annotations() -> Result<()> {
+    gromit(&["--clear"])
+}
+
+/// Moves `path` to the desktop trash, where it can be restored from.
+pub fn trash(path: &Path) -> Result<()> {
+    let output = Command::new("gio")
+        .arg("trash")
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error("gio", error))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("gio trash failed: {}", stderr.trim()));
+    }
+    Ok(())
+}
+
+/// Records `seconds` from each source in `devices` into its own WAV file
+/// in `dir`, returning a label naming the source alongside each file.
+pub fn record_audio_sample(
+    devices: &AudioDevices,
+    seconds: u32,
+    dir: &Path,
+) -> Result<Vec<(String, PathBuf)>> {
+    let sources: Vec<(&str, &String)> = [("mic", &devices.mic), ("system", &devices.system)]
+        .into_iter()
+        .filter_map(|(label, source)| source.as_ref().map(|source| (label, source)))
+        .collect();
+    if sources.is_empty() {
+        return Err(anyhow!("audio is disabled or has no inputs configured"));
+    }
+    let mut command = Command::new("ffmpeg");
+    command.args(["-hide_banner", "-loglevel", "error", "-y"]);
+    for (_, source) in &sources {
+        command
+            .args(["-f", "pulse", "-t", &seconds.to_string(), "-i"])
+            .arg(source);
+    }
+    let mut files = Vec::new();
+    for (index, (label, source)) in sources.iter().enumerate() {
+        let file = dir.join(format!("screen-recorder-test-{}.wav", label));
+        command.args(["-map", &format!("{}:a", index)]).arg(&file);
+        files.push((format!("{} ({})", label, source), file));
+    }
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error("ffmpeg", error))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("audio test recording failed: {}", stderr.trim()));
+    }
+    Ok(files)
+}
+
+pub fn play_audio(file: &Path) -> Result<()> {
+    let status = Command::new("paplay")
+        .arg(file)
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|error| spawn_error("paplay", error))?;
+    if !status.success() {
+        return Err(anyhow!("paplay could not play {}", file.display()));
+    }
+    Ok(())
+}
+
+fn gromit(args: &[&str]) -> Result<()> {
+    let status = Command::new("gromit-mpx")
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|error| spawn_error("gromit-mpx", error))?;
+    if !status.success() {
+        return Err(anyhow!("gromit-mpx {} failed", args.join(" ")));
+    }
+    Ok(())
+}
+
+/// Pointer position in root window coordinates, via xdotool.
+pub fn pointer_position() -> Result<(i32, i32)> {
+    let output = Command::new("xdotool")
+        .args(["getmouselocation", "--shell"])
+        .output()
+        .map_err(|error| spawn_error("xdotool", error))?;
+    if !output.status.success() {
+        return Err(anyhow!("xdotool getmouselocation failed"));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let coordinate = |name: &str| {
+        stdout
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+            .and_then(|value| value.trim().parse::<i32>().ok())
+            .ok_or_else(|| anyhow!("xdotool did not report {}", name))
+    };
+    Ok((coordinate("X")?, coordinate("Y")?))
+}
+
+/// X screen number the pointer is on, for setups running several screens
+/// on one display.
+pub fn pointer_screen() -> Option<u32> {
+    let output = Command::new("xdotool")
+        .args(["getmouselocation", "--shell"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("SCREEN="))
+        .and_then(|screen| screen.trim().parse().ok())
+}
+
+/// WM_CLASS (`instance.Class`) and frame of each window on the current
+/// desktop, bottom of the stack first, via wmctrl and the root window's
+/// `_NET_CLIENT_LIST_STACKING`.
+pub fn stacked_windows() -> Result<Vec<(String, Rect)>> {
+    let listing = Command::new("wmctrl")
+        .arg("-lGx")
+        .output()
+        .map_err(|error| spawn_error("wmctrl", error))?;
+    if !listing.status.success() {
+        return Err(anyhow!("wmctrl could not list windows"));
+    }
+    let desktops = Command::new("wmctrl")
+        .arg("-d")
+        .output()
+        .map_err(|error| spawn_error("wmctrl", error))?;
+    let current = String::from_utf8_lossy(&desktops.stdout)
+        .lines()
+        .find(|line| line.split_whitespace().nth(1) == Some("*"))
+        .and_then(|line| line.split_whitespace().next())
+        .map(str::to_string);
+    let stacking = Command::new("xprop")
+        .args(["-root", "_NET_CLIENT_LIST_STACKING"])
+        .output()
+        .map_err(|error| spawn_error("xprop", error))?;
+    let order: Vec<u64> = String::from_utf8_lossy(&stacking.stdout)
+        .split_once('#')
+        .map(|(_, ids)| {
+            ids.split(',')
+                .filter_map(|id| parse_window_id(id.trim()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut windows: Vec<(usize, String, Rect)> = String::from_utf8_lossy(&listing.stdout)
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 7 {
+                return None;
+            }
+            // Desktop -1 is a sticky window, shown on every desktop.
+            if fields[1] != "-1"
+                && current
+                    .as_deref()
+                    .is_some_and(|desktop| fields[1] != desktop)
+            {
+                return None;
+            }
+            let id = parse_window_id(fields[0])?;
+            let number = |index: usize| fields[index].parse::<i32>().ok();
+            let rect = Rect {
+                x: number(2)?,
+                y: number(3)?,
+                w: number(4)?,
+                h: number(5)?,
+            };
+            let depth = order.iter().position(|other| *other == id).unwrap_or(0);
+            Some((depth, fields[6].to_string(), rect))
+        })
+        .collect();
+    windows.sort_by_key(|(depth, _, _)| *depth);
+    Ok(windows
+        .into_iter()
+        .map(|(_, class, rect)| (class, rect))
+        .collect())
+}
+
+fn parse_window_id(raw: &str) -> Option<u64> {
+    u64::from_str_radix(raw.strip_prefix("0x")?, 16).ok()
+}
+
+/// Sends a filter command to the zmq filter of a running capture.
+pub fn send_filter_command(port: u16, command: &str) -> Result<()> {
+    let mut child = Command::new("zmqsend")
+        .arg("-b")
+        .arg(format!("tcp://127.0.0.1:{}", port))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| spawn_error("zmqsend", error))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(command.as_bytes())
+            .context("failed to write filter command")?;
+    }
+    // zmqsend waits forever for a reply when no capture is listening.
+    let deadline = Instant::now() + FILTER_COMMAND_TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait().context("failed to wait for zmqsend")? {
+            if !status.success() {
+                return Err(anyhow!("filter command rejected: {}", command));
+            }
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!("no capture answered filter command: {}", command));
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// Offers the PNG at `image` on the clipboard. xclip reads the file before
+/// it forks to serve the selection, so the file can go once this returns.
+pub fn copy_image_to_clipboard(image: &Path) -> Result<()> {
+    let status = Command::new("xclip")
+        .args(["-selection", "clipboard", "-t", "image/png", "-i"])
+        .arg(image)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|error| spawn_error("xclip", error))?;
+    if !status.success() {
+        return Err(anyhow!("xclip could not take the image"));
+    }
+    Ok(())
 }
 
 pub fn open_settings() -> Result<()> {
     Command::new("xdg-open")
-        .arg(SETTINGS_URL)
+        .arg(tray::settings_url())
         .stdin(Stdio::null())
         .stdout(Stdio::null())
         .stderr(Stdio::null())
@@ -238,6 +1392,45 @@ fn parse_selection_geometry(raw: &str) -> Result<Rect> {
     })
 }
 
+/// Connected outputs from `xrandr --query`, each with the refresh rate of its
+/// current mode (the indented mode line marked `*`).
+fn parse_xrandr_monitors(stdout: &str) -> Vec<Monitor> {
+    let mut monitors: Vec<Monitor> = Vec::new();
+    let mut in_connected_output = false;
+    for line in stdout.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            let monitor = parse_xrandr_line(line);
+            in_connected_output = monitor.is_some();
+            monitors.extend(monitor);
+            continue;
+        }
+        if !in_connected_output {
+            continue;
+        }
+        if let (Some(monitor), Some(rate)) = (monitors.last_mut(), parse_current_refresh(line)) {
+            monitor.refresh_hz.get_or_insert(rate);
+            if monitor.mode_size.is_none() {
+                monitor.mode_size = line
+                    .split_whitespace()
+                    .next()
+                    .and_then(|mode| mode.split_once('x'))
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+            }
+        }
+    }
+    monitors
+}
+
+fn parse_current_refresh(mode_line: &str) -> Option<f64> {
+    mode_line
+        .split_whitespace()
+        .skip(1)
+        .find(|rate| rate.contains('*'))?
+        .trim_end_matches(['*', '+'])
+        .parse()
+        .ok()
+}
+
 fn parse_xrandr_line(line: &str) -> Option<Monitor> {
     if !line.contains(" connected") {
         return None;
@@ -245,23 +1438,271 @@ fn parse_xrandr_line(line: &str) -> Option<Monitor> {
     let geometry = line
         .split_whitespace()
         .find(|token| token.contains('x') && token.contains('+'))?;
-    parse_monitor_geometry(geometry)
-}
-
-fn parse_monitor_geometry(token: &str) -> Option<Monitor> {
-    let x_split = token.find('x')?;
-    let width = token[..x_split].parse::<i32>().ok()?;
-    let after_x = &token[x_split + 1..];
-    let first_sign = after_x.find(['+', '-'])?;
-    let height = after_x[..first_sign].parse::<i32>().ok()?;
-    let after_height = &after_x[first_sign..];
-    let second_sign = after_height[1..].find(['+', '-'])? + 1;
-    let x = after_height[..second_sign].parse::<i32>().ok()?;
-    let y = after_height[second_sign..].parse::<i32>().ok()?;
-    Some(Monitor {
-        x,
-        y,
-        w: width,
-        h: height,
-    })
+    let mut monitor = parse_x_geometry(geometry)?;
+    monitor.name = line.split_whitespace().next().map(str::to_string);
+    // The rotation follows the geometry, before the parenthesised list of
+    // supported rotations: `DP-1 connected 1080x1920+0+0 left (normal ...`.
+    monitor.rotation = line
+        .split('(')
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .skip_while(|token| *token != geometry)
+        .nth(1)
+        .and_then(Rotation::parse)
+        .unwrap_or_default();
+    Some(monitor)
+}
+
+/// Streams raw XInput2 events and calls `on_click` for every left, middle or
+/// right button press anywhere on the display. The returned child is the
+/// listener; killing it ends the stream.
+pub fn listen_for_clicks(on_click: impl Fn() + Send + 'static) -> Result<Child> {
+    let mut child = Command::new("xinput")
+        .args(["test-xi2", "--root"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| spawn_error("xinput", error))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow!("xinput output unavailable"))?;
+    thread::spawn(move || {
+        let mut in_press = false;
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            if line.starts_with("EVENT type") {
+                in_press = line.contains("(RawButtonPress)");
+                continue;
+            }
+            let Some(button) = line.trim().strip_prefix("detail:") else {
+                continue;
+            };
+            // Buttons 4 and up are scroll wheel steps, not clicks.
+            if in_press && matches!(button.trim(), "1" | "2" | "3") {
+                on_click();
+            }
+            in_press = false;
+        }
+    });
+    Ok(child)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::runner::fake::FakeRunner;
+    use proptest::prelude::*;
+
+    const XRANDR: &str = "\
+Screen 0: minimum 8 x 8, current 3840 x 1080, maximum 32767 x 32767
+DP-1 connected primary 1920x1080+0+0 (normal left inverted right x axis y axis) 527mm x 296mm
+   1920x1080     60.00*+  59.94
+HDMI-1 connected 1920x1080+1920+0 (normal left inverted right x axis y axis) 477mm x 268mm
+   1920x1080     74.97*   60.00
+DP-2 disconnected (normal left inverted right x axis y axis)
+";
+
+    #[test]
+    fn name_owner_reply_is_read() {
+        let reply = "method return time=1.2 sender=org.freedesktop.DBus -> destination=:1.9 serial=3 reply_serial=2\n   boolean true\n";
+        assert!(reply_is_true(reply));
+        assert!(!reply_is_true(&reply.replace("true", "false")));
+        assert!(!reply_is_true(""));
+    }
+
+    #[test]
+    fn notification_actions_run_the_clicked_command() {
+        let script = action_script(&[
+            (
+                "Redo",
+                NotificationAction::Run(vec!["/bin/rec".to_string(), "it's".to_string()]),
+            ),
+            (
+                "Settings",
+                NotificationAction::OpenUrl("http://127.0.0.1:42700/".to_string()),
+            ),
+        ]);
+        assert_eq!(
+            script,
+            "choice=$(notify-send --wait -u normal -t \"$1\" --action='0=Redo' --action='1=Settings' \"$2\" \"$3\") \\\n\
+             \x20   || exec notify-send -u normal -t \"$1\" \"$2\" \"$3\"\n\
+             case \"$choice\" in\n\
+             \x20 0) exec '/bin/rec' 'it'\\''s' ;;\n\
+             \x20 1) exec 'xdg-open' 'http://127.0.0.1:42700/' ;;\n\
+             esac"
+        );
+    }
+
+    #[test]
+    fn menu_gets_the_options_and_returns_the_pick() {
+        let runner = FakeRunner::default().reply("sh", 0, "hq\n", "");
+        let options = ["draft".to_string(), "hq".to_string()];
+        let choice = pick_from_menu_with(&runner, "rofi -dmenu", &options).unwrap();
+        assert_eq!(choice.as_deref(), Some("hq"));
+        let calls = runner.calls.borrow();
+        assert_eq!(calls[0][2], "printf '%s\\n' \"$@\" | rofi -dmenu");
+        assert_eq!(calls[0][3..], ["sh", "draft", "hq"]);
+    }
+
+    #[test]
+    fn dismissed_menu_picks_nothing() {
+        let runner = FakeRunner::default().reply("sh", 1, "", "");
+        assert_eq!(pick_from_menu_with(&runner, "dmenu", &[]).unwrap(), None);
+        let missing = FakeRunner::default().reply("sh", 127, "", "sh: 1: fuzzel: not found");
+        let error = pick_from_menu_with(&missing, "fuzzel --dmenu", &[]).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Failure>(),
+            Some(Failure::Unavailable(_))
+        ));
+    }
+
+    #[test]
+    fn selection_reads_slop_geometry() {
+        let runner = FakeRunner::default().reply("slop", 0, "10,20,640,480\n", "");
+        let rect = select_region_with(&runner, None).unwrap();
+        assert_eq!(
+            rect,
+            Some(Rect {
+                x: 10,
+                y: 20,
+                w: 640,
+                h: 480
+            })
+        );
+    }
+
+    #[test]
+    fn selection_tells_cancels_from_failures() {
+        let cancelled = FakeRunner::default().reply(
+            "slop",
+            1,
+            "",
+            "Selection was cancelled by keystroke or right-click.",
+        );
+        assert_eq!(select_region_with(&cancelled, None).unwrap(), None);
+
+        let broken = FakeRunner::default().reply("slop", 1, "", "Failed to open X display");
+        let error = select_region_with(&broken, None).unwrap_err();
+        assert!(format!("{:#}", error).contains("Failed to open X display"));
+
+        let missing = FakeRunner::default();
+        let error = select_region_with(&missing, None).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(crate::failure::Failure::MissingTool("slop"))
+        ));
+    }
+
+    #[test]
+    fn monitors_come_from_connected_outputs() {
+        let runner = FakeRunner::default().reply("xrandr", 0, XRANDR, "");
+        let monitors = get_monitors_with(&runner, "--query").unwrap();
+        let names: Vec<_> = monitors
+            .iter()
+            .map(|monitor| monitor.name.as_deref().unwrap_or_default())
+            .collect();
+        assert_eq!(names, ["DP-1", "HDMI-1"]);
+        assert_eq!(monitors[1].x, 1920);
+        assert_eq!(monitors[1].refresh_hz, Some(74.97));
+        assert_eq!(runner.calls.borrow()[0], ["xrandr", "--query"]);
+
+        let failing = FakeRunner::default().reply("xrandr", 1, "", "Can't open display");
+        assert!(get_monitors_with(&failing, "--current").is_err());
+    }
+
+    #[test]
+    fn capture_grabs_the_selected_area() {
+        // Out of the way of real recordings, which count up from 1.
+        let id = 9_000;
+        let runner = FakeRunner::default().reply("ffmpeg", 0, "", "");
+        let rect = Rect {
+            x: 100,
+            y: 50,
+            w: 640,
+            h: 480,
+        };
+        let config = Config::default();
+        let output = Path::new("/tmp/recording.mkv");
+        let result = start_capture_with(
+            &runner,
+            &rect,
+            &config,
+            &AudioDevices::default(),
+            output,
+            id,
+        );
+        let _ = fs::remove_file(super::super::capture_log(id));
+        result.unwrap();
+        let calls = runner.calls.borrow();
+        let args = &calls[0];
+        assert_eq!(args[0], "ffmpeg");
+        assert!(args.windows(2).any(|pair| pair == ["-f", "x11grab"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["-video_size", "640x480"]));
+        assert!(args.iter().any(|arg| arg.ends_with("+100,50")));
+        assert_eq!(args.last().map(String::as_str), Some("/tmp/recording.mkv"));
+    }
+
+    #[test]
+    fn system_gate_wraps_only_the_system_input() {
+        let id = 9_001;
+        let runner = FakeRunner::default().reply("ffmpeg", 0, "", "");
+        let mut config = Config::default();
+        config.audio.system_gate_db = -40.0;
+        let audio = AudioDevices {
+            mic: Some("mic".to_string()),
+            system: Some("speakers.monitor".to_string()),
+            ..AudioDevices::default()
+        };
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            w: 320,
+            h: 240,
+        };
+        let result = start_capture_with(
+            &runner,
+            &rect,
+            &config,
+            &audio,
+            Path::new("/tmp/recording.mkv"),
+            id,
+        );
+        let _ = fs::remove_file(super::super::capture_log(id));
+        result.unwrap();
+        let calls = runner.calls.borrow();
+        let graph = calls[0]
+            .windows(2)
+            .find(|pair| pair[0] == "-filter_complex")
+            .map(|pair| pair[1].clone())
+            .unwrap();
+        assert_eq!(
+            graph,
+            "[2:a]agate=threshold=0.010000:range=0:attack=5:release=300[gated];\
+             [1:a][gated]amerge=inputs=2[aout]"
+        );
+        assert_eq!(system_gate(0.0), None);
+    }
+
+    proptest! {
+        #[test]
+        fn selection_geometry_round_trips(
+            x in -8000i32..8000,
+            y in -8000i32..8000,
+            w in 0i32..8000,
+            h in 0i32..8000,
+        ) {
+            let parsed = parse_selection_geometry(&format!("{},{},{},{}", x, y, w, h)).unwrap();
+            prop_assert_eq!(parsed, Rect { x, y, w, h });
+        }
+
+        #[test]
+        fn selection_geometry_never_panics(raw in any::<String>()) {
+            let _ = parse_selection_geometry(&raw);
+        }
+    }
 }
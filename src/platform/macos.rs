@@ -1,32 +1,92 @@
 use anyhow::{anyhow, Result};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::time::Duration;
 
-use crate::{Config, Monitor, Rect};
+use crate::settings_gui::SettingsForm;
+use crate::state::AudioDevices;
+use crate::{AudioConfig, Config, Monitor, Rect};
 
-pub fn select_region() -> Result<Option<Rect>> {
+pub fn select_region(_timeout: Option<Duration>) -> Result<Option<Rect>> {
     Err(anyhow!(
         "plugin-screen-recorder: region selection is not implemented on macOS"
     ))
 }
 
+pub fn window_rect(_target: &str) -> Result<Rect> {
+    Err(anyhow!(
+        "plugin-screen-recorder: window capture is not implemented on macOS"
+    ))
+}
+
+pub fn workspace_rect(_name: &str) -> Result<Rect> {
+    Err(anyhow!(
+        "plugin-screen-recorder: workspace capture is not implemented on macOS"
+    ))
+}
+
+pub fn focused_workspace() -> Result<String> {
+    Err(anyhow!(
+        "plugin-screen-recorder: workspace capture is not implemented on macOS"
+    ))
+}
+
+pub fn workspace_visible(_name: &str) -> Result<bool> {
+    Ok(true)
+}
+
+pub fn pick_from_menu(_command: &str, _options: &[String]) -> Result<Option<String>> {
+    Err(anyhow!(
+        "plugin-screen-recorder: menu picker is not implemented on macOS"
+    ))
+}
+
 pub fn get_monitors() -> Result<Vec<Monitor>> {
     Err(anyhow!(
         "plugin-screen-recorder: monitor enumeration is not implemented on macOS"
     ))
 }
 
+pub fn current_monitors() -> Result<Vec<Monitor>> {
+    get_monitors()
+}
+
 pub fn full_screen_bounds() -> Result<Monitor> {
     Err(anyhow!(
         "plugin-screen-recorder: full screen bounds are not implemented on macOS"
     ))
 }
 
-pub fn start_capture(_rect: &Rect, _config: &Config, _output_file: &Path) -> Result<u32> {
+pub fn preview_selection(_rect: Rect, _adjustments: &[&str], _secs: u64) -> Result<bool> {
+    Ok(true)
+}
+
+pub fn confirm(_title: &str, _text: &str) -> Result<bool> {
+    Ok(true)
+}
+
+pub fn prompt_text(_title: &str, _text: &str) -> Result<Option<String>> {
+    Ok(None)
+}
+
+pub fn start_capture(
+    _rect: &Rect,
+    _config: &Config,
+    _audio: &AudioDevices,
+    _output_file: &Path,
+    _id: u32,
+) -> Result<u32> {
     Err(anyhow!(
         "plugin-screen-recorder: capture start is not implemented on macOS"
     ))
 }
 
+pub fn route_audio(audio: &AudioConfig) -> Result<AudioDevices> {
+    Ok(AudioDevices::from_config(audio))
+}
+
+pub fn release_audio(_devices: &AudioDevices) {}
+
 pub fn stop_capture(_pid: u32) -> Result<()> {
     Err(anyhow!(
         "plugin-screen-recorder: capture stop is not implemented on macOS"
@@ -37,12 +97,113 @@ pub fn process_alive(_pid: u32) -> bool {
     false
 }
 
+pub fn check_display() -> Result<()> {
+    Ok(())
+}
+
+pub fn check_audio_server() -> Result<()> {
+    Ok(())
+}
+
+pub fn idle_secs() -> Result<u64> {
+    Err(anyhow!(
+        "plugin-screen-recorder: idle detection is not implemented on macOS"
+    ))
+}
+
 pub fn show_notification(_title: &str, _message: &str, _timeout_ms: u32) {
     // Notifications are fire-and-forget UX; silently no-op on macOS.
 }
 
+pub fn show_notification_with_actions(
+    _title: &str,
+    _message: &str,
+    _timeout_ms: u32,
+    _actions: &[(&str, super::NotificationAction)],
+) {
+    // Notifications are fire-and-forget UX; silently no-op on macOS.
+}
+
+pub fn edit_settings(_form: &SettingsForm) -> Result<Option<SettingsForm>> {
+    Err(anyhow!(
+        "plugin-screen-recorder: native settings window is not implemented on macOS"
+    ))
+}
+
+pub fn toggle_annotation() -> Result<()> {
+    Err(anyhow!(
+        "plugin-screen-recorder: screen annotation is not implemented on macOS"
+    ))
+}
+
+pub fn clear_annotations() -> Result<()> {
+    Ok(())
+}
+
+pub fn trash(_path: &Path) -> Result<()> {
+    Err(anyhow!(
+        "plugin-screen-recorder: moving files to the trash is not implemented on macOS"
+    ))
+}
+
+pub fn pointer_screen() -> Option<u32> {
+    None
+}
+
+pub fn show_stop_button(_x: i32, _y: i32, _size: (i32, i32)) -> Result<Child> {
+    Err(anyhow!(
+        "plugin-screen-recorder: the stop button is not implemented on macOS"
+    ))
+}
+
+pub fn stacked_windows() -> Result<Vec<(String, Rect)>> {
+    Err(anyhow!(
+        "plugin-screen-recorder: window lookup is not implemented on macOS"
+    ))
+}
+
+pub fn pointer_position() -> Result<(i32, i32)> {
+    Err(anyhow!(
+        "plugin-screen-recorder: pointer lookup is not implemented on macOS"
+    ))
+}
+
+pub fn send_filter_command(_port: u16, _command: &str) -> Result<()> {
+    Err(anyhow!(
+        "plugin-screen-recorder: live filter control is not implemented on macOS"
+    ))
+}
+
+pub fn copy_image_to_clipboard(_image: &Path) -> Result<()> {
+    Err(anyhow!(
+        "plugin-screen-recorder: clipboard images are not implemented on macOS"
+    ))
+}
+
 pub fn open_settings() -> Result<()> {
     Err(anyhow!(
         "plugin-screen-recorder: settings launcher is not implemented on macOS"
     ))
 }
+
+pub fn listen_for_clicks(_on_click: impl Fn() + Send + 'static) -> Result<Child> {
+    Err(anyhow!(
+        "plugin-screen-recorder: click detection is not implemented on macOS"
+    ))
+}
+
+pub fn record_audio_sample(
+    _devices: &AudioDevices,
+    _seconds: u32,
+    _dir: &Path,
+) -> Result<Vec<(String, PathBuf)>> {
+    Err(anyhow!(
+        "plugin-screen-recorder: audio capture is not implemented on macOS"
+    ))
+}
+
+pub fn play_audio(_file: &Path) -> Result<()> {
+    Err(anyhow!(
+        "plugin-screen-recorder: audio playback is not implemented on macOS"
+    ))
+}
@@ -1,10 +1,16 @@
 #[cfg(target_os = "linux")]
+mod i3ipc;
+#[cfg(target_os = "linux")]
 mod linux;
 #[cfg(target_os = "macos")]
 mod macos;
+#[cfg(target_os = "linux")]
+mod runner;
 #[cfg(target_os = "windows")]
 mod windows;
 
+#[cfg(target_os = "linux")]
+pub use i3ipc::{focused_workspace, window_rect, workspace_rect, workspace_visible};
 #[cfg(target_os = "linux")]
 pub use linux::*;
 #[cfg(target_os = "macos")]
@@ -12,4 +18,66 @@ pub use macos::*;
 #[cfg(target_os = "windows")]
 pub use windows::*;
 
-pub const CAPTURE_LOG: &str = "/tmp/record-region.log";
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::NotificationLevel;
+
+static NOTIFICATIONS_MUTED: AtomicBool = AtomicBool::new(false);
+static NOTIFICATION_LEVEL: AtomicU8 = AtomicU8::new(NotificationLevel::All as u8);
+
+/// What a notification button does when clicked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotificationAction {
+    /// Opens a URL in the default browser.
+    OpenUrl(String),
+    /// Runs a program with its arguments.
+    Run(Vec<String>),
+}
+
+pub fn capture_log(id: u32) -> PathBuf {
+    PathBuf::from(format!("/tmp/record-region-{}.log", id))
+}
+
+/// Where notifications are written when there is no notification daemon
+/// to show them.
+#[allow(dead_code, reason = "only the linux platform impl shows notifications")]
+pub fn notification_log() -> PathBuf {
+    PathBuf::from(crate::state::STATE_DIR).join("notifications.log")
+}
+
+/// TCP port the capture's zmq filter listens on for live filter commands.
+pub fn filter_control_port(id: u32) -> u16 {
+    5560 + id as u16
+}
+
+/// Silences desktop notifications for the rest of the process, for runs with
+/// nobody watching the screen.
+pub fn mute_notifications() {
+    NOTIFICATIONS_MUTED.store(true, Ordering::Relaxed);
+}
+
+/// Applies `record.notifications` for the rest of the process.
+pub fn set_notification_level(level: NotificationLevel) {
+    NOTIFICATION_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Whether routine feedback such as "Recording started" is shown, as
+/// opposed to failures and warnings only.
+pub fn routine_notifications_shown() -> bool {
+    NOTIFICATION_LEVEL.load(Ordering::Relaxed) == NotificationLevel::All as u8
+}
+
+/// A notification that only confirms what the user just did. Left out at
+/// the `important` level, so a shared screen shows nothing but problems.
+pub fn show_status(title: &str, message: &str, timeout_ms: u32) {
+    if routine_notifications_shown() {
+        show_notification(title, message, timeout_ms);
+    }
+}
+
+#[allow(dead_code, reason = "only the linux platform impl shows notifications")]
+fn notifications_muted() -> bool {
+    NOTIFICATIONS_MUTED.load(Ordering::Relaxed)
+        || NOTIFICATION_LEVEL.load(Ordering::Relaxed) == NotificationLevel::Silent as u8
+}
@@ -0,0 +1,135 @@
+use std::io;
+use std::process::{Child, Command, ExitStatus, Output};
+
+/// Starts external programs. Code that shells out to slop, xrandr or ffmpeg
+/// goes through one of these, so tests can stand in canned output and
+/// failures for a live X session.
+pub trait Runner {
+    /// Runs `command` to completion and collects what it printed.
+    fn output(&self, command: &mut Command) -> io::Result<Output>;
+
+    /// Starts `command` without waiting for it.
+    fn spawn(&self, command: &mut Command) -> io::Result<Box<dyn Process>>;
+}
+
+/// A program started by a [`Runner`].
+pub trait Process {
+    fn id(&self) -> u32;
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>>;
+    /// Kills the program and reaps it.
+    fn kill(&mut self) -> io::Result<()>;
+    /// Waits for the exit and collects whatever was piped.
+    fn wait_with_output(self: Box<Self>) -> io::Result<Output>;
+}
+
+/// Runs programs for real.
+pub struct System;
+
+impl Runner for System {
+    fn output(&self, command: &mut Command) -> io::Result<Output> {
+        command.output()
+    }
+
+    fn spawn(&self, command: &mut Command) -> io::Result<Box<dyn Process>> {
+        Ok(Box::new(command.spawn()?))
+    }
+}
+
+impl Process for Child {
+    fn id(&self) -> u32 {
+        Child::id(self)
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Child::try_wait(self)
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        Child::kill(self)?;
+        Child::wait(self).map(|_| ())
+    }
+
+    fn wait_with_output(self: Box<Self>) -> io::Result<Output> {
+        Child::wait_with_output(*self)
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::io;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{Command, ExitStatus, Output};
+
+    use super::{Process, Runner};
+
+    /// Answers each program with a canned exit code and output, and keeps
+    /// the command lines it was asked to run. Unknown programs are missing.
+    #[derive(Default)]
+    pub struct FakeRunner {
+        replies: HashMap<String, (i32, String, String)>,
+        pub calls: RefCell<Vec<Vec<String>>>,
+    }
+
+    impl FakeRunner {
+        pub fn reply(mut self, program: &str, code: i32, stdout: &str, stderr: &str) -> Self {
+            self.replies.insert(
+                program.to_string(),
+                (code, stdout.to_string(), stderr.to_string()),
+            );
+            self
+        }
+
+        fn run(&self, command: &Command) -> io::Result<Output> {
+            let program = command.get_program().to_string_lossy().to_string();
+            let mut line = vec![program.clone()];
+            line.extend(
+                command
+                    .get_args()
+                    .map(|arg| arg.to_string_lossy().to_string()),
+            );
+            self.calls.borrow_mut().push(line);
+            let (code, stdout, stderr) = self
+                .replies
+                .get(&program)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+            Ok(Output {
+                status: ExitStatus::from_raw(code << 8),
+                stdout: stdout.clone().into_bytes(),
+                stderr: stderr.clone().into_bytes(),
+            })
+        }
+    }
+
+    impl Runner for FakeRunner {
+        fn output(&self, command: &mut Command) -> io::Result<Output> {
+            self.run(command)
+        }
+
+        fn spawn(&self, command: &mut Command) -> io::Result<Box<dyn Process>> {
+            Ok(Box::new(Finished(self.run(command)?)))
+        }
+    }
+
+    /// A spawned program that has already exited.
+    struct Finished(Output);
+
+    impl Process for Finished {
+        fn id(&self) -> u32 {
+            u32::MAX
+        }
+
+        fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+            Ok(Some(self.0.status))
+        }
+
+        fn kill(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn wait_with_output(self: Box<Self>) -> io::Result<Output> {
+            Ok(self.0)
+        }
+    }
+}
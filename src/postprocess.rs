@@ -0,0 +1,1387 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+use crate::container;
+use crate::failure::{spawn_error, Failure};
+use crate::state::Note;
+use crate::{AudioConfig, GifConfig, PostConfig, SplitAudioFormat, SubtitleConfig, VideoConfig};
+
+/// Footage shorter than this fraction of the expected length is treated as
+/// truncated, after allowing for the encoder's startup delay.
+const TRUNCATION_RATIO: f64 = 0.9;
+const TRUNCATION_SLACK_SECS: f64 = 2.0;
+
+/// Longest a `mark` note stays on screen in the notes subtitles.
+const NOTE_CUE_SECS: i64 = 4;
+/// Silences this close to either end of the file count as touching it.
+const EDGE_TOLERANCE_SECS: f64 = 0.05;
+
+/// Losslessly joins `segments` into `output` with the concat demuxer.
+pub(crate) fn concat(segments: &[PathBuf], output: &Path) -> Result<()> {
+    let list_path = output.with_extension("concat.txt");
+    let list: String = segments
+        .iter()
+        .map(|segment| {
+            let escaped = segment.to_string_lossy().replace('\'', "'\\''");
+            format!("file '{}'\n", escaped)
+        })
+        .collect();
+    fs::write(&list_path, list).context("failed to write concat list")?;
+    let result = run(
+        ffmpeg()
+            .args(["-f", "concat", "-safe", "0", "-i"])
+            .arg(&list_path)
+            .args(["-c", "copy"])
+            .arg(output),
+        "concat",
+    );
+    let _ = fs::remove_file(&list_path);
+    result
+}
+
+/// Fails unless every file carries the same streams with the same codecs,
+/// sizes and sample rates, which the concat demuxer needs to join them
+/// without re-encoding.
+pub(crate) fn check_joinable(files: &[PathBuf]) -> Result<()> {
+    let Some((first, rest)) = files.split_first() else {
+        return Ok(());
+    };
+    let expected = stream_layout(first)?;
+    for file in rest {
+        if stream_layout(file)? != expected {
+            return Err(anyhow!(
+                "{} does not match the format of {}; convert it first",
+                file.display(),
+                first.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Adds `take` to the end of `base` in place and removes `take`.
+pub(crate) fn append(base: &Path, take: &Path) -> Result<()> {
+    let files = [base.to_path_buf(), take.to_path_buf()];
+    check_joinable(&files)?;
+    let tmp = temp_sibling(base);
+    if let Err(error) = concat(&files, &tmp) {
+        let _ = fs::remove_file(&tmp);
+        return Err(error);
+    }
+    fs::rename(&tmp, base).context("failed to replace recording after append")?;
+    let _ = fs::remove_file(take);
+    Ok(())
+}
+
+fn stream_layout(file: &Path) -> Result<String> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "stream=codec_type,codec_name,width,height,pix_fmt,sample_rate,channels",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(file)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error("ffprobe", error))?;
+    if !output.status.success() {
+        return Err(anyhow!("ffprobe could not read {}", file.display()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Rewrites `file` in place with one chapter per marker.
+pub(crate) fn write_chapters(
+    file: &Path,
+    markers: &[i64],
+    notes: &[Note],
+    total_secs: i64,
+) -> Result<()> {
+    let metadata_path = file.with_extension("chapters.txt");
+    fs::write(&metadata_path, chapter_metadata(markers, notes, total_secs))
+        .context("failed to write chapter metadata")?;
+    let result = rewrite(file, "chapter mux", |command, tmp| {
+        command
+            .arg("-i")
+            .arg(file)
+            .arg("-i")
+            .arg(&metadata_path)
+            .args(["-map", "0", "-map_metadata", "1", "-map_chapters", "1"])
+            .args(["-codec", "copy"])
+            .arg(tmp);
+    });
+    let _ = fs::remove_file(&metadata_path);
+    result
+}
+
+/// Single-pass EBU R128 normalization of every audio stream; video is copied.
+pub(crate) fn normalize_loudness(file: &Path, target_lufs: f64, audio: &AudioConfig) -> Result<()> {
+    rewrite(file, "loudness normalization", |command, tmp| {
+        command
+            .arg("-i")
+            .arg(file)
+            .args(["-map", "0", "-c", "copy"])
+            .arg("-af")
+            .arg(format!("loudnorm=I={}:TP=-1.5:LRA=11", target_lufs))
+            .args(audio.encoder_args())
+            .args(["-ar", &audio.sample_rate.to_string()])
+            .arg(tmp);
+    })
+}
+
+/// Mixes a short click into the audio at each offset (seconds into the
+/// footage). A recording without audio gets the clicks as its only track.
+pub(crate) fn mix_clicks(
+    file: &Path,
+    clicks: &[f64],
+    has_audio: bool,
+    volume: f64,
+    audio: &AudioConfig,
+) -> Result<()> {
+    let graph = click_graph(clicks, has_audio, volume);
+    rewrite(file, "click mixing", |command, tmp| {
+        command
+            .arg("-i")
+            .arg(file)
+            .arg("-filter_complex")
+            .arg(graph)
+            .args(["-map", "0:v", "-map", "[aout]", "-c:v", "copy"])
+            .args(audio.encoder_args())
+            .args(["-ar", &audio.sample_rate.to_string()]);
+        if !has_audio {
+            command.arg("-shortest");
+        }
+        command.arg(tmp);
+    })
+}
+
+/// Formats `convert` can turn a recording into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConvertTarget {
+    /// Animated PNG: lossless full color, for short UI captures where a
+    /// GIF palette would band gradients.
+    Apng,
+    Gif,
+    Mp4,
+    Webm,
+}
+
+impl ConvertTarget {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "apng" => Some(Self::Apng),
+            "gif" => Some(Self::Gif),
+            "mp4" => Some(Self::Mp4),
+            "webm" => Some(Self::Webm),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            // Viewers and browsers only animate APNG under the .png name.
+            Self::Apng => "png",
+            Self::Gif => "gif",
+            Self::Mp4 => "mp4",
+            Self::Webm => "webm",
+        }
+    }
+}
+
+/// Transcodes `input` into `output`, optionally scaled down to `width`
+/// pixels wide. MP4 reuses the configured encoder when MP4 can hold it.
+pub(crate) fn convert(
+    input: &Path,
+    output: &Path,
+    target: ConvertTarget,
+    width: Option<u32>,
+    video: &VideoConfig,
+    gif: &GifConfig,
+) -> Result<()> {
+    let scale = width.map(|width| format!("scale={}:-2:flags=lanczos", width));
+    let mut command = ffmpeg();
+    command.arg("-i").arg(input);
+    match target {
+        ConvertTarget::Gif => {
+            // One pass: build a palette from the clip itself, then map onto it.
+            let mut chain = vec![format!("fps={}", gif.fps.max(1))];
+            chain.extend(scale);
+            command
+                .arg("-filter_complex")
+                .arg(gif_graph("[0:v]", &chain.join(","), "", gif));
+        }
+        _ => {
+            if let Some(scale) = scale {
+                command.arg("-vf").arg(scale);
+            }
+        }
+    }
+    command.args(target_args(target, video)?);
+    command.arg(output);
+    run(&mut command, "conversion")?;
+    if target == ConvertTarget::Gif && gif.gifsicle {
+        optimize_gif(output, gif)?;
+    }
+    Ok(())
+}
+
+/// Builds a palette from `input` after `chain`, then maps it onto the clip
+/// and labels the result `label`.
+fn gif_graph(input: &str, chain: &str, label: &str, gif: &GifConfig) -> String {
+    format!(
+        "{}{},split[a{}][b{}];[a{}]palettegen=max_colors={}:stats_mode=diff[p{}];\
+         [b{}][p{}]paletteuse=dither={}:diff_mode=rectangle{}",
+        input,
+        chain,
+        label,
+        label,
+        label,
+        gif.colors.clamp(2, 256),
+        label,
+        label,
+        label,
+        gif.dither.filter_name(),
+        if label.is_empty() {
+            String::new()
+        } else {
+            format!("[{}]", label)
+        }
+    )
+}
+
+/// Encoder and muxer arguments for one output of `target`.
+fn target_args(target: ConvertTarget, video: &VideoConfig) -> Result<Vec<String>> {
+    let strings = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    Ok(match target {
+        ConvertTarget::Gif => strings(&["-an"]),
+        ConvertTarget::Apng => strings(&["-an", "-plays", "0", "-pred", "mixed", "-f", "apng"]),
+        ConvertTarget::Mp4 => {
+            let mut video = video.clone();
+            if container::check_codecs("mp4", &video.codec, None).is_err() {
+                video.codec = "libx264".to_string();
+            }
+            let mut args = video.encoder_args()?;
+            args.extend(strings(&[
+                "-c:a",
+                "aac",
+                "-b:a",
+                "192k",
+                "-movflags",
+                "+faststart",
+            ]));
+            args
+        }
+        ConvertTarget::Webm => {
+            let mut args = strings(&["-c:v", "libvpx-vp9", "-b:v", "0", "-row-mt", "1", "-crf"]);
+            args.push(video.crf.to_string());
+            args.extend(strings(&["-c:a", "libopus", "-b:a", "128k"]));
+            args
+        }
+    })
+}
+
+/// One extra copy of a recording in `output.renditions`: a format and an
+/// optional height it is scaled down to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Rendition {
+    pub target: ConvertTarget,
+    pub height: Option<u32>,
+}
+
+impl Rendition {
+    /// Parses `FORMAT` or `FORMAT:HEIGHT`, such as `gif` or `mp4:720`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || {
+            Failure::Usage(format!(
+                "invalid rendition {:?}; use FORMAT or FORMAT:HEIGHT, such as mp4:720",
+                spec
+            ))
+        };
+        let (format, height) = match spec.split_once(':') {
+            Some((format, height)) => (
+                format,
+                Some(
+                    height
+                        .trim_end_matches('p')
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|height| *height >= 2)
+                        .ok_or_else(invalid)?,
+                ),
+            ),
+            None => (spec, None),
+        };
+        let target = ConvertTarget::parse(format.trim()).ok_or_else(invalid)?;
+        Ok(Self { target, height })
+    }
+
+    /// `clip.720p.mp4` next to `recording`, or `clip.gif` when unscaled.
+    pub fn path(self, recording: &Path) -> PathBuf {
+        let stem = recording
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name = match self.height {
+            Some(height) => format!("{}.{}p.{}", stem, height, self.target.extension()),
+            None => format!("{}.{}", stem, self.target.extension()),
+        };
+        recording.with_file_name(name)
+    }
+}
+
+/// Parses every `output.renditions` entry, failing on the first bad one.
+pub(crate) fn parse_renditions(specs: &[String]) -> Result<Vec<Rendition>> {
+    specs.iter().map(|spec| Rendition::parse(spec)).collect()
+}
+
+/// Writes every rendition of `input` in a single ffmpeg run that decodes
+/// the recording once. Returns the files written.
+pub(crate) fn render_renditions(
+    input: &Path,
+    renditions: &[Rendition],
+    has_audio: bool,
+    video: &VideoConfig,
+    gif: &GifConfig,
+) -> Result<Vec<PathBuf>> {
+    let renditions: Vec<(Rendition, PathBuf)> = renditions
+        .iter()
+        .map(|rendition| (*rendition, rendition.path(input)))
+        .filter(|(_, path)| path != input)
+        .collect();
+    if renditions.is_empty() {
+        return Ok(Vec::new());
+    }
+    let targets: Vec<Rendition> = renditions.iter().map(|(rendition, _)| *rendition).collect();
+    let mut command = ffmpeg();
+    command
+        .arg("-i")
+        .arg(input)
+        .arg("-filter_complex")
+        .arg(rendition_graph(&targets, gif));
+    for (index, (rendition, path)) in renditions.iter().enumerate() {
+        command.args(["-map", &format!("[r{}]", index)]);
+        let audible = matches!(rendition.target, ConvertTarget::Mp4 | ConvertTarget::Webm);
+        if audible && has_audio {
+            command.args(["-map", "0:a"]);
+        }
+        command
+            .args(target_args(rendition.target, video)?)
+            .arg(path);
+    }
+    if let Err(error) = run(&mut command, "renditions") {
+        for (_, path) in &renditions {
+            let _ = fs::remove_file(path);
+        }
+        return Err(error);
+    }
+    for (rendition, path) in &renditions {
+        if rendition.target == ConvertTarget::Gif && gif.gifsicle {
+            optimize_gif(path, gif)?;
+        }
+    }
+    Ok(renditions.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Splits the decoded video once per rendition, scales each to its height
+/// (never up) and labels the results `[r0]`, `[r1]`, and so on.
+fn rendition_graph(renditions: &[Rendition], gif: &GifConfig) -> String {
+    let labels: String = (0..renditions.len())
+        .map(|index| format!("[s{}]", index))
+        .collect();
+    let mut graph = vec![format!("[0:v]split={}{}", renditions.len(), labels)];
+    for (index, rendition) in renditions.iter().enumerate() {
+        let mut chain = Vec::new();
+        if rendition.target == ConvertTarget::Gif {
+            chain.push(format!("fps={}", gif.fps.max(1)));
+        }
+        chain.push(match rendition.height {
+            Some(height) => format!("scale=-2:'min({},ih)':flags=lanczos", height),
+            None => "null".to_string(),
+        });
+        let input = format!("[s{}]", index);
+        let label = format!("r{}", index);
+        graph.push(match rendition.target {
+            ConvertTarget::Gif => gif_graph(&input, &chain.join(","), &label, gif),
+            _ => format!("{}{}[{}]", input, chain.join(","), label),
+        });
+    }
+    graph.join(";")
+}
+
+/// Rewrites `file` in place with gifsicle.
+fn optimize_gif(file: &Path, gif: &GifConfig) -> Result<()> {
+    let mut command = Command::new(&gif.gifsicle_path);
+    command
+        .arg("--batch")
+        .arg(format!("-O{}", gif.gifsicle_level.clamp(1, 3)));
+    if gif.lossy > 0 {
+        command.arg(format!("--lossy={}", gif.lossy));
+    }
+    let output = command
+        .arg(file)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error("gifsicle", error))
+        .context("GIF optimization could not start; the unoptimized GIF was kept")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("gifsicle failed: {}", stderr.trim()));
+    }
+    Ok(())
+}
+
+/// Re-encodes `input` into an H.264 MP4 of at most `target_mb` megabytes
+/// using a two-pass average bitrate.
+pub(crate) fn compress_to_size(input: &Path, output: &Path, target_mb: f64) -> Result<()> {
+    const AUDIO_KBPS: f64 = 96.0;
+    // Container overhead and rate control overshoot eat a few percent.
+    const HEADROOM: f64 = 0.95;
+    const MIN_VIDEO_KBPS: f64 = 100.0;
+    let duration = probe_duration(input)?;
+    let budget_kbps = target_mb * 8.0 * 1000.0 * HEADROOM / duration.max(0.1);
+    let video_kbps = budget_kbps - AUDIO_KBPS;
+    if video_kbps < MIN_VIDEO_KBPS {
+        return Err(anyhow!(
+            "{:.0}s of video cannot fit in {} MB",
+            duration,
+            target_mb
+        ));
+    }
+    let bitrate = format!("{:.0}k", video_kbps);
+    let passlog = env::temp_dir().join(format!("screen-recorder-pass-{}", process::id()));
+
+    let mut first = ffmpeg();
+    first
+        .arg("-i")
+        .arg(input)
+        .args(["-c:v", "libx264", "-preset", "medium", "-b:v", &bitrate])
+        .args(["-pass", "1", "-passlogfile"])
+        .arg(&passlog)
+        .args(["-an", "-f", "null", "-"]);
+    let mut second = ffmpeg();
+    second
+        .arg("-i")
+        .arg(input)
+        .args(["-c:v", "libx264", "-preset", "medium", "-b:v", &bitrate])
+        .args(["-pix_fmt", "yuv420p", "-pass", "2", "-passlogfile"])
+        .arg(&passlog)
+        .args(["-c:a", "aac", "-b:a", &format!("{:.0}k", AUDIO_KBPS)])
+        .args(["-movflags", "+faststart"])
+        .arg(output);
+    let result =
+        run(&mut first, "compression analysis").and_then(|()| run(&mut second, "compression"));
+    for suffix in ["-0.log", "-0.log.mbtree"] {
+        let mut log = passlog.clone().into_os_string();
+        log.push(suffix);
+        let _ = fs::remove_file(log);
+    }
+    result
+}
+
+/// Writes `input` to `output` played at `speed`, either as a whole or only
+/// between `from` and `to` seconds, re-encoding with the configured encoder.
+pub(crate) fn retime(
+    input: &Path,
+    output: &Path,
+    speed: f64,
+    from: Option<f64>,
+    to: Option<f64>,
+    video: &VideoConfig,
+    audio: &AudioConfig,
+) -> Result<()> {
+    let has_audio = stream_layout(input)?
+        .lines()
+        .any(|line| line.contains("audio"));
+    let graph = retime_graph(speed, from, to, has_audio);
+    let mut command = ffmpeg();
+    command
+        .arg("-i")
+        .arg(input)
+        .arg("-filter_complex")
+        .arg(graph)
+        .args(["-map", "[v]"]);
+    if has_audio {
+        command.args(["-map", "[a]"]).args(audio.encoder_args());
+    }
+    command.args(video.encoder_args()?).arg(output);
+    run(&mut command, "retime")
+}
+
+/// Splits the footage at `from`/`to`, speeds up (or slows down) the middle
+/// piece and joins the pieces back together.
+fn retime_graph(speed: f64, from: Option<f64>, to: Option<f64>, audio: bool) -> String {
+    let mut pieces: Vec<(String, f64)> = Vec::new();
+    if let Some(from) = from.filter(|from| *from > 0.0) {
+        pieces.push((format!("end={}", from), 1.0));
+    }
+    let middle = match (from.filter(|from| *from > 0.0), to) {
+        (Some(from), Some(to)) => format!("start={}:end={}", from, to),
+        (Some(from), None) => format!("start={}", from),
+        (None, Some(to)) => format!("end={}", to),
+        (None, None) => String::new(),
+    };
+    pieces.push((middle, speed));
+    if let Some(to) = to {
+        pieces.push((format!("start={}", to), 1.0));
+    }
+
+    let n = pieces.len();
+    let mut graph = format!("[0:v]split={}", n);
+    for index in 0..n {
+        graph.push_str(&format!("[vs{}]", index));
+    }
+    if audio {
+        graph.push_str(&format!(";[0:a]asplit={}", n));
+        for index in 0..n {
+            graph.push_str(&format!("[as{}]", index));
+        }
+    }
+    let mut joined = String::new();
+    for (index, (range, factor)) in pieces.iter().enumerate() {
+        let trim = if range.is_empty() {
+            String::new()
+        } else {
+            format!("trim={},", range)
+        };
+        graph.push_str(&format!(
+            ";[vs{0}]{1}setpts=(PTS-STARTPTS)/{2}[v{0}]",
+            index, trim, factor
+        ));
+        joined.push_str(&format!("[v{}]", index));
+        if audio {
+            let atrim = if range.is_empty() {
+                String::new()
+            } else {
+                format!("atrim={},", range)
+            };
+            graph.push_str(&format!(
+                ";[as{0}]{1}asetpts=PTS-STARTPTS,{2}[a{0}]",
+                index,
+                atrim,
+                atempo_chain(*factor)
+            ));
+            joined.push_str(&format!("[a{}]", index));
+        }
+    }
+    graph.push_str(&format!(
+        ";{}concat=n={}:v=1:a={}[v]{}",
+        joined,
+        n,
+        u8::from(audio),
+        if audio { "[a]" } else { "" }
+    ));
+    graph
+}
+
+/// `atempo` only accepts factors from 0.5 to 2 on older ffmpeg, so larger
+/// changes are chained.
+fn atempo_chain(mut factor: f64) -> String {
+    let mut steps = Vec::new();
+    while factor > 2.0 {
+        steps.push("atempo=2".to_string());
+        factor /= 2.0;
+    }
+    while factor < 0.5 {
+        steps.push("atempo=0.5".to_string());
+        factor /= 0.5;
+    }
+    steps.push(format!("atempo={}", factor));
+    steps.join(",")
+}
+
+/// Saves one PNG every `interval_secs` of footage into `directory` as
+/// `frame-00001.png`, `frame-00002.png`, ….
+pub(crate) fn extract_frames_every(
+    input: &Path,
+    directory: &Path,
+    interval_secs: f64,
+) -> Result<()> {
+    fs::create_dir_all(directory).context("failed to create frames directory")?;
+    run(
+        ffmpeg()
+            .arg("-i")
+            .arg(input)
+            .arg("-vf")
+            .arg(format!("fps=1/{}", interval_secs))
+            .arg(directory.join("frame-%05d.png")),
+        "frame extraction",
+    )
+}
+
+/// Saves the frame shown `at_secs` into the footage as a PNG.
+pub(crate) fn extract_frame_at(input: &Path, at_secs: f64, output: &Path) -> Result<()> {
+    run(
+        ffmpeg()
+            .arg("-ss")
+            .arg(format!("{:.3}", at_secs))
+            .arg("-i")
+            .arg(input)
+            .args(["-frames:v", "1"])
+            .arg(output),
+        "frame extraction",
+    )
+}
+
+/// Drops `first` frames from the start and `last` from the end of `file`.
+/// Returns `false` when there was nothing to drop or the recording is too
+/// short to lose that many frames.
+pub(crate) fn trim_frames(
+    file: &Path,
+    first: u32,
+    last: u32,
+    fps: u32,
+    video: &VideoConfig,
+    audio: Option<&AudioConfig>,
+) -> Result<bool> {
+    let duration = probe_duration(file)?;
+    let Some((start, end)) = frame_window(duration, fps, first, last) else {
+        return Ok(false);
+    };
+    let encoder = video.encoder_args()?;
+    rewrite(file, "frame trim", |command, tmp| {
+        command
+            .arg("-i")
+            .arg(file)
+            .arg("-vf")
+            .arg(format!(
+                "trim=start={:.3}:end={:.3},setpts=PTS-STARTPTS",
+                start, end
+            ))
+            .args(&encoder);
+        match audio {
+            Some(audio) => command
+                .arg("-af")
+                .arg(format!(
+                    "atrim=start={:.3}:end={:.3},asetpts=PTS-STARTPTS",
+                    start, end
+                ))
+                .args(audio.encoder_args()),
+            None => command.arg("-an"),
+        };
+        command.arg(tmp);
+    })?;
+    Ok(true)
+}
+
+/// The span left after dropping `first` and `last` frames, or `None` when
+/// nothing is dropped or less than a frame would remain.
+fn frame_window(duration: f64, fps: u32, first: u32, last: u32) -> Option<(f64, f64)> {
+    if fps == 0 || first + last == 0 {
+        return None;
+    }
+    let frame = 1.0 / f64::from(fps);
+    let start = f64::from(first) * frame;
+    let end = duration - f64::from(last) * frame;
+    (end - start >= frame).then_some((start, end))
+}
+
+/// Cuts leading and trailing silence and, when `post.trim_mid_silence_secs`
+/// is set, every silence at least that long. Returns `false` when nothing
+/// needed trimming.
+pub(crate) fn trim_silence(
+    file: &Path,
+    post: &PostConfig,
+    video: &VideoConfig,
+    audio: &AudioConfig,
+) -> Result<bool> {
+    let duration = probe_duration(file)?;
+    let silences = detect_silences(file, post.silence_threshold_db, post.min_silence_secs)?;
+    let mid_min = (post.trim_mid_silence_secs > 0.0).then_some(post.trim_mid_silence_secs);
+    let keep = keep_intervals(&silences, duration, mid_min);
+    if keep.is_empty() || keep == [(0.0, duration)] {
+        return Ok(false);
+    }
+
+    let ranges: Vec<String> = keep
+        .iter()
+        .map(|(start, end)| format!("between(t,{:.3},{:.3})", start, end))
+        .collect();
+    let expression = ranges.join("+");
+    let encoder = video.encoder_args()?;
+    rewrite(file, "silence trim", |command, tmp| {
+        command
+            .arg("-i")
+            .arg(file)
+            .arg("-vf")
+            .arg(format!("select='{}',setpts=N/FRAME_RATE/TB", expression))
+            .arg("-af")
+            .arg(format!("aselect='{}',asetpts=N/SR/TB", expression))
+            .args(&encoder)
+            .args(audio.encoder_args())
+            .arg(tmp);
+    })?;
+    Ok(true)
+}
+
+/// Runs whisper.cpp over the recording's audio and returns the `.srt` it
+/// wrote next to `file`. `audio_filter` narrows the audio to the voice track.
+pub(crate) fn transcribe(
+    file: &Path,
+    subtitles: &SubtitleConfig,
+    audio_filter: Option<&str>,
+) -> Result<PathBuf> {
+    if subtitles.model.is_empty() {
+        return Err(anyhow!("subtitles.model is not set"));
+    }
+    let wav = file.with_extension("whisper.wav");
+    let mut extract = ffmpeg();
+    extract.arg("-i").arg(file).arg("-vn");
+    if let Some(filter) = audio_filter {
+        extract.arg("-af").arg(filter);
+    }
+    extract
+        .args(["-ac", "1", "-ar", "16000", "-c:a", "pcm_s16le"])
+        .arg(&wav);
+    run(&mut extract, "audio extraction")?;
+
+    let base = file.with_extension("");
+    let mut whisper = Command::new(&subtitles.binary);
+    whisper
+        .arg("-m")
+        .arg(&subtitles.model)
+        .arg("-l")
+        .arg(&subtitles.language)
+        .arg("-f")
+        .arg(&wav)
+        .arg("-osrt")
+        .arg("-of")
+        .arg(&base)
+        .stdin(Stdio::null());
+    let output = whisper
+        .output()
+        .with_context(|| format!("failed to run {}", subtitles.binary));
+    let _ = fs::remove_file(&wav);
+    let output = output?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("{} failed: {}", subtitles.binary, stderr.trim()));
+    }
+    Ok(file.with_extension("srt"))
+}
+
+/// Adds `srt` to `file` as a subtitle stream in the container's native codec.
+pub(crate) fn mux_subtitles(file: &Path, srt: &Path) -> Result<()> {
+    let extension = file
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let codec = match extension.as_str() {
+        "mp4" | "mov" => "mov_text",
+        "webm" => "webvtt",
+        _ => "srt",
+    };
+    rewrite(file, "subtitle mux", |command, tmp| {
+        command
+            .arg("-i")
+            .arg(file)
+            .arg("-i")
+            .arg(srt)
+            .args(["-map", "0", "-map", "1", "-c", "copy", "-c:s", codec])
+            .arg(tmp);
+    })
+}
+
+/// Rewrites a fragmented MP4/MOV as a regular one with the index up front,
+/// for players that handle fragments poorly.
+pub(crate) fn defragment(file: &Path) -> Result<()> {
+    rewrite(file, "defragment", |command, tmp| {
+        command
+            .arg("-i")
+            .arg(file)
+            .args(["-map", "0", "-c", "copy", "-movflags", "+faststart"])
+            .arg(tmp);
+    })
+}
+
+/// Writes the first audio stream of `input` to `output` on its own.
+pub(crate) fn extract_audio(input: &Path, output: &Path, format: SplitAudioFormat) -> Result<()> {
+    run(
+        ffmpeg()
+            .arg("-i")
+            .arg(input)
+            .args(["-map", "0:a:0", "-vn"])
+            .args(format.encoder_args())
+            .arg(output),
+        "audio extraction",
+    )
+}
+
+/// Copies every stream of `input` into the container `output`'s extension
+/// names, converting subtitles to the text format that container takes.
+pub(crate) fn remux(input: &Path, output: &Path) -> Result<()> {
+    let mut command = ffmpeg();
+    command
+        .arg("-i")
+        .arg(input)
+        .args(["-map", "0", "-c", "copy"]);
+    if container::is_mp4_family(output) {
+        command.args(["-c:s", "mov_text", "-movflags", "+faststart"]);
+    } else if output.extension().is_some_and(|ext| ext == "webm") {
+        command.args(["-c:s", "webvtt"]);
+    }
+    command.arg(output);
+    run(&mut command, "remux")
+}
+
+/// Outcome of checking a finished recording with ffprobe.
+#[derive(Debug)]
+pub(crate) enum Verification {
+    Ok,
+    /// The file failed its first check but passed after a remux.
+    Repaired(String),
+    Corrupt(String),
+}
+
+#[derive(Debug, Deserialize)]
+struct Probe {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeStream {
+    codec_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+}
+
+/// Checks stream layout and duration, remuxing once to salvage a damaged
+/// container before giving up.
+pub(crate) fn verify_and_repair(
+    file: &Path,
+    expect_audio: bool,
+    expected_secs: f64,
+) -> Verification {
+    let problem = match check(file, expect_audio, expected_secs) {
+        Ok(()) => return Verification::Ok,
+        Err(error) => format!("{:#}", error),
+    };
+    let repaired = rewrite(file, "repair remux", |command, tmp| {
+        command
+            .args(["-err_detect", "ignore_err", "-fflags", "+genpts", "-i"])
+            .arg(file)
+            .args(["-map", "0", "-c", "copy"])
+            .arg(tmp);
+    });
+    match repaired.and_then(|()| check(file, expect_audio, expected_secs)) {
+        Ok(()) => Verification::Repaired(problem),
+        Err(_) => Verification::Corrupt(problem),
+    }
+}
+
+fn check(file: &Path, expect_audio: bool, expected_secs: f64) -> Result<()> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration:stream=codec_type",
+        ])
+        .args(["-of", "json"])
+        .arg(file)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error("ffprobe", error))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() || !stderr.trim().is_empty() {
+        return Err(anyhow!("ffprobe reported: {}", stderr.trim()));
+    }
+    let probe: Probe = serde_json::from_slice(&output.stdout).context("invalid ffprobe output")?;
+
+    let has = |kind: &str| probe.streams.iter().any(|stream| stream.codec_type == kind);
+    if !has("video") {
+        return Err(anyhow!("no video stream"));
+    }
+    if expect_audio && !has("audio") {
+        return Err(anyhow!("audio stream missing"));
+    }
+    let duration = probe
+        .format
+        .and_then(|format| format.duration)
+        .and_then(|duration| duration.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    if duration <= 0.0 {
+        return Err(anyhow!("duration is zero"));
+    }
+    if duration < expected_secs * TRUNCATION_RATIO - TRUNCATION_SLACK_SECS {
+        return Err(anyhow!(
+            "only {:.1}s of an expected {:.0}s",
+            duration,
+            expected_secs
+        ));
+    }
+    Ok(())
+}
+
+/// Container duration in seconds, as reported by ffprobe.
+pub(crate) fn probe_duration(file: &Path) -> Result<f64> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(file)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error("ffprobe", error))?;
+    if !output.status.success() {
+        return Err(anyhow!("ffprobe could not read {}", file.display()));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<f64>()
+        .context("invalid duration from ffprobe")
+}
+
+fn detect_silences(file: &Path, threshold_db: f64, min_secs: f64) -> Result<Vec<(f64, f64)>> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(file)
+        .args(["-map", "0:a:0", "-af"])
+        .arg(format!(
+            "silencedetect=noise={}dB:d={}",
+            threshold_db, min_secs
+        ))
+        .args(["-f", "null", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run ffmpeg for silence detection")?;
+    if !output.status.success() {
+        return Err(anyhow!("silence detection failed for {}", file.display()));
+    }
+    Ok(parse_silences(&String::from_utf8_lossy(&output.stderr)))
+}
+
+/// Seconds of the first audio stream quieter than `threshold_db` for at
+/// least `min_secs` at a time.
+pub(crate) fn silent_secs(
+    file: &Path,
+    threshold_db: f64,
+    min_secs: f64,
+    duration: f64,
+) -> Result<f64> {
+    let silences = detect_silences(file, threshold_db, min_secs)?;
+    Ok(total_silence(&silences, duration))
+}
+
+fn total_silence(silences: &[(f64, f64)], duration: f64) -> f64 {
+    silences
+        .iter()
+        .map(|&(start, end)| (end.min(duration) - start).max(0.0))
+        .sum()
+}
+
+/// Mean and peak volume of the first audio stream, in dBFS.
+pub(crate) fn audio_levels(file: &Path) -> Result<(f64, f64)> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(file)
+        .args(["-map", "0:a:0", "-af", "volumedetect", "-f", "null", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error("ffmpeg", error))?;
+    if !output.status.success() {
+        return Err(anyhow!("volume detection failed for {}", file.display()));
+    }
+    parse_volume(&String::from_utf8_lossy(&output.stderr))
+        .ok_or_else(|| anyhow!("no volume stats for {}", file.display()))
+}
+
+/// Reads `mean_volume` and `max_volume` off a volumedetect log. Digital
+/// silence is reported as `-inf dB`.
+fn parse_volume(log: &str) -> Option<(f64, f64)> {
+    let value_after = |key: &str| -> Option<f64> {
+        let line = log.lines().find(|line| line.contains(key))?;
+        let rest = &line[line.find(key)? + key.len()..];
+        rest.split_whitespace().next()?.parse().ok()
+    };
+    Some((value_after("mean_volume: ")?, value_after("max_volume: ")?))
+}
+
+/// Pairs `silence_start`/`silence_end` log lines; a trailing start without an
+/// end runs to the end of the file and is reported with `f64::INFINITY`.
+fn parse_silences(log: &str) -> Vec<(f64, f64)> {
+    let value_after = |line: &str, key: &str| -> Option<f64> {
+        let rest = &line[line.find(key)? + key.len()..];
+        rest.split_whitespace().next()?.parse().ok()
+    };
+    let mut silences = Vec::new();
+    let mut open = None;
+    for line in log.lines() {
+        if let Some(start) = value_after(line, "silence_start: ") {
+            open = Some(start);
+        } else if let Some(end) = value_after(line, "silence_end: ") {
+            if let Some(start) = open.take() {
+                silences.push((start, end));
+            }
+        }
+    }
+    if let Some(start) = open {
+        silences.push((start, f64::INFINITY));
+    }
+    silences
+}
+
+fn keep_intervals(silences: &[(f64, f64)], duration: f64, mid_min: Option<f64>) -> Vec<(f64, f64)> {
+    let mut keep = Vec::new();
+    let mut cursor = 0.0;
+    for &(start, end) in silences {
+        let end = end.min(duration);
+        let touches_start = start <= EDGE_TOLERANCE_SECS;
+        let touches_end = end >= duration - EDGE_TOLERANCE_SECS;
+        let long_mid = mid_min.is_some_and(|min| end - start >= min);
+        if !(touches_start || touches_end || long_mid) {
+            continue;
+        }
+        if start > cursor {
+            keep.push((cursor, start));
+        }
+        cursor = end;
+    }
+    if cursor < duration {
+        keep.push((cursor, duration));
+    }
+    keep
+}
+
+/// Runs an ffmpeg pass that reads `file` and writes a temporary sibling,
+/// then swaps the result into place.
+fn rewrite(file: &Path, what: &str, build: impl FnOnce(&mut Command, &Path)) -> Result<()> {
+    let tmp = temp_sibling(file);
+    let mut command = ffmpeg();
+    build(&mut command, &tmp);
+    if let Err(error) = run(&mut command, what) {
+        let _ = fs::remove_file(&tmp);
+        return Err(error);
+    }
+    fs::rename(&tmp, file).with_context(|| format!("failed to replace recording after {}", what))
+}
+
+/// One decaying 2 kHz blip per click, each delayed to its offset and summed
+/// without the level drop `amix` normally applies per input.
+fn click_graph(clicks: &[f64], has_audio: bool, volume: f64) -> String {
+    let volume = volume.clamp(0.0, 1.0);
+    let tone = format!("{}*sin(2*PI*2000*t)*exp(-60*t)", volume);
+    let mut graph = format!(
+        "aevalsrc='{0}|{0}':d=0.08:s=48000,asplit={1}",
+        tone,
+        clicks.len()
+    );
+    for index in 0..clicks.len() {
+        graph.push_str(&format!("[c{}]", index));
+    }
+    let mut mix_inputs = String::from(if has_audio { "[0:a]" } else { "" });
+    for (index, offset) in clicks.iter().enumerate() {
+        let delay_ms = (offset.max(0.0) * 1000.0).round() as u64;
+        graph.push_str(&format!(";[c{0}]adelay={1}|{1}[d{0}]", index, delay_ms));
+        mix_inputs.push_str(&format!("[d{}]", index));
+    }
+    let inputs = clicks.len() + usize::from(has_audio);
+    if has_audio {
+        graph.push_str(&format!(
+            ";{}amix=inputs={}:duration=first:normalize=0[aout]",
+            mix_inputs, inputs
+        ));
+    } else {
+        graph.push_str(&format!(
+            ";{}amix=inputs={}:duration=longest:normalize=0,apad[aout]",
+            mix_inputs, inputs
+        ));
+    }
+    graph
+}
+
+/// Chapters starting at each marker. A chapter whose marker came with a
+/// note is titled with it.
+fn chapter_metadata(markers: &[i64], notes: &[Note], total_secs: i64) -> String {
+    let mut starts: Vec<i64> = markers
+        .iter()
+        .copied()
+        .filter(|offset| *offset > 0 && *offset < total_secs)
+        .collect();
+    starts.sort_unstable();
+    starts.dedup();
+    starts.insert(0, 0);
+
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for (index, start) in starts.iter().enumerate() {
+        let end = starts.get(index + 1).copied().unwrap_or(total_secs);
+        let title = notes
+            .iter()
+            .find(|note| note.at == *start)
+            .map(|note| escape_metadata(&note.text))
+            .unwrap_or_else(|| format!("Chapter {}", index + 1));
+        metadata.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1\nSTART={}\nEND={}\ntitle={}\n",
+            start, end, title
+        ));
+    }
+    metadata
+}
+
+/// Escapes the characters ffmetadata gives a meaning to.
+fn escape_metadata(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// SubRip cues for the `mark` notes, each shown until the next note or for
+/// `NOTE_CUE_SECS`, whichever is sooner.
+pub(crate) fn notes_srt(notes: &[Note], total_secs: i64) -> String {
+    let mut notes: Vec<&Note> = notes.iter().collect();
+    notes.sort_by_key(|note| note.at);
+    let mut srt = String::new();
+    for (index, note) in notes.iter().enumerate() {
+        let mut end = note.at + NOTE_CUE_SECS;
+        if let Some(next) = notes.get(index + 1).filter(|next| next.at > note.at) {
+            end = end.min(next.at);
+        }
+        if total_secs > note.at {
+            end = end.min(total_secs);
+        }
+        srt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index + 1,
+            srt_time(note.at),
+            srt_time(end),
+            note.text
+        ));
+    }
+    srt
+}
+
+fn srt_time(secs: i64) -> String {
+    format!(
+        "{:02}:{:02}:{:02},000",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// First line of `ffmpeg -version`, e.g. `ffmpeg version 6.1.1`.
+pub(crate) fn ffmpeg_version() -> Option<String> {
+    let output = Command::new("ffmpeg").arg("-version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().next()?;
+    // Drop the copyright notice that follows the version.
+    Some(
+        line.split(" Copyright")
+            .next()
+            .unwrap_or(line)
+            .trim()
+            .to_string(),
+    )
+}
+
+/// Renders the spotlight overlay: a `2w`x`2h` dimming layer with a clear,
+/// soft-edged circle in the middle, so it can be slid under any pointer
+/// position and still cover the whole frame.
+pub(crate) fn render_spotlight_mask(
+    mask: &Path,
+    w: i32,
+    h: i32,
+    radius: u32,
+    dim: f64,
+) -> Result<()> {
+    const FEATHER_PX: u32 = 24;
+    let alpha = (dim.clamp(0.0, 1.0) * 255.0).round();
+    let source = format!(
+        "color=c=black:s={}x{},format=rgba,geq=r=0:g=0:b=0:a='{}*clip((hypot(X-W/2,Y-H/2)-{})/{},0,1)'",
+        w * 2,
+        h * 2,
+        alpha,
+        radius,
+        FEATHER_PX
+    );
+    run(
+        ffmpeg()
+            .args(["-f", "lavfi", "-i"])
+            .arg(source)
+            .args(["-frames:v", "1"])
+            .arg(mask),
+        "spotlight mask",
+    )
+}
+
+/// Hidden path next to `file` that keeps its extension, so ffmpeg still picks
+/// the right muxer.
+fn temp_sibling(file: &Path) -> PathBuf {
+    let name = file
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    file.with_file_name(format!(".tmp-{}", name))
+}
+
+fn ffmpeg() -> Command {
+    let mut command = Command::new("ffmpeg");
+    command.args(["-hide_banner", "-loglevel", "error", "-y"]);
+    command
+}
+
+fn run(command: &mut Command, what: &str) -> Result<()> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error("ffmpeg", error))
+        .with_context(|| format!("ffmpeg {} could not start", what))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("ffmpeg {} failed: {}", what, stderr.trim()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        chapter_metadata, frame_window, keep_intervals, notes_srt, parse_silences, parse_volume,
+        rendition_graph, retime_graph, total_silence, ConvertTarget, Rendition,
+    };
+    use crate::state::Note;
+
+    #[test]
+    fn silence_trim_keeps_speech_between_edges() {
+        let log = "[silencedetect @ 0x1] silence_start: 0\n\
+                   [silencedetect @ 0x1] silence_end: 1.5 | silence_duration: 1.5\n\
+                   [silencedetect @ 0x1] silence_start: 4\n\
+                   [silencedetect @ 0x1] silence_end: 5 | silence_duration: 1\n\
+                   [silencedetect @ 0x1] silence_start: 8.25\n";
+        let silences = parse_silences(log);
+        assert_eq!(
+            silences,
+            vec![(0.0, 1.5), (4.0, 5.0), (8.25, f64::INFINITY)]
+        );
+        assert_eq!(keep_intervals(&silences, 10.0, None), vec![(1.5, 8.25)]);
+        assert_eq!(total_silence(&silences, 10.0), 4.25);
+        assert_eq!(
+            keep_intervals(&silences, 10.0, Some(1.0)),
+            vec![(1.5, 4.0), (5.0, 8.25)]
+        );
+    }
+
+    #[test]
+    fn renditions_split_once_and_never_upscale() {
+        let mp4 = Rendition::parse("mp4:720p").unwrap();
+        assert_eq!(
+            mp4,
+            Rendition {
+                target: ConvertTarget::Mp4,
+                height: Some(720),
+            }
+        );
+        assert!(Rendition::parse("mkv:720").is_err());
+        assert!(Rendition::parse("mp4:big").is_err());
+        assert_eq!(
+            mp4.path(std::path::Path::new("/v/clip.mkv")),
+            std::path::Path::new("/v/clip.720p.mp4")
+        );
+
+        let gif = Rendition::parse("gif").unwrap();
+        let graph = rendition_graph(&[mp4, gif], &crate::GifConfig::default());
+        assert!(graph.starts_with("[0:v]split=2[s0][s1];"));
+        assert!(graph.contains("[s0]scale=-2:'min(720,ih)':flags=lanczos[r0]"));
+        assert!(graph.contains("[s1]fps="));
+        assert!(graph.ends_with("diff_mode=rectangle[r1]"));
+    }
+
+    #[test]
+    fn frame_trim_drops_whole_frames_from_each_end() {
+        assert_eq!(frame_window(10.0, 0, 5, 0), None);
+        assert_eq!(frame_window(10.0, 30, 0, 0), None);
+        assert_eq!(frame_window(10.0, 20, 10, 0), Some((0.5, 10.0)));
+        assert_eq!(frame_window(10.0, 20, 10, 20), Some((0.5, 9.0)));
+        assert_eq!(frame_window(1.0, 10, 5, 5), None);
+    }
+
+    #[test]
+    fn chapters_span_markers_through_end() {
+        let metadata = chapter_metadata(&[30, 10, 10, 0, 90], &[], 60);
+        assert_eq!(
+            metadata,
+            ";FFMETADATA1\n\
+             [CHAPTER]\nTIMEBASE=1/1\nSTART=0\nEND=10\ntitle=Chapter 1\n\
+             [CHAPTER]\nTIMEBASE=1/1\nSTART=10\nEND=30\ntitle=Chapter 2\n\
+             [CHAPTER]\nTIMEBASE=1/1\nSTART=30\nEND=60\ntitle=Chapter 3\n"
+        );
+    }
+
+    #[test]
+    fn notes_title_chapters_and_become_subtitles() {
+        let notes = [
+            Note {
+                at: 10,
+                text: "bug reproduced".to_string(),
+            },
+            Note {
+                at: 12,
+                text: "a=b; #1".to_string(),
+            },
+        ];
+        let metadata = chapter_metadata(&[10, 12], &notes, 30);
+        assert!(metadata.contains("START=0\nEND=10\ntitle=Chapter 1\n"));
+        assert!(metadata.contains("START=10\nEND=12\ntitle=bug reproduced\n"));
+        assert!(metadata.contains("START=12\nEND=30\ntitle=a\\=b\\; \\#1\n"));
+        assert_eq!(
+            notes_srt(&notes, 14),
+            "1\n00:00:10,000 --> 00:00:12,000\nbug reproduced\n\n\
+             2\n00:00:12,000 --> 00:00:14,000\na=b; #1\n\n"
+        );
+    }
+
+    #[test]
+    fn retime_speeds_up_only_the_middle_section() {
+        assert_eq!(
+            retime_graph(4.0, Some(10.0), Some(20.0), true),
+            "[0:v]split=3[vs0][vs1][vs2];[0:a]asplit=3[as0][as1][as2]\
+             ;[vs0]trim=end=10,setpts=(PTS-STARTPTS)/1[v0]\
+             ;[as0]atrim=end=10,asetpts=PTS-STARTPTS,atempo=1[a0]\
+             ;[vs1]trim=start=10:end=20,setpts=(PTS-STARTPTS)/4[v1]\
+             ;[as1]atrim=start=10:end=20,asetpts=PTS-STARTPTS,atempo=2,atempo=2[a1]\
+             ;[vs2]trim=start=20,setpts=(PTS-STARTPTS)/1[v2]\
+             ;[as2]atrim=start=20,asetpts=PTS-STARTPTS,atempo=1[a2]\
+             ;[v0][a0][v1][a1][v2][a2]concat=n=3:v=1:a=1[v][a]"
+        );
+        assert_eq!(
+            retime_graph(2.0, None, None, false),
+            "[0:v]split=1[vs0];[vs0]setpts=(PTS-STARTPTS)/2[v0];[v0]concat=n=1:v=1:a=0[v]"
+        );
+    }
+
+    #[test]
+    fn volume_stats_read_from_volumedetect_log() {
+        let log = "[Parsed_volumedetect_0 @ 0x1] n_samples: 288000\n\
+                   [Parsed_volumedetect_0 @ 0x1] mean_volume: -31.4 dB\n\
+                   [Parsed_volumedetect_0 @ 0x1] max_volume: -6.2 dB\n";
+        assert_eq!(parse_volume(log), Some((-31.4, -6.2)));
+
+        let silent = "mean_volume: -inf dB\nmax_volume: -inf dB\n";
+        assert_eq!(
+            parse_volume(silent),
+            Some((f64::NEG_INFINITY, f64::NEG_INFINITY))
+        );
+        assert_eq!(parse_volume("no stats"), None);
+    }
+}
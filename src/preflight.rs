@@ -0,0 +1,86 @@
+//! Checks run before a region is picked, so a missing permission surfaces
+//! as a notification saying what to fix instead of a dead encoder.
+
+use anyhow::Result;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::process;
+
+use crate::failure::Failure;
+use crate::state::{AudioDevices, STATE_DIR};
+use crate::{container, platform, postprocess, Config, Staging};
+
+/// Runs every check and returns the first that fails.
+pub(crate) fn check(config: &Config, output: Option<&Path>) -> Result<()> {
+    postprocess::parse_renditions(&config.output.renditions)?;
+    writable(
+        Path::new(STATE_DIR),
+        "State directory",
+        "it is probably left over from another user's session; delete it",
+    )?;
+    let output_dir = match output {
+        Some(path) if path == Path::new("-") || container::is_stream(path) => None,
+        Some(path) => Some(
+            path.parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or(Path::new("."))
+                .to_path_buf(),
+        ),
+        None => Some(config.output.resolved_directory().map_err(unavailable)?),
+    };
+    if let Some(dir) = output_dir {
+        writable(&dir, "Output directory", "pick another output.directory")?;
+    }
+    if config.output.staging == Staging::Tmpfs {
+        writable(
+            &config.output.staging_directory(),
+            "Staging directory",
+            "set output.staging to off",
+        )?;
+    }
+    platform::check_display().map_err(unavailable)?;
+    if AudioDevices::from_config(&config.audio).has_any() {
+        platform::check_audio_server().map_err(unavailable)?;
+    }
+    Ok(())
+}
+
+/// Creates `dir` if needed and proves a file can be written in it.
+fn writable(dir: &Path, what: &str, fix: &str) -> Result<()> {
+    let probe = dir.join(format!(".write-test-{}", process::id()));
+    let result = fs::create_dir_all(dir).and_then(|()| {
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&probe)
+            .map(drop)
+    });
+    let _ = fs::remove_file(&probe);
+    match result {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::PermissionDenied => {
+            Err(Failure::Unavailable(format!(
+                "{} {} is not writable by this user; {}",
+                what,
+                dir.display(),
+                fix
+            ))
+            .into())
+        }
+        Err(error) => Err(Failure::Unavailable(format!(
+            "{} {} cannot be written: {}",
+            what,
+            dir.display(),
+            error
+        ))
+        .into()),
+    }
+}
+
+fn unavailable(error: anyhow::Error) -> anyhow::Error {
+    if error.downcast_ref::<Failure>().is_some() {
+        return error;
+    }
+    Failure::Unavailable(format!("{:#}", error)).into()
+}
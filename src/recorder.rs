@@ -0,0 +1,886 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::container;
+use crate::events::{self, Event};
+use crate::failure::Failure;
+use crate::platform;
+use crate::platform::NotificationAction;
+use crate::postprocess::{self, ConvertTarget, Verification};
+use crate::session;
+use crate::sidecar;
+use crate::signals;
+use crate::state::{self, LastRegion, Note, RecordingState};
+use crate::tray;
+use crate::upload;
+use crate::{AudioChannels, Config, Framerate, Rect, Staging};
+
+const EXIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Compact recorder summary for the tray host and status bars.
+#[derive(Debug, Serialize)]
+pub(crate) struct StatusReport {
+    pub recording: bool,
+    pub recordings: Vec<RecordingStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct RecordingStatus {
+    pub id: u32,
+    pub paused: bool,
+    pub elapsed_secs: i64,
+    pub output: PathBuf,
+}
+
+pub(crate) fn status_report() -> StatusReport {
+    let recordings: Vec<RecordingStatus> = active_recordings()
+        .iter()
+        .map(|recording| RecordingStatus {
+            id: recording.id,
+            paused: recording.paused,
+            elapsed_secs: footage_secs(recording),
+            output: recording.destination().to_path_buf(),
+        })
+        .collect();
+    StatusReport {
+        recording: !recordings.is_empty(),
+        recordings,
+    }
+}
+
+pub(crate) fn start(
+    rect: Rect,
+    config: &Config,
+    output: PathBuf,
+    profile: Option<String>,
+    monitor: Option<String>,
+) -> Result<RecordingState> {
+    let id = state::next_id();
+    let (output, destination) = if container::is_stream(&output) {
+        (output, None)
+    } else if config.output.staging == Staging::Tmpfs
+        && !container::is_image_sequence(&config.video.format)
+    {
+        (staged_path(&output, id, config)?, Some(output))
+    } else {
+        (partial_path(&output), Some(output))
+    };
+    let audio = platform::route_audio(&config.audio)?;
+    let pid = match platform::start_capture(&rect, config, &audio, &output, id) {
+        Ok(pid) => pid,
+        Err(error) => {
+            platform::release_audio(&audio);
+            return Err(error);
+        }
+    };
+    let now = Local::now();
+    let recording = RecordingState {
+        id,
+        pid,
+        started_at: now.timestamp(),
+        rect,
+        output: output.clone(),
+        destination,
+        profile,
+        monitor,
+        display: Some(config.capture.resolved_display()),
+        audio,
+        capture_file: output,
+        segments: Vec::new(),
+        paused: false,
+        segment_started_at: now.timestamp(),
+        segment_started_at_ms: now.timestamp_millis(),
+        recorded_secs: 0,
+        framerate: config.video.fps(),
+        zoom: None,
+        markers: Vec::new(),
+        take: None,
+        notes: Vec::new(),
+        append_to: None,
+        workspace: None,
+        auto_paused: false,
+        tags: Vec::new(),
+        auto_format: false,
+    };
+    state::save(&recording)?;
+    Ok(recording)
+}
+
+/// Returns the live recordings described by the state directory. Entries
+/// whose capture process is gone are skipped but left alone: a stop or pause
+/// in progress looks the same for a moment, so only the supervisor's
+/// watchdog decides that a capture crashed.
+pub(crate) fn active_recordings() -> Vec<RecordingState> {
+    state::load_all()
+        .into_iter()
+        .filter(|recording| recording.paused || platform::process_alive(recording.pid))
+        .collect()
+}
+
+/// Cleans up after a capture that died without being stopped and reports
+/// it with the last lines of the encoder log.
+pub(crate) fn abandon(recording: &RecordingState) {
+    // Lines of the encoder log quoted in the notification.
+    const EXCERPT_LINES: usize = 3;
+    state::clear(recording.id);
+    platform::release_audio(&recording.audio);
+    // Whatever was captured before the crash still belongs at the final
+    // path rather than under a hidden or RAM-backed name.
+    if let Some(destination) = &recording.destination {
+        let _ = publish(&recording.capture_file, destination);
+    }
+    let log = fs::read_to_string(platform::capture_log(recording.id)).unwrap_or_default();
+    let lines: Vec<&str> = log.lines().filter(|line| !line.trim().is_empty()).collect();
+    let excerpt = lines[lines.len().saturating_sub(EXCERPT_LINES)..].join("\n");
+    let message = format!(
+        "Capture ended unexpectedly: {}",
+        recording.destination().display()
+    );
+    if excerpt.is_empty() {
+        platform::show_notification("Recording interrupted", &message, 2000);
+    } else {
+        platform::show_notification(
+            "Recording interrupted",
+            &format!("{}\n{}", message, excerpt),
+            6000,
+        );
+    }
+}
+
+/// Stops and finalizes `recording`, then launches a start that was pressed
+/// while it finalized.
+pub(crate) fn stop(recording: &RecordingState, config: &Config) -> Result<()> {
+    let result = {
+        let _finalizing = state::Finalizing::begin(recording.id);
+        finish(recording, config)
+    };
+    if !signals::requested() && !state::finalizing() {
+        launch_queued_start();
+    }
+    result
+}
+
+/// Runs the queued start as a detached `start` invocation, which picks its
+/// own region and keeps recording after this process exits.
+pub(crate) fn launch_queued_start() {
+    let Some(args) = state::take_queued_start() else {
+        return;
+    };
+    let Ok(exe) = env::current_exe() else {
+        return;
+    };
+    let launched = Command::new(exe)
+        .arg("start")
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Err(error) = launched {
+        platform::show_notification(
+            "Queued recording failed",
+            &format!("Could not start it: {}", error),
+            3000,
+        );
+    }
+}
+
+fn finish(recording: &RecordingState, config: &Config) -> Result<()> {
+    let mut recording = recording.clone();
+    let config = &recording_config(&recording, config);
+    if !recording.paused {
+        end_segment(&mut recording)?;
+    }
+    platform::release_audio(&recording.audio);
+    let clicks = state::load_clicks(recording.id);
+    if container::is_stream(&recording.output) {
+        state::clear(recording.id);
+        platform::show_status("Recording stopped", "Stream closed", 2000);
+        events::emit(Event::Stopped {
+            id: recording.id,
+            output: recording.output.clone(),
+        });
+        return Ok(());
+    }
+    // A frame directory has no container to join, fix or verify.
+    let frames = container::is_image_sequence(&config.video.format);
+    if !frames {
+        let finalized =
+            unstage(&mut recording).and_then(|()| finalize(&recording, &clicks, config));
+        if let Err(error) = finalized {
+            // The segments could not be moved or joined. Keeping them on
+            // record as a paused recording means stopping it again retries.
+            recording.paused = true;
+            let _ = state::save(&recording);
+            return Err(error);
+        }
+    }
+
+    let verification = if config.post.verify && !frames {
+        postprocess::verify_and_repair(
+            &recording.output,
+            recording.audio.has_any(),
+            recording.recorded_secs as f64,
+        )
+    } else {
+        Verification::Ok
+    };
+    if recording.auto_format && !matches!(verification, Verification::Corrupt(_)) {
+        if let Err(error) = settle_format(&mut recording, config) {
+            platform::show_notification(
+                "Kept as MKV",
+                &format!("Could not convert it: {:#}", error),
+                3000,
+            );
+        }
+    }
+    if let Some(mut destination) = recording.destination.take() {
+        if config.output.tags_in_filename && !recording.tags.is_empty() {
+            destination = tagged_path(&destination, &recording.tags);
+        }
+        // A damaged file keeps its in-progress name so watchers still skip it.
+        let target = match verification {
+            Verification::Corrupt(_) => partial_path(&destination),
+            _ => destination.clone(),
+        };
+        if recording.output != target {
+            publish(&recording.output, &target)?;
+        }
+        let srt = recording.output.with_extension("srt");
+        if srt.exists() {
+            publish(&srt, &destination.with_extension("srt"))?;
+        }
+        let notes = notes_path(&recording.output);
+        if notes.exists() {
+            publish(&notes, &notes_path(&destination))?;
+        }
+        recording.output = target;
+    }
+    // Only now is nothing left under a staged or in-progress name.
+    state::clear(recording.id);
+    if let Some(base) = recording.append_to.clone() {
+        if !matches!(verification, Verification::Corrupt(_)) {
+            match postprocess::append(&base, &recording.output) {
+                Ok(()) => recording.output = base,
+                Err(error) => platform::show_notification(
+                    "Could not append take",
+                    &format!("Kept it separately: {:#}", error),
+                    4000,
+                ),
+            }
+        }
+    }
+    let saved = recording.output.display();
+    match verification {
+        Verification::Ok => {
+            if platform::routine_notifications_shown() {
+                platform::show_notification_with_actions(
+                    "Recording stopped",
+                    &format!(
+                        "Saved to {}\n{}",
+                        saved,
+                        recording_summary(&recording, config)
+                    ),
+                    4000,
+                    &stop_actions(&recording),
+                );
+            }
+        }
+        Verification::Repaired(problem) => platform::show_notification(
+            "Recording repaired",
+            &format!("Remuxed after: {}. Saved to {}", problem, saved),
+            4000,
+        ),
+        Verification::Corrupt(problem) => {
+            return Err(Failure::Corrupt(format!("{}: {}", saved, problem)).into());
+        }
+    }
+    state::set_last_recording(&recording.output)?;
+    state::set_last_region(&LastRegion {
+        rect: recording.rect,
+        profile: recording.profile.clone(),
+    })?;
+    if let Some(take) = &recording.take {
+        session::finish_take(take, &recording.output, recording.recorded_secs)?;
+    }
+    if config.output.sidecar {
+        if let Err(error) = sidecar::write(&recording, config) {
+            platform::show_notification("Sidecar failed", &format!("{:#}", error), 2000);
+        }
+    }
+    // A GIF picked by the auto format has no sound left to split off.
+    let has_audio =
+        recording.audio.has_any() && recording.output.extension().is_none_or(|ext| ext != "gif");
+    if config.output.split_audio && has_audio {
+        let format = config.output.split_audio_format;
+        let audio = recording.output.with_extension(format.extension());
+        if let Err(error) = postprocess::extract_audio(&recording.output, &audio, format) {
+            platform::show_notification("Audio split failed", &format!("{:#}", error), 2000);
+        }
+    }
+    if !config.output.renditions.is_empty() {
+        let rendered =
+            postprocess::parse_renditions(&config.output.renditions).and_then(|renditions| {
+                postprocess::render_renditions(
+                    &recording.output,
+                    &renditions,
+                    has_audio,
+                    &config.video,
+                    &config.gif,
+                )
+            });
+        if let Err(error) = rendered {
+            platform::show_notification("Renditions failed", &format!("{:#}", error), 2000);
+        }
+    }
+    if config.output.clipboard_image {
+        if let Err(error) = copy_preview(&recording, frames) {
+            platform::show_notification("Clipboard preview failed", &format!("{:#}", error), 2000);
+        }
+    }
+    if config.upload.enabled {
+        if let Err(error) = upload::enqueue(&recording.output) {
+            platform::show_notification("Upload failed", &format!("{:#}", error), 4000);
+        }
+    }
+    events::emit(Event::Stopped {
+        id: recording.id,
+        output: recording.output.clone(),
+    });
+    Ok(())
+}
+
+/// One line describing how `recording` was made, such as
+/// `1280x720 · 30 fps · mp4 · libx264 crf 23`.
+fn recording_summary(recording: &RecordingState, config: &Config) -> String {
+    let format = recording
+        .output
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_else(|| "png frames".to_string());
+    format!(
+        "{}x{} · {} fps · {} · {} crf {}",
+        recording.rect.w,
+        recording.rect.h,
+        recording.framerate,
+        format,
+        config.video.codec,
+        config.video.crf
+    )
+}
+
+/// Buttons on the stop notification: Redo records the same area with the
+/// same profile again, Settings opens the tray's settings page.
+fn stop_actions(recording: &RecordingState) -> Vec<(&'static str, NotificationAction)> {
+    let mut actions = Vec::new();
+    if let Ok(exe) = env::current_exe() {
+        let mut command = vec![
+            exe.to_string_lossy().to_string(),
+            "start".to_string(),
+            "--last-region".to_string(),
+        ];
+        if let Some(profile) = &recording.profile {
+            command.extend(["--profile".to_string(), profile.clone()]);
+        }
+        actions.push(("Redo", NotificationAction::Run(command)));
+    }
+    actions.push((
+        "Settings",
+        NotificationAction::OpenUrl(tray::settings_url()),
+    ));
+    actions
+}
+
+/// Puts the recording's first frame on the clipboard as an image: the first
+/// PNG of a frame directory, or a frame grabbed from the start of a video.
+fn copy_preview(recording: &RecordingState, frames: bool) -> Result<()> {
+    if frames {
+        let first = fs::read_dir(&recording.output)
+            .context("failed to read frame directory")?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "png"))
+            .min()
+            .ok_or_else(|| anyhow!("no frames were captured"))?;
+        return platform::copy_image_to_clipboard(&first);
+    }
+    let preview = env::temp_dir().join(format!("screen-recorder-preview-{}.png", recording.id));
+    postprocess::extract_frame_at(&recording.output, 0.0, &preview)?;
+    let result = platform::copy_image_to_clipboard(&preview);
+    let _ = fs::remove_file(&preview);
+    result
+}
+
+/// Stops the capture and deletes everything it wrote.
+pub(crate) fn cancel(recording: &RecordingState) -> Result<()> {
+    if !recording.paused {
+        platform::stop_capture(recording.pid)?;
+        wait_for_exit(recording.pid);
+    }
+    platform::release_audio(&recording.audio);
+    state::clear(recording.id);
+    discard(&recording.capture_file);
+    for segment in &recording.segments {
+        discard(segment);
+    }
+    platform::show_status("Recording discarded", "Nothing was saved", 1200);
+    Ok(())
+}
+
+/// Deletes a capture, which is a directory of frames for `png-seq`.
+fn discard(capture: &Path) {
+    if capture.is_dir() {
+        let _ = fs::remove_dir_all(capture);
+    } else {
+        let _ = fs::remove_file(capture);
+    }
+}
+
+pub(crate) fn toggle_pause(recording: &RecordingState, config: &Config) -> Result<()> {
+    let mut recording = recording.clone();
+    let config = &recording_config(&recording, config);
+    if container::is_stream(&recording.output) {
+        return Err(anyhow!("a streamed recording cannot be paused"));
+    }
+    recording.auto_paused = false;
+    if recording.paused {
+        resume(&mut recording, config)?;
+        platform::show_status("Recording resumed", "Press your hotkey to pause", 1200);
+    } else {
+        end_segment(&mut recording)?;
+        recording.paused = true;
+        platform::show_status("Recording paused", "Press your hotkey to resume", 1200);
+    }
+    state::save(&recording)
+}
+
+/// Pauses while the recorded workspace is out of view and resumes when it
+/// comes back, leaving pauses made by hand alone. Returns whether anything
+/// changed.
+pub(crate) fn follow_workspace(
+    recording: &RecordingState,
+    visible: bool,
+    config: &Config,
+) -> Result<bool> {
+    // Nothing to do while capture already matches what is on screen.
+    if visible != recording.paused || (visible && !recording.auto_paused) {
+        return Ok(false);
+    }
+    let mut recording = recording.clone();
+    let config = &recording_config(&recording, config);
+    if visible {
+        resume(&mut recording, config)?;
+        recording.auto_paused = false;
+    } else {
+        end_segment(&mut recording)?;
+        recording.paused = true;
+        recording.auto_paused = true;
+    }
+    state::save(&recording)?;
+    Ok(true)
+}
+
+/// Cleans user-typed tags for use in file names: splits on commas and
+/// whitespace and keeps letters, digits, `-` and `_`.
+pub(crate) fn parse_tags(raw: &str) -> Vec<String> {
+    raw.split(|c: char| c == ',' || c.is_whitespace())
+        .map(|tag| {
+            tag.chars()
+                .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+                .collect::<String>()
+        })
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// Adds a chapter marker at the current footage offset, with `text` as its
+/// note when given.
+pub(crate) fn mark(recording: &RecordingState, text: Option<&str>) -> Result<()> {
+    let mut recording = recording.clone();
+    let offset = footage_secs(&recording);
+    recording.markers.push(offset);
+    if let Some(text) = text {
+        recording.notes.push(Note {
+            at: offset,
+            text: text.to_string(),
+        });
+    }
+    state::save(&recording)?;
+    let mut message = format!(
+        "Marker {} at {}",
+        recording.markers.len(),
+        format_offset(offset)
+    );
+    if let Some(text) = text {
+        message.push_str(": ");
+        message.push_str(text);
+    }
+    platform::show_status("Chapter marked", &message, 1000);
+    Ok(())
+}
+
+/// Seconds of footage captured so far, excluding paused time.
+pub(crate) fn footage_secs(recording: &RecordingState) -> i64 {
+    if recording.paused {
+        return recording.recorded_secs;
+    }
+    recording.recorded_secs + (Local::now().timestamp() - recording.segment_started_at)
+}
+
+/// Like [`footage_secs`] but with millisecond precision. State written
+/// before segment starts were kept in milliseconds falls back to seconds.
+pub(crate) fn footage_offset_precise(recording: &RecordingState) -> f64 {
+    if recording.paused || recording.segment_started_at_ms == 0 {
+        return footage_secs(recording) as f64;
+    }
+    let segment_ms = Local::now().timestamp_millis() - recording.segment_started_at_ms;
+    recording.recorded_secs as f64 + segment_ms.max(0) as f64 / 1000.0
+}
+
+/// The config with the recording's profile and the framerate it started at,
+/// so an `auto` rate resolved against one monitor carries over to later
+/// segments.
+fn recording_config(recording: &RecordingState, config: &Config) -> Config {
+    let mut config = recording
+        .profile
+        .as_deref()
+        .and_then(|name| config.with_profile(name).ok())
+        .unwrap_or_else(|| config.clone());
+    if recording.framerate > 0 {
+        config.video.framerate = Framerate::Fixed(recording.framerate);
+    }
+    if let Some(display) = &recording.display {
+        config.capture.display = display.clone();
+    }
+    config
+}
+
+fn resume(recording: &mut RecordingState, config: &Config) -> Result<()> {
+    if container::is_image_sequence(&config.video.format) {
+        // Frames keep numbering on in the same directory.
+        recording.pid = platform::start_capture(
+            &recording.rect,
+            config,
+            &recording.audio,
+            &recording.output,
+            recording.id,
+        )?;
+        mark_resumed(recording);
+        return Ok(());
+    }
+    if recording.segments == [recording.output.clone()] {
+        let first = segment_path(&recording.output, 1);
+        fs::rename(&recording.output, &first).context("failed to move first segment")?;
+        recording.segments = vec![first];
+    }
+    let segment = segment_path(&recording.output, recording.segments.len() + 1);
+    recording.pid = platform::start_capture(
+        &recording.rect,
+        config,
+        &recording.audio,
+        &segment,
+        recording.id,
+    )?;
+    recording.capture_file = segment;
+    mark_resumed(recording);
+    Ok(())
+}
+
+fn mark_resumed(recording: &mut RecordingState) {
+    recording.zoom = None;
+    let now = Local::now();
+    recording.segment_started_at = now.timestamp();
+    recording.segment_started_at_ms = now.timestamp_millis();
+    recording.paused = false;
+}
+
+fn end_segment(recording: &mut RecordingState) -> Result<()> {
+    platform::stop_capture(recording.pid)?;
+    wait_for_exit(recording.pid);
+    recording.recorded_secs += Local::now().timestamp() - recording.segment_started_at;
+    recording.segments.push(recording.capture_file.clone());
+    Ok(())
+}
+
+/// Joins the segments into the output and runs the configured passes over
+/// it. Only a failed join is an error; a pass that fails is reported and
+/// the file is kept as it was before that pass.
+fn finalize(recording: &RecordingState, clicks: &[f64], config: &Config) -> Result<()> {
+    if recording.segments.len() > 1 {
+        postprocess::concat(&recording.segments, &recording.output)?;
+        for segment in &recording.segments {
+            let _ = fs::remove_file(segment);
+        }
+    }
+    if config.output.crash_safe
+        && config.output.defragment
+        && container::is_mp4_family(&recording.output)
+    {
+        optional_pass("Defragment", postprocess::defragment(&recording.output));
+    }
+    // Mixed in before any trimming so the offsets still match the footage.
+    if config.post.click_sounds && !clicks.is_empty() {
+        optional_pass(
+            "Click sounds",
+            postprocess::mix_clicks(
+                &recording.output,
+                clicks,
+                recording.audio.has_any(),
+                config.post.click_volume,
+                &config.audio,
+            ),
+        );
+    }
+    if config.post.trim_first_frames > 0 || config.post.trim_last_frames > 0 {
+        optional_pass(
+            "Frame trim",
+            postprocess::trim_frames(
+                &recording.output,
+                config.post.trim_first_frames,
+                config.post.trim_last_frames,
+                recording.framerate,
+                &config.video,
+                recording.audio.has_any().then_some(&config.audio),
+            ),
+        );
+    }
+    if config.post.trim_silence && recording.audio.has_any() {
+        optional_pass(
+            "Silence trim",
+            postprocess::trim_silence(
+                &recording.output,
+                &config.post,
+                &config.video,
+                &config.audio,
+            ),
+        );
+    }
+    if config.post.loudnorm && recording.audio.has_any() {
+        optional_pass(
+            "Loudness normalization",
+            postprocess::normalize_loudness(
+                &recording.output,
+                config.post.loudnorm_target,
+                &config.audio,
+            ),
+        );
+    }
+    if config.subtitles.enabled && recording.audio.mic.is_some() {
+        // Transcription is a nicety; a missing model or binary must not cost
+        // the user the recording itself.
+        optional_pass("Subtitles", add_subtitles(recording, config));
+    }
+    if !recording.markers.is_empty() {
+        optional_pass(
+            "Chapters",
+            postprocess::write_chapters(
+                &recording.output,
+                &recording.markers,
+                &recording.notes,
+                recording.recorded_secs,
+            ),
+        );
+    }
+    if !recording.notes.is_empty() {
+        optional_pass(
+            "Marker notes",
+            fs::write(
+                notes_path(&recording.output),
+                postprocess::notes_srt(&recording.notes, recording.recorded_secs),
+            )
+            .context("failed to write marker notes"),
+        );
+    }
+    Ok(())
+}
+
+/// Reports a post-processing pass that failed; the recording goes on
+/// without it.
+fn optional_pass<T>(name: &str, result: Result<T>) {
+    if let Err(error) = result {
+        platform::show_notification(&format!("{} failed", name), &format!("{:#}", error), 2000);
+    }
+}
+
+/// Writes an `auto` recording into the container it suits, now that its
+/// length and streams are known, and points its destination at the new
+/// extension.
+fn settle_format(recording: &mut RecordingState, config: &Config) -> Result<()> {
+    let audio = recording
+        .audio
+        .has_any()
+        .then_some(config.audio.codec.encoder());
+    let format = container::auto_container(
+        recording.recorded_secs,
+        recording.rect.w.max(recording.rect.h),
+        &config.video.codec,
+        audio,
+        config.video.auto_gif_secs,
+    );
+    if format == "mkv" {
+        return Ok(());
+    }
+    let converted = recording.output.with_extension(format);
+    let result = if format == "gif" {
+        postprocess::convert(
+            &recording.output,
+            &converted,
+            ConvertTarget::Gif,
+            None,
+            &config.video,
+            &config.gif,
+        )
+    } else {
+        postprocess::remux(&recording.output, &converted)
+    };
+    if let Err(error) = result {
+        let _ = fs::remove_file(&converted);
+        return Err(error);
+    }
+    let _ = fs::remove_file(&recording.output);
+    recording.output = converted;
+    if let Some(destination) = &mut recording.destination {
+        *destination = destination.with_extension(format);
+    }
+    Ok(())
+}
+
+fn add_subtitles(recording: &RecordingState, config: &Config) -> Result<()> {
+    // With both sources merged the mic occupies the first channel; a fixed
+    // layout has already mixed them together.
+    let mic_channel = (recording.audio.system.is_some()
+        && config.audio.channels == AudioChannels::Auto)
+        .then_some("pan=mono|c0=c0");
+    let srt = postprocess::transcribe(&recording.output, &config.subtitles, mic_channel)?;
+    if config.subtitles.mux {
+        postprocess::mux_subtitles(&recording.output, &srt)?;
+        let _ = fs::remove_file(&srt);
+    }
+    Ok(())
+}
+
+/// Subtitle file with the `mark` notes of the recording at `output`.
+pub(crate) fn notes_path(output: &Path) -> PathBuf {
+    output.with_extension("notes.srt")
+}
+
+/// `destination` with the tags appended to its stem, e.g.
+/// `recording-….bug-1234.mkv` for the tag `bug-1234`, numbered when that
+/// name is already taken.
+fn tagged_path(destination: &Path, tags: &[String]) -> PathBuf {
+    let stem = destination
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = destination
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // Only the untagged name was reserved when the recording started.
+    crate::unique_path(
+        destination.parent().unwrap_or(Path::new(".")),
+        &format!("{}.{}", stem, tags.join(".")),
+        &extension,
+    )
+}
+
+/// Hidden in-progress name next to `destination`, so sync tools and file
+/// watchers leave the file alone until it is complete. The extension stays
+/// last so ffmpeg still infers the muxer from it.
+fn partial_path(destination: &Path) -> PathBuf {
+    let stem = destination
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match destination.extension() {
+        Some(ext) => format!(".recording-{}.part.{}", stem, ext.to_string_lossy()),
+        None => format!(".recording-{}.part", stem),
+    };
+    destination.with_file_name(name)
+}
+
+/// RAM-backed path a recording headed for `output` is captured to. The ID
+/// keeps concurrent recordings with the same file name apart.
+fn staged_path(output: &Path, id: u32, config: &Config) -> Result<PathBuf> {
+    let directory = config.output.staging_directory();
+    fs::create_dir_all(&directory).context("failed to create staging directory")?;
+    let name = output
+        .file_name()
+        .ok_or_else(|| anyhow!("output path has no file name: {}", output.display()))?;
+    Ok(directory.join(format!("{}-{}", id, name.to_string_lossy())))
+}
+
+/// Moves the segments of a recording captured to RAM next to its
+/// destination under in-progress names before any post-processing, so a
+/// pass that fails cannot leave the footage behind in the staging directory.
+fn unstage(recording: &mut RecordingState) -> Result<()> {
+    let Some(destination) = &recording.destination else {
+        return Ok(());
+    };
+    let partial = partial_path(destination);
+    if recording.output == partial {
+        return Ok(());
+    }
+    let single = recording.segments.len() == 1;
+    for index in 0..recording.segments.len() {
+        let target = if single {
+            partial.clone()
+        } else {
+            segment_path(&partial, index + 1)
+        };
+        if recording.segments[index] != target {
+            publish(&recording.segments[index], &target)?;
+            recording.segments[index] = target;
+        }
+    }
+    if let Some(last) = recording.segments.last() {
+        recording.capture_file = last.clone();
+    }
+    recording.output = partial;
+    Ok(())
+}
+
+/// Moves a staged file to its destination, copying when the two live on
+/// different filesystems.
+fn publish(staged: &Path, destination: &Path) -> Result<()> {
+    if fs::rename(staged, destination).is_ok() {
+        return Ok(());
+    }
+    fs::copy(staged, destination)
+        .with_context(|| format!("failed to move recording to {}", destination.display()))?;
+    fs::remove_file(staged).context("failed to remove staged recording")
+}
+
+fn wait_for_exit(pid: u32) {
+    let deadline = Instant::now() + EXIT_TIMEOUT;
+    while platform::process_alive(pid) && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+fn segment_path(output: &Path, index: usize) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = output
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_default();
+    output.with_file_name(format!("{}.part{}.{}", stem, index, extension))
+}
+
+fn format_offset(secs: i64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,222 @@
+//! Opt-in HTTP control listener run by the daemon, so a test orchestrator
+//! on another machine can start and stop recordings. Every request must
+//! carry `Authorization: Bearer <remote.token>`.
+//!
+//! - `POST /start`, `/stop`, `/pause`, `/mark`, `/session`: runs the action
+//!   with the request body as its arguments and answers with the same JSON
+//!   reply as the control socket.
+//! - `GET /status`: the `status` JSON.
+//! - `GET /recording`: the last finished recording itself.
+
+use anyhow::{anyhow, Context, Result};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use crate::daemon;
+use crate::state;
+use crate::RemoteConfig;
+
+/// Actions a remote client may run; everything else stays local.
+const ACTIONS: &[&str] = &["start", "stop", "pause", "mark", "session"];
+const MAX_BODY: usize = 64 * 1024;
+/// Request line and headers together; checked before the token, so an
+/// unauthenticated client cannot make the daemon buffer without limit.
+const MAX_HEAD: u64 = 16 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Start of an HTTP request: what the router needs from it.
+#[derive(Debug, PartialEq, Eq)]
+struct Head {
+    method: String,
+    path: String,
+    token: Option<String>,
+    content_length: usize,
+}
+
+/// Binds `remote.listen` and answers requests on a background thread for
+/// as long as the daemon runs.
+pub(crate) fn spawn(config: &RemoteConfig) -> Result<()> {
+    if config.token.is_empty() {
+        return Err(anyhow!(
+            "remote.token must be set before the remote listener is enabled"
+        ));
+    }
+    let listener = TcpListener::bind(&config.listen)
+        .with_context(|| format!("failed to listen on {}", config.listen))?;
+    let token = config.token.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let token = token.clone();
+            thread::spawn(move || {
+                if let Err(error) = serve(stream, &token) {
+                    eprintln!("remote request failed: {:#}", error);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn serve(stream: TcpStream, token: &str) -> Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut head = String::new();
+    let mut limited = (&mut reader).take(MAX_HEAD);
+    let complete = loop {
+        let mut line = String::new();
+        if limited.read_line(&mut line)? == 0 {
+            break false;
+        }
+        if line.trim_end().is_empty() {
+            break true;
+        }
+        head.push_str(&line);
+    };
+    if !complete && limited.limit() == 0 {
+        return respond(
+            &stream,
+            "431 Request Header Fields Too Large",
+            "text/plain",
+            b"headers too large\n",
+        );
+    }
+    let Some(head) = parse_head(&head) else {
+        return respond(&stream, "400 Bad Request", "text/plain", b"bad request\n");
+    };
+    if !authorized(&head, token) {
+        return respond(&stream, "401 Unauthorized", "text/plain", b"unauthorized\n");
+    }
+    if head.content_length > MAX_BODY {
+        return respond(
+            &stream,
+            "413 Payload Too Large",
+            "text/plain",
+            b"too large\n",
+        );
+    }
+    let mut body = vec![0; head.content_length];
+    reader.read_exact(&mut body)?;
+    let args = String::from_utf8_lossy(&body);
+
+    match (head.method.as_str(), head.path.trim_start_matches('/')) {
+        ("GET", "status") => {
+            let reply = daemon::run_action(&["status"])?;
+            respond(
+                &stream,
+                "200 OK",
+                "application/json",
+                reply.stdout.as_bytes(),
+            )
+        }
+        ("GET", "recording") => send_last_recording(&stream),
+        ("POST", action) if ACTIONS.contains(&action) => {
+            let mut command = vec![action];
+            command.extend(args.split_whitespace());
+            let reply = daemon::run_action(&command)?;
+            let json = serde_json::to_string(&reply)?;
+            respond(&stream, "200 OK", "application/json", json.as_bytes())
+        }
+        _ => respond(&stream, "404 Not Found", "text/plain", b"not found\n"),
+    }
+}
+
+fn send_last_recording(mut stream: &TcpStream) -> Result<()> {
+    let Some(file) = state::last_recording().and_then(|path| File::open(path).ok()) else {
+        return respond(stream, "404 Not Found", "text/plain", b"no recording yet\n");
+    };
+    let length = file.metadata()?.len();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        length
+    )?;
+    io::copy(&mut BufReader::new(file), &mut stream)?;
+    Ok(())
+}
+
+fn respond(mut stream: &TcpStream, status: &str, content_type: &str, body: &[u8]) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    Ok(())
+}
+
+/// Reads the request line and the headers the router cares about.
+fn parse_head(head: &str) -> Option<Head> {
+    let mut lines = head.lines();
+    let mut request = lines.next()?.split_whitespace();
+    let method = request.next()?.to_string();
+    let path = request.next()?.split('?').next()?.to_string();
+    let mut token = None;
+    let mut content_length = 0;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("authorization") {
+            token = value.strip_prefix("Bearer ").map(str::to_string);
+        } else if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().ok()?;
+        }
+    }
+    Some(Head {
+        method,
+        path,
+        token,
+        content_length,
+    })
+}
+
+/// Compares the bearer token without stopping at the first differing byte,
+/// so response timing says nothing about how much of a guess was right.
+fn authorized(head: &Head, token: &str) -> bool {
+    let Some(given) = &head.token else {
+        return false;
+    };
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{authorized, parse_head, Head};
+
+    #[test]
+    fn requests_need_the_exact_bearer_token() {
+        let head = parse_head(
+            "POST /start?x=1 HTTP/1.1\r\nHost: lab\r\nauthorization: Bearer s3cret\r\n\
+             Content-Length: 12\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            head,
+            Head {
+                method: "POST".to_string(),
+                path: "/start".to_string(),
+                token: Some("s3cret".to_string()),
+                content_length: 12,
+            }
+        );
+        assert!(authorized(&head, "s3cret"));
+        assert!(!authorized(&head, "s3cre"));
+        assert!(!authorized(&head, "s3creT"));
+
+        let anonymous = parse_head("GET /status HTTP/1.1\r\n").unwrap();
+        assert!(!authorized(&anonymous, "s3cret"));
+        assert!(parse_head("GET\r\n").is_none());
+    }
+}
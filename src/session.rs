@@ -0,0 +1,226 @@
+//! Multi-take sessions: while one is open, each new recording is a numbered
+//! take named after the session, recorded with the settings in effect when
+//! the session started, and `session end` lists what was kept.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::state::STATE_DIR;
+use crate::Config;
+
+/// An open session and the takes finished so far.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Session {
+    pub name: String,
+    /// Number the next recording gets.
+    pub next_take: u32,
+    #[serde(default)]
+    pub takes: Vec<Take>,
+    /// Effective settings when the session started. Every take records
+    /// with these, so editing the config mid-session cannot make takes
+    /// differ, and the session file shows how they were made.
+    #[serde(default)]
+    pub config: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Take {
+    pub number: u32,
+    pub file: PathBuf,
+    pub secs: i64,
+}
+
+/// Which take of which session a recording is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TakeRef {
+    pub session: String,
+    pub number: u32,
+}
+
+/// A lock older than this is left over from a crashed process.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+const LOCK_RETRY: Duration = Duration::from_millis(20);
+
+fn session_path() -> PathBuf {
+    Path::new(STATE_DIR).join("session.json")
+}
+
+fn lock_path() -> PathBuf {
+    Path::new(STATE_DIR).join("session.lock")
+}
+
+/// Held while the session file is read, changed and written back, so
+/// concurrent recordings cannot claim the same take or drop each other's.
+struct SessionLock;
+
+impl SessionLock {
+    fn acquire() -> Result<Self> {
+        fs::create_dir_all(STATE_DIR).context("failed to create state directory")?;
+        let started = Instant::now();
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(lock_path())
+            {
+                Ok(_) => return Ok(Self),
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                    if started.elapsed() > LOCK_TIMEOUT {
+                        let _ = fs::remove_file(lock_path());
+                    }
+                    thread::sleep(LOCK_RETRY);
+                }
+                Err(error) => return Err(error).context("failed to lock session"),
+            }
+        }
+    }
+}
+
+impl Drop for SessionLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(lock_path());
+    }
+}
+
+pub(crate) fn current() -> Option<Session> {
+    let content = fs::read_to_string(session_path()).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Writes through a temp file and a rename, like `state::save`.
+fn save(session: &Session) -> Result<()> {
+    fs::create_dir_all(STATE_DIR).context("failed to create state directory")?;
+    let json = serde_json::to_string_pretty(session).context("failed to encode session")?;
+    let tmp = session_path().with_extension("json.tmp");
+    fs::write(&tmp, json).context("failed to save session")?;
+    fs::rename(&tmp, session_path()).context("failed to save session")
+}
+
+/// Opens a session called `name` locked to `config`; takes start at 1.
+pub(crate) fn begin(name: &str, config: &Config) -> Result<Session> {
+    let _lock = SessionLock::acquire()?;
+    if let Some(open) = current() {
+        return Err(anyhow!("session {} is still open; end it first", open.name));
+    }
+    let session = Session {
+        name: name.to_string(),
+        next_take: 1,
+        takes: Vec::new(),
+        config: Some(serde_json::to_value(config).context("failed to snapshot settings")?),
+    };
+    save(&session)?;
+    Ok(session)
+}
+
+/// Closes the open session and returns it with its takes.
+pub(crate) fn end() -> Result<Session> {
+    let _lock = SessionLock::acquire()?;
+    let session = current().ok_or_else(|| anyhow!("no session is open"))?;
+    fs::remove_file(session_path()).context("failed to close session")?;
+    Ok(session)
+}
+
+/// Settings the open session was locked to, if a session is open.
+pub(crate) fn locked_config() -> Option<Config> {
+    let snapshot = current()?.config?;
+    serde_json::from_value(snapshot).ok()
+}
+
+/// Hands out the next take number of the open session, if one is open.
+pub(crate) fn claim_take() -> Result<Option<TakeRef>> {
+    let _lock = SessionLock::acquire()?;
+    let Some(mut session) = current() else {
+        return Ok(None);
+    };
+    let number = session.next_take.max(1);
+    session.next_take = number + 1;
+    save(&session)?;
+    Ok(Some(TakeRef {
+        session: session.name,
+        number,
+    }))
+}
+
+/// Adds a finished take to its session, unless that session has since been
+/// closed or replaced.
+pub(crate) fn finish_take(take: &TakeRef, file: &Path, secs: i64) -> Result<()> {
+    let _lock = SessionLock::acquire()?;
+    let Some(mut session) = current().filter(|session| session.name == take.session) else {
+        return Ok(());
+    };
+    session.takes.push(Take {
+        number: take.number,
+        file: file.to_path_buf(),
+        secs,
+    });
+    save(&session)
+}
+
+/// `take-03` style label used in file names.
+pub(crate) fn take_label(number: u32) -> String {
+    format!("take-{:02}", number)
+}
+
+/// One line per take and a total, for `session end`.
+pub(crate) fn summary(session: &Session) -> String {
+    let mut lines = vec![format!(
+        "Session {}: {} take{}",
+        session.name,
+        session.takes.len(),
+        if session.takes.len() == 1 { "" } else { "s" }
+    )];
+    for take in &session.takes {
+        lines.push(format!(
+            "  {}  {}  {}",
+            take_label(take.number),
+            clock(take.secs),
+            take.file.display()
+        ));
+    }
+    let total: i64 = session.takes.iter().map(|take| take.secs).sum();
+    lines.push(format!("Total {}", clock(total)));
+    lines.join("\n")
+}
+
+fn clock(secs: i64) -> String {
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{summary, Session, Take};
+    use std::path::PathBuf;
+
+    #[test]
+    fn summary_lists_every_take_and_the_total() {
+        let session = Session {
+            name: "chapter-3".to_string(),
+            next_take: 4,
+            takes: vec![
+                Take {
+                    number: 1,
+                    file: PathBuf::from("/v/chapter-3-take-01.mkv"),
+                    secs: 95,
+                },
+                Take {
+                    number: 3,
+                    file: PathBuf::from("/v/chapter-3-take-03.mkv"),
+                    secs: 130,
+                },
+            ],
+            config: None,
+        };
+        assert_eq!(
+            summary(&session),
+            "Session chapter-3: 2 takes\n\
+             \x20 take-01  1:35  /v/chapter-3-take-01.mkv\n\
+             \x20 take-03  2:10  /v/chapter-3-take-03.mkv\n\
+             Total 3:45"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::platform;
+use crate::{Config, Framerate};
+
+const PLUGIN_DIR: &str = "qol-tray/plugins/plugin-screen-recorder";
+
+/// The handful of settings the native window edits.
+#[derive(Debug, Clone)]
+pub(crate) struct SettingsForm {
+    pub audio_enabled: bool,
+    pub mic: bool,
+    pub system: bool,
+    pub format: String,
+    pub framerate: String,
+    pub directory: PathBuf,
+}
+
+impl SettingsForm {
+    fn from_config(config: &Config) -> Result<Self> {
+        let wants = |name: &str| config.audio.inputs.iter().any(|input| input == name);
+        Ok(Self {
+            audio_enabled: config.audio.enabled,
+            mic: wants("mic"),
+            system: wants("system"),
+            format: config.video.format.clone(),
+            framerate: match &config.video.framerate {
+                Framerate::Fixed(fps) => fps.to_string(),
+                Framerate::Named(name) => name.clone(),
+            },
+            directory: config.output.resolved_directory()?,
+        })
+    }
+}
+
+/// Shows the native settings window and writes the result to the plugin
+/// config file, for setups without the qol-tray web settings server.
+pub(crate) fn run(config: &Config) -> Result<()> {
+    let form = SettingsForm::from_config(config)?;
+    let Some(edited) = platform::edit_settings(&form)? else {
+        return Ok(());
+    };
+    save(&edited)
+}
+
+fn config_path() -> Result<PathBuf> {
+    let base = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("HOME").context("HOME is not set")?).join(".config"),
+    };
+    Ok(base.join(PLUGIN_DIR).join("config.json"))
+}
+
+/// Merges the edited keys into the existing config file, leaving every
+/// setting the window does not show untouched.
+fn save(form: &SettingsForm) -> Result<()> {
+    let path = config_path()?;
+    let mut root = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+        .filter(Value::is_object)
+        .unwrap_or_else(|| Value::Object(Map::new()));
+
+    let inputs = [("mic", form.mic), ("system", form.system)]
+        .into_iter()
+        .filter(|(_, wanted)| *wanted)
+        .map(|(name, _)| Value::from(name))
+        .collect();
+    let framerate = match form.framerate.trim().parse::<u64>() {
+        Ok(fps) => Value::from(fps),
+        Err(_) => Value::from(form.framerate.trim()),
+    };
+    set(
+        &mut root,
+        "audio",
+        "enabled",
+        Value::from(form.audio_enabled),
+    );
+    set(&mut root, "audio", "inputs", Value::Array(inputs));
+    set(
+        &mut root,
+        "video",
+        "format",
+        Value::from(form.format.as_str()),
+    );
+    set(&mut root, "video", "framerate", framerate);
+    set(
+        &mut root,
+        "output",
+        "directory",
+        Value::from(form.directory.to_string_lossy().to_string()),
+    );
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("failed to create config directory")?;
+    }
+    let json = serde_json::to_string_pretty(&root).context("failed to encode config")?;
+    fs::write(&path, json).with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn set(root: &mut Value, section: &str, key: &str, value: Value) {
+    let Some(root) = root.as_object_mut() else {
+        return;
+    };
+    let section = root
+        .entry(section.to_string())
+        .or_insert_with(|| Value::Object(Map::new()));
+    if !section.is_object() {
+        *section = Value::Object(Map::new());
+    }
+    if let Some(section) = section.as_object_mut() {
+        section.insert(key.to_string(), value);
+    }
+}
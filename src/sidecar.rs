@@ -0,0 +1,77 @@
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::postprocess;
+use crate::state::RecordingState;
+use crate::{Config, Rect};
+
+/// Provenance of one recording, written as `<recording>.json` so it can be
+/// attached to a bug report alongside the video.
+#[derive(Debug, Serialize)]
+struct Sidecar<'a> {
+    recorder_version: &'static str,
+    recording: &'a Path,
+    /// RFC 3339 time the capture started.
+    started_at: String,
+    duration_secs: i64,
+    region: Rect,
+    monitor: Option<&'a str>,
+    profile: Option<&'a str>,
+    session: Option<&'a str>,
+    take: Option<u32>,
+    tags: &'a [String],
+    mic: Option<&'a str>,
+    system_audio: Option<&'a str>,
+    /// Seconds of the audio track below `post.silence_threshold_db`.
+    audio_silent_secs: Option<f64>,
+    framerate: u32,
+    codec: &'a str,
+    ffmpeg_version: Option<String>,
+}
+
+pub(crate) fn path_for(recording: &Path) -> PathBuf {
+    recording.with_extension("json")
+}
+
+pub(crate) fn write(recording: &RecordingState, config: &Config) -> Result<()> {
+    let started_at = Local
+        .timestamp_opt(recording.started_at, 0)
+        .single()
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_default();
+    let sidecar = Sidecar {
+        recorder_version: env!("CARGO_PKG_VERSION"),
+        recording: &recording.output,
+        started_at,
+        duration_secs: recording.recorded_secs,
+        region: recording.rect,
+        monitor: recording.monitor.as_deref(),
+        profile: recording.profile.as_deref(),
+        session: recording.take.as_ref().map(|take| take.session.as_str()),
+        take: recording.take.as_ref().map(|take| take.number),
+        tags: &recording.tags,
+        mic: recording.audio.mic.as_deref(),
+        system_audio: recording.audio.system.as_deref(),
+        audio_silent_secs: recording
+            .audio
+            .has_any()
+            .then(|| {
+                postprocess::silent_secs(
+                    &recording.output,
+                    config.post.silence_threshold_db,
+                    config.post.min_silence_secs,
+                    recording.recorded_secs as f64,
+                )
+                .ok()
+            })
+            .flatten(),
+        framerate: recording.framerate,
+        codec: &config.video.codec,
+        ffmpeg_version: postprocess::ffmpeg_version(),
+    };
+    let json = serde_json::to_string_pretty(&sidecar).context("failed to encode sidecar")?;
+    fs::write(path_for(&recording.output), json).context("failed to write sidecar")
+}
@@ -0,0 +1,39 @@
+//! Shutdown requests for the long-running processes. SIGTERM, SIGINT and
+//! SIGHUP (the session ending) only raise a flag, which the daemon and the
+//! recording supervisor poll so they can finalize recordings before exiting.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+const SIGHUP: i32 = 1;
+#[cfg(unix)]
+const SIGINT: i32 = 2;
+#[cfg(unix)]
+const SIGTERM: i32 = 15;
+
+#[cfg(unix)]
+extern "C" {
+    fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+}
+
+#[cfg(unix)]
+extern "C" fn request_shutdown(_signum: i32) {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+/// Routes the shutdown signals to [`requested`].
+pub(crate) fn install() {
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+    #[cfg(unix)]
+    unsafe {
+        signal(SIGTERM, request_shutdown);
+        signal(SIGINT, request_shutdown);
+        signal(SIGHUP, request_shutdown);
+    }
+}
+
+pub(crate) fn requested() -> bool {
+    SHUTDOWN.load(Ordering::SeqCst)
+}
@@ -0,0 +1,38 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::platform;
+use crate::postprocess;
+use crate::state::RecordingState;
+use crate::{Rect, SpotlightConfig};
+
+/// Instance name of the overlay filter the pointer commands address.
+const OVERLAY_FILTER: &str = "spotlight";
+
+fn mask_path(id: u32) -> PathBuf {
+    PathBuf::from(format!("/tmp/record-region-{}-spotlight.png", id))
+}
+
+/// Filter chain that lays the dimming mask over the capture, initially
+/// centered on the capture area.
+#[allow(dead_code, reason = "only the linux platform impl captures frames")]
+pub(crate) fn overlay_filter(rect: &Rect, spotlight: &SpotlightConfig, id: u32) -> Result<String> {
+    let mask = mask_path(id);
+    postprocess::render_spotlight_mask(&mask, rect.w, rect.h, spotlight.radius, spotlight.dim)?;
+    Ok(format!(
+        "movie={}[spot];[in][spot]overlay@{}=x={}:y={}",
+        mask.display(),
+        OVERLAY_FILTER,
+        -rect.w / 2,
+        -rect.h / 2
+    ))
+}
+
+/// Moves the clear circle under `pointer`, given in root window coordinates.
+pub(crate) fn follow(recording: &RecordingState, pointer: (i32, i32)) -> Result<()> {
+    let port = platform::filter_control_port(recording.id);
+    let x = pointer.0 - recording.rect.x - recording.rect.w;
+    let y = pointer.1 - recording.rect.y - recording.rect.h;
+    platform::send_filter_command(port, &format!("overlay@{} x {}", OVERLAY_FILTER, x))?;
+    platform::send_filter_command(port, &format!("overlay@{} y {}", OVERLAY_FILTER, y))
+}
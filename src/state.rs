@@ -0,0 +1,327 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::platform;
+use crate::session::TakeRef;
+use crate::{AudioConfig, Rect};
+
+pub(crate) const STATE_DIR: &str = "/tmp/record-region";
+const PRESS_FILE: &str = "/tmp/record-region/press";
+const LAST_FILE: &str = "/tmp/record-region/last";
+const LAST_REGION_FILE: &str = "/tmp/record-region/last-region.json";
+const QUEUED_START_FILE: &str = "/tmp/record-region/queued-start";
+const FINALIZING_PREFIX: &str = "finalizing-";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct RecordingState {
+    pub id: u32,
+    pub pid: u32,
+    /// Unix timestamp (seconds) at which the capture process was spawned.
+    pub started_at: i64,
+    pub rect: Rect,
+    /// In-progress file the finished recording is assembled in.
+    pub output: PathBuf,
+    /// Where the recording is moved once finished and verified; `None` when
+    /// `output` is already the final location, as for streams.
+    #[serde(default)]
+    pub destination: Option<PathBuf>,
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Output name of the monitor the recording area sits on.
+    #[serde(default)]
+    pub monitor: Option<String>,
+    /// X display and screen being grabbed, so resumed segments grab the
+    /// same screen whatever `DISPLAY` the resuming invocation has.
+    #[serde(default)]
+    pub display: Option<String>,
+    #[serde(default)]
+    pub audio: AudioDevices,
+    /// File the running capture process is writing to.
+    pub capture_file: PathBuf,
+    /// Finished segments, in recording order, waiting to be joined on stop.
+    #[serde(default)]
+    pub segments: Vec<PathBuf>,
+    #[serde(default)]
+    pub paused: bool,
+    /// Unix timestamp (seconds) at which the current segment began.
+    pub segment_started_at: i64,
+    /// The same instant in milliseconds, for events that need finer timing.
+    #[serde(default)]
+    pub segment_started_at_ms: i64,
+    /// Seconds of footage captured by the finished segments.
+    #[serde(default)]
+    pub recorded_secs: i64,
+    /// Frames per second the capture runs at, fixed when recording starts.
+    #[serde(default)]
+    pub framerate: u32,
+    /// Part of the capture area currently zoomed into, relative to `rect`.
+    #[serde(default)]
+    pub zoom: Option<Rect>,
+    /// Chapter marker offsets, in seconds from the start of the footage.
+    #[serde(default)]
+    pub markers: Vec<i64>,
+    /// Take number within the session open when the recording started.
+    #[serde(default)]
+    pub take: Option<TakeRef>,
+    /// Text given with `mark "…"`, kept with the offset it was marked at.
+    #[serde(default)]
+    pub notes: Vec<Note>,
+    /// Earlier recording this take is joined onto when it stops.
+    #[serde(default)]
+    pub append_to: Option<PathBuf>,
+    /// Workspace being recorded; capture pauses while it is out of view.
+    #[serde(default)]
+    pub workspace: Option<String>,
+    /// Set when the pause came from switching away from `workspace`, so
+    /// only those pauses are lifted on switching back.
+    #[serde(default)]
+    pub auto_paused: bool,
+    /// Labels such as ticket IDs given when the recording was stopped.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Set for `video.format = "auto"` recordings written to a default
+    /// name; the final container is picked when the recording stops.
+    #[serde(default)]
+    pub auto_format: bool,
+}
+
+/// A marker with a short text, such as "bug reproduced".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Note {
+    /// Seconds from the start of the footage.
+    pub at: i64,
+    pub text: String,
+}
+
+impl RecordingState {
+    /// Final location of the recording, staged or not.
+    pub fn destination(&self) -> &Path {
+        self.destination.as_deref().unwrap_or(&self.output)
+    }
+}
+
+/// Area and profile of the most recent recording, for `--last-region` and
+/// the Redo button.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct LastRegion {
+    pub rect: Rect,
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+/// Pulse sources a recording captures from, plus any routing the recorder
+/// set up to provide them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct AudioDevices {
+    pub mic: Option<String>,
+    pub system: Option<String>,
+    /// Pulse modules loaded for this recording, unloaded when it ends.
+    #[serde(default)]
+    pub pulse_modules: Vec<u32>,
+    /// Default sink to restore once the recording ends.
+    #[serde(default)]
+    pub previous_default_sink: Option<String>,
+    /// Bluetooth card switched for the recording, with the profile to put
+    /// it back to.
+    #[serde(default)]
+    pub previous_card_profile: Option<(String, String)>,
+}
+
+impl AudioDevices {
+    pub fn has_any(&self) -> bool {
+        self.mic.is_some() || self.system.is_some()
+    }
+
+    pub fn from_config(audio: &AudioConfig) -> Self {
+        if !audio.enabled {
+            return Self::default();
+        }
+        let wants = |name: &str| audio.inputs.iter().any(|input| input == name);
+        Self {
+            mic: wants("mic").then(|| audio.mic_device.clone()),
+            system: wants("system").then(|| format!("{}.monitor", audio.system_device)),
+            ..Self::default()
+        }
+    }
+}
+
+fn state_path(id: u32) -> PathBuf {
+    PathBuf::from(STATE_DIR).join(format!("{}.json", id))
+}
+
+pub(crate) fn load(id: u32) -> Option<RecordingState> {
+    let content = fs::read_to_string(state_path(id)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Returns every recorded state entry ordered by ID, skipping unreadable files.
+pub(crate) fn load_all() -> Vec<RecordingState> {
+    let Ok(entries) = fs::read_dir(STATE_DIR) else {
+        return Vec::new();
+    };
+    let mut states: Vec<RecordingState> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let id = name.to_str()?.strip_suffix(".json")?.parse::<u32>().ok()?;
+            load(id)
+        })
+        .collect();
+    states.sort_by_key(|state| state.id);
+    states
+}
+
+/// Smallest positive ID without a state entry.
+pub(crate) fn next_id() -> u32 {
+    let taken: Vec<u32> = load_all().iter().map(|state| state.id).collect();
+    (1..).find(|id| !taken.contains(id)).unwrap_or(1)
+}
+
+/// Writes the state through a sibling temp file and a rename so readers never
+/// observe a partially written document.
+pub(crate) fn save(state: &RecordingState) -> Result<()> {
+    fs::create_dir_all(STATE_DIR).context("failed to create state directory")?;
+    let json = serde_json::to_string_pretty(state).context("failed to encode state file")?;
+    let path = state_path(state.id);
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json).context("failed to write state file")?;
+    fs::rename(&tmp, &path).context("failed to replace state file")?;
+    Ok(())
+}
+
+pub(crate) fn clear(id: u32) {
+    let _ = fs::remove_file(state_path(id));
+    let _ = fs::remove_file(clicks_path(id));
+}
+
+fn clicks_path(id: u32) -> PathBuf {
+    PathBuf::from(STATE_DIR).join(format!("{}.clicks", id))
+}
+
+/// Records a mouse click at `offset_secs` into the footage. Clicks live in
+/// their own append-only file so the supervisor never races other writers
+/// of the state document.
+pub(crate) fn append_click(id: u32, offset_secs: f64) -> Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(clicks_path(id))
+        .context("failed to open click log")?;
+    writeln!(file, "{:.3}", offset_secs).context("failed to write click log")
+}
+
+pub(crate) fn load_clicks(id: u32) -> Vec<f64> {
+    fs::read_to_string(clicks_path(id))
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| line.trim().parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Unix timestamp (milliseconds) of a hotkey press still waiting to see
+/// whether a second press follows.
+pub(crate) fn pending_press() -> Option<i64> {
+    let content = fs::read_to_string(PRESS_FILE).ok()?;
+    content.trim().parse().ok()
+}
+
+pub(crate) fn set_pending_press(at_ms: i64) -> Result<()> {
+    fs::create_dir_all(STATE_DIR).context("failed to create state directory")?;
+    fs::write(PRESS_FILE, at_ms.to_string()).context("failed to write press marker")
+}
+
+pub(crate) fn clear_pending_press() {
+    let _ = fs::remove_file(PRESS_FILE);
+}
+
+/// The most recently finished recording, for `delete-last`.
+pub(crate) fn last_recording() -> Option<PathBuf> {
+    let content = fs::read_to_string(LAST_FILE).ok()?;
+    Some(PathBuf::from(content.trim_end_matches('\n')))
+}
+
+pub(crate) fn set_last_recording(path: &Path) -> Result<()> {
+    fs::create_dir_all(STATE_DIR).context("failed to create state directory")?;
+    fs::write(LAST_FILE, path.to_string_lossy().as_bytes())
+        .context("failed to remember last recording")
+}
+
+pub(crate) fn last_region() -> Option<LastRegion> {
+    let content = fs::read_to_string(LAST_REGION_FILE).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub(crate) fn set_last_region(region: &LastRegion) -> Result<()> {
+    fs::create_dir_all(STATE_DIR).context("failed to create state directory")?;
+    let json = serde_json::to_string(region).context("failed to encode last region")?;
+    fs::write(LAST_REGION_FILE, json).context("failed to remember last region")
+}
+
+pub(crate) fn clear_last_recording() {
+    let _ = fs::remove_file(LAST_FILE);
+}
+
+/// Marks a recording as being finalized by this process for as long as the
+/// guard lives, so a start pressed meanwhile waits for it.
+pub(crate) struct Finalizing(PathBuf);
+
+impl Finalizing {
+    pub fn begin(id: u32) -> Self {
+        let path = Path::new(STATE_DIR).join(format!("{}{}", FINALIZING_PREFIX, id));
+        let _ = fs::create_dir_all(STATE_DIR);
+        let _ = fs::write(&path, process::id().to_string());
+        Self(path)
+    }
+}
+
+impl Drop for Finalizing {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Whether a live process is still finalizing a stopped recording.
+pub(crate) fn finalizing() -> bool {
+    let Ok(entries) = fs::read_dir(STATE_DIR) else {
+        return false;
+    };
+    entries.filter_map(|entry| entry.ok()).any(|entry| {
+        entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(FINALIZING_PREFIX)
+            && marker_held(&entry.path())
+    })
+}
+
+/// Whether a live process is finalizing recording `id` right now.
+pub(crate) fn finalizing_recording(id: u32) -> bool {
+    marker_held(&Path::new(STATE_DIR).join(format!("{}{}", FINALIZING_PREFIX, id)))
+}
+
+fn marker_held(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|pid| pid.trim().parse::<u32>().ok())
+        .is_some_and(platform::process_alive)
+}
+
+/// Remembers the arguments of a start pressed during finalization.
+pub(crate) fn queue_start(args: &[String]) -> Result<()> {
+    fs::create_dir_all(STATE_DIR).context("failed to create state directory")?;
+    fs::write(QUEUED_START_FILE, args.join("\n")).context("failed to queue start")
+}
+
+/// Takes the queued start, if any, leaving none behind.
+pub(crate) fn take_queued_start() -> Option<Vec<String>> {
+    let content = fs::read_to_string(QUEUED_START_FILE).ok()?;
+    fs::remove_file(QUEUED_START_FILE).ok()?;
+    Some(content.lines().map(str::to_string).collect())
+}
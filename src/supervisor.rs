@@ -0,0 +1,291 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::geometry;
+use crate::platform;
+use crate::recorder;
+use crate::signals;
+use crate::spotlight;
+use crate::state::{self, RecordingState};
+use crate::tray;
+use crate::{Config, IdleAction, Monitor, Rect};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+/// Time a stop or pause gets to clear the state after the encoder exits
+/// before the exit counts as a crash.
+const EXIT_GRACE: Duration = Duration::from_secs(2);
+const SPOTLIGHT_INTERVAL: Duration = Duration::from_millis(66);
+/// Size of the floating stop button, in pixels.
+const STOP_BUTTON_SIZE: (i32, i32) = (96, 40);
+
+/// Launches `supervise <id>` as a detached background process that outlives
+/// the hotkey invocation which started the recording.
+pub(crate) fn spawn(id: u32) -> Result<()> {
+    let exe = env::current_exe().context("failed to locate recorder binary")?;
+    Command::new(exe)
+        .args(["supervise", &id.to_string()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to start recording supervisor")?;
+    Ok(())
+}
+
+/// Watches one recording until it ends: reports an encoder that dies
+/// mid-recording, stops cleanly when a monitor under it is unplugged or
+/// changes mode, keeps the spotlight under the pointer, logs mouse clicks
+/// for the click sounds and applies the idle policy whenever the desktop has
+/// seen no input for the configured time. A shutdown signal, such as the
+/// session ending, stops and finalizes the recording instead of leaving the
+/// encoder orphaned.
+pub(crate) fn run(id: u32, config: &Config) -> Result<()> {
+    signals::install();
+    let mut click_listener = None;
+    if config.post.click_sounds {
+        match platform::listen_for_clicks(move || log_click(id)) {
+            Ok(listener) => click_listener = Some(listener),
+            Err(error) => platform::show_notification(
+                "Click sounds unavailable",
+                &format!("{:#}", error),
+                2000,
+            ),
+        }
+    }
+    let mut stop_button = None;
+    if config.record.stop_button {
+        match show_stop_button(id) {
+            Ok(button) => stop_button = button,
+            Err(error) => platform::show_notification(
+                "Stop button unavailable",
+                &format!("{:#}", error),
+                2000,
+            ),
+        }
+    }
+    let result = watch(id, config, &mut stop_button);
+    for mut child in click_listener.into_iter().chain(stop_button) {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    result
+}
+
+fn show_stop_button(id: u32) -> Result<Option<Child>> {
+    let Some(recording) = find(id) else {
+        return Ok(None);
+    };
+    let screen = platform::full_screen_bounds()?.bounds();
+    let (x, y) = stop_button_position(&recording.rect, &screen);
+    platform::show_stop_button(x, y, STOP_BUTTON_SIZE).map(Some)
+}
+
+/// Top-left corner for the stop button: just outside the recorded area so
+/// it stays out of the footage, trying below, above, right and left in
+/// turn, and inside the bottom-right corner when the area fills the screen.
+fn stop_button_position(area: &Rect, screen: &Rect) -> (i32, i32) {
+    let (w, h) = STOP_BUTTON_SIZE;
+    let right_aligned = (area.x + area.w - w).max(screen.x);
+    let bottom_aligned = (area.y + area.h - h).max(screen.y);
+    if area.y + area.h + h <= screen.y + screen.h {
+        (right_aligned, area.y + area.h)
+    } else if area.y - h >= screen.y {
+        (right_aligned, area.y - h)
+    } else if area.x + area.w + w <= screen.x + screen.w {
+        (area.x + area.w, bottom_aligned)
+    } else if area.x - w >= screen.x {
+        (area.x - w, bottom_aligned)
+    } else {
+        (right_aligned, bottom_aligned)
+    }
+}
+
+fn watch(id: u32, config: &Config, stop_button: &mut Option<Child>) -> Result<()> {
+    let tick = if config.spotlight.enabled {
+        SPOTLIGHT_INTERVAL
+    } else if config.record.watchdog || stop_button.is_some() || recording_follows_workspace(id) {
+        WATCHDOG_INTERVAL
+    } else {
+        POLL_INTERVAL
+    };
+    let mut recording = find(id);
+    // Layout the capture was set up for; unplugging or resizing a monitor
+    // under it leaves x11grab reading outside the screen.
+    let monitors = config
+        .record
+        .watchdog
+        .then(|| platform::current_monitors().ok())
+        .flatten();
+    let mut last_poll = Instant::now();
+    let mut last_pointer = None;
+    let mut last_pid = None;
+    while let Some(current) = &recording {
+        thread::sleep(tick);
+        if signals::requested() || stop_clicked(stop_button) {
+            if let Some(current) = find(id) {
+                recorder::stop(&current, config)?;
+                tray::publish_status(&recorder::status_report());
+            }
+            return Ok(());
+        }
+        if let Some(change) = monitors
+            .as_deref()
+            .and_then(|before| layout_changed(before, current))
+        {
+            platform::show_notification(
+                "Monitor layout changed",
+                &format!("{}; stopping the recording", change),
+                4000,
+            );
+            // The cached state can be up to a poll old; stop what is on disk
+            // so recent marks, pauses and segments are not lost.
+            if let Some(current) = find(id) {
+                recorder::stop(&current, config)?;
+            }
+            tray::publish_status(&recorder::status_report());
+            return Ok(());
+        }
+        if config.record.watchdog && crashed(current) {
+            return Ok(());
+        }
+        if current.workspace.is_some() && sync_workspace(id, config)? {
+            recording = find(id);
+            continue;
+        }
+        if config.spotlight.enabled && !current.paused {
+            // A resumed segment starts a fresh overlay at its default spot.
+            if last_pid != Some(current.pid) {
+                last_pid = Some(current.pid);
+                last_pointer = None;
+            }
+            if let Ok(pointer) = platform::pointer_position() {
+                if last_pointer != Some(pointer) && spotlight::follow(current, pointer).is_ok() {
+                    last_pointer = Some(pointer);
+                }
+            }
+        }
+        if last_poll.elapsed() < POLL_INTERVAL {
+            continue;
+        }
+        last_poll = Instant::now();
+        recording = find(id);
+        if let Some(current) = &recording {
+            apply_idle_policy(current, config)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the stop button was pressed. A button closed any other way is
+/// forgotten and recording carries on.
+fn stop_clicked(stop_button: &mut Option<Child>) -> bool {
+    let Some(button) = stop_button else {
+        return false;
+    };
+    match button.try_wait() {
+        Ok(Some(status)) => {
+            *stop_button = None;
+            status.success()
+        }
+        Ok(None) => false,
+        Err(_) => {
+            *stop_button = None;
+            false
+        }
+    }
+}
+
+/// Whether the encoder of `recording` died on its own. The recording is
+/// abandoned, with a notification, when it did. A recording that is being
+/// finalized was stopped on purpose and is left to the finalizer.
+fn crashed(recording: &RecordingState) -> bool {
+    if recording.paused || platform::process_alive(recording.pid) {
+        return false;
+    }
+    thread::sleep(EXIT_GRACE);
+    match state::load(recording.id) {
+        Some(current)
+            if current.pid == recording.pid
+                && !current.paused
+                && !platform::process_alive(current.pid)
+                && !state::finalizing_recording(current.id) =>
+        {
+            recorder::abandon(&current);
+            tray::publish_status(&recorder::status_report());
+            true
+        }
+        _ => false,
+    }
+}
+
+/// How the monitors under `recording` changed since `before`. A layout that
+/// cannot be read right now counts as unchanged.
+fn layout_changed(before: &[Monitor], recording: &RecordingState) -> Option<String> {
+    let after = platform::current_monitors().ok()?;
+    geometry::layout_change(before, &after, recording.rect)
+}
+
+/// Clicks while paused are not part of the footage and are dropped.
+fn log_click(id: u32) {
+    if let Some(recording) = state::load(id).filter(|recording| !recording.paused) {
+        let _ = state::append_click(id, recorder::footage_offset_precise(&recording));
+    }
+}
+
+/// Pauses or resumes with the visibility of the recorded workspace. Returns
+/// whether the recording changed.
+fn sync_workspace(id: u32, config: &Config) -> Result<bool> {
+    let Some(recording) = find(id) else {
+        return Ok(false);
+    };
+    let Some(workspace) = &recording.workspace else {
+        return Ok(false);
+    };
+    let Ok(visible) = platform::workspace_visible(workspace) else {
+        return Ok(false);
+    };
+    recorder::follow_workspace(&recording, visible, config)
+}
+
+fn recording_follows_workspace(id: u32) -> bool {
+    state::load(id).is_some_and(|recording| recording.workspace.is_some())
+}
+
+fn find(id: u32) -> Option<RecordingState> {
+    recorder::active_recordings()
+        .into_iter()
+        .find(|recording| recording.id == id)
+}
+
+fn apply_idle_policy(recording: &RecordingState, config: &Config) -> Result<()> {
+    let idle_limit_secs = config.record.idle_timeout_mins * 60;
+    if recording.paused || idle_limit_secs == 0 {
+        return Ok(());
+    }
+    if platform::idle_secs()? < idle_limit_secs {
+        return Ok(());
+    }
+
+    platform::show_notification(
+        "No activity",
+        &format!(
+            "Idle for {} minutes, {} the recording",
+            config.record.idle_timeout_mins,
+            match config.record.idle_action {
+                IdleAction::Stop => "stopping",
+                IdleAction::Pause => "pausing",
+            }
+        ),
+        3000,
+    );
+    match config.record.idle_action {
+        IdleAction::Stop => recorder::stop(recording, config)?,
+        IdleAction::Pause => recorder::toggle_pause(recording, config)?,
+    }
+    tray::publish_status(&recorder::status_report());
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use std::env;
+use std::process::{Command, Stdio};
+
+use crate::recorder::StatusReport;
+use crate::Config;
+
+/// Where qol-tray listens unless told otherwise.
+pub(crate) const DEFAULT_TRAY_URL: &str = "http://127.0.0.1:42700";
+/// Set by the tray host for the plugins it launches, and by `tray.url`.
+pub(crate) const TRAY_URL_VAR: &str = "QOL_TRAY_URL";
+const PLUGIN_ID: &str = "plugin-screen-recorder";
+/// Sections a settings reply must carry to be taken as a full config;
+/// every field has a default, so anything less would parse as one.
+const REQUIRED_SECTIONS: &[&str] = &["record", "video", "audio", "output"];
+
+/// Base URL of the running qol-tray, without a trailing slash.
+fn tray_url() -> String {
+    resolve_tray_url(env::var(TRAY_URL_VAR).ok().as_deref())
+}
+
+fn resolve_tray_url(configured: Option<&str>) -> String {
+    configured
+        .map(|url| url.trim().trim_end_matches('/'))
+        .filter(|url| !url.is_empty())
+        .unwrap_or(DEFAULT_TRAY_URL)
+        .to_string()
+}
+
+/// Points every later request at `url`, unless the tray host already said
+/// where it is.
+pub(crate) fn use_tray_url(url: &str) {
+    if url.trim().is_empty() || env::var_os(TRAY_URL_VAR).is_some() {
+        return;
+    }
+    env::set_var(TRAY_URL_VAR, url.trim());
+}
+
+pub(crate) fn settings_url() -> String {
+    format!("{}/plugins/{}/", tray_url(), PLUGIN_ID)
+}
+
+fn config_url() -> String {
+    format!("{}/api/plugins/{}/config", tray_url(), PLUGIN_ID)
+}
+
+fn state_url() -> String {
+    format!("{}/api/plugins/{}/state", tray_url(), PLUGIN_ID)
+}
+
+/// Pushes the recorder summary to the tray host so it can badge its icon.
+/// Delivery is best effort; a tray that is not running is not an error.
+pub(crate) fn publish_status(report: &StatusReport) {
+    let Ok(body) = serde_json::to_string(report) else {
+        return;
+    };
+    let _ = Command::new("curl")
+        .args(["--silent", "--fail", "--max-time", "1", "-X", "POST"])
+        .args(["-H", "Content-Type: application/json", "--data-binary"])
+        .arg(body)
+        .arg(state_url())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Pulls the plugin settings from the running qol-tray so edits made in the
+/// web UI apply without waiting for the config file to sync.
+pub(crate) fn fetch_config() -> Result<Config> {
+    let output = Command::new("curl")
+        .args(["--silent", "--fail", "--max-time", "1"])
+        .arg(config_url())
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run curl")?;
+    if !output.status.success() {
+        return Err(anyhow!("qol-tray settings endpoint unavailable"));
+    }
+    let reply: Value =
+        serde_json::from_slice(&output.stdout).context("invalid settings from qol-tray")?;
+    let missing = missing_sections(&reply);
+    if !missing.is_empty() {
+        return Err(anyhow!("qol-tray settings lack {}", missing.join(", ")));
+    }
+    serde_json::from_value(reply).context("invalid settings from qol-tray")
+}
+
+fn missing_sections(reply: &Value) -> Vec<&'static str> {
+    REQUIRED_SECTIONS
+        .iter()
+        .copied()
+        .filter(|section| !reply.get(*section).is_some_and(Value::is_object))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{missing_sections, resolve_tray_url, DEFAULT_TRAY_URL};
+    use serde_json::{Map, Value};
+
+    #[test]
+    fn tray_url_falls_back_to_the_default_port() {
+        assert_eq!(resolve_tray_url(None), DEFAULT_TRAY_URL);
+        assert_eq!(resolve_tray_url(Some("  ")), DEFAULT_TRAY_URL);
+        assert_eq!(
+            resolve_tray_url(Some("http://127.0.0.1:42711/")),
+            "http://127.0.0.1:42711"
+        );
+    }
+
+    #[test]
+    fn settings_replies_need_every_section() {
+        assert_eq!(
+            missing_sections(&Value::Object(Map::new())),
+            vec!["record", "video", "audio", "output"]
+        );
+        let mut full = Map::new();
+        for section in ["record", "video", "audio", "output"] {
+            full.insert(section.to_string(), Value::Object(Map::new()));
+        }
+        full.insert("video".to_string(), Value::from("wrapped"));
+        assert_eq!(missing_sections(&Value::Object(full)), vec!["video"]);
+    }
+}
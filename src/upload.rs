@@ -0,0 +1,281 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::failure::spawn_error;
+use crate::platform;
+use crate::state::STATE_DIR;
+use crate::{hooks, HooksConfig, UploadConfig};
+
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(5);
+/// Percent steps between progress notifications for large files.
+const PROGRESS_STEP: f64 = 25.0;
+/// curl exit codes worth another attempt: resolve, connect, timeout, TLS
+/// handshake and connection-drop failures.
+const TRANSIENT_CURL_CODES: &[i32] = &[6, 7, 28, 35, 52, 55, 56];
+
+fn queue_dir() -> PathBuf {
+    Path::new(STATE_DIR).join("uploads")
+}
+
+fn lock_path() -> PathBuf {
+    queue_dir().join("worker.lock")
+}
+
+/// Queues `file` for upload and makes sure a worker is draining the queue.
+/// The worker is detached, so uploads finish while new recordings start.
+pub(crate) fn enqueue(file: &Path) -> Result<()> {
+    let dir = queue_dir();
+    fs::create_dir_all(&dir).context("failed to create upload queue")?;
+    write_job(&dir, file).context("failed to queue upload")?;
+    let exe = env::current_exe().context("failed to locate recorder binary")?;
+    Command::new(exe)
+        .arg("upload-worker")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to start upload worker")?;
+    Ok(())
+}
+
+/// Writes a job named by the time it was queued, so the queue drains in
+/// order. The pid and a per-process count keep two recordings finished in
+/// the same millisecond apart; the job is renamed into place whole so a
+/// worker never reads it half written.
+fn write_job(dir: &Path, file: &Path) -> std::io::Result<()> {
+    static QUEUED: AtomicU32 = AtomicU32::new(0);
+    let name = format!(
+        "{}-{}-{}",
+        Local::now().timestamp_millis(),
+        process::id(),
+        QUEUED.fetch_add(1, Ordering::Relaxed)
+    );
+    let partial = dir.join(format!("{}.tmp", name));
+    fs::write(&partial, file.to_string_lossy().as_bytes())?;
+    fs::rename(&partial, dir.join(format!("{}.job", name)))
+}
+
+/// Uploads queued files oldest first until the queue is empty. Returns at
+/// once when another worker already holds the queue.
+pub(crate) fn run_worker(config: &UploadConfig, hooks: &HooksConfig) -> Result<()> {
+    if !acquire_lock()? {
+        return Ok(());
+    }
+    let result = drain(config, hooks);
+    let _ = fs::remove_file(lock_path());
+    result
+}
+
+fn drain(config: &UploadConfig, hooks: &HooksConfig) -> Result<()> {
+    while let Some(job) = next_job() {
+        let file = PathBuf::from(fs::read_to_string(&job).unwrap_or_default().trim());
+        let _ = fs::remove_file(&job);
+        if !file.is_file() {
+            continue;
+        }
+        match upload_with_retry(&file, config) {
+            Ok(()) => {
+                platform::show_notification(
+                    "Upload finished",
+                    &format!("{} uploaded", display_name(&file)),
+                    2000,
+                );
+                hooks::announce(hooks, &uploaded_url(&file, &config.url));
+            }
+            Err(error) => platform::show_notification(
+                "Upload failed",
+                &format!("{}: {:#}", display_name(&file), error),
+                6000,
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn next_job() -> Option<PathBuf> {
+    let mut jobs: Vec<PathBuf> = fs::read_dir(queue_dir())
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "job"))
+        .collect();
+    jobs.sort();
+    jobs.into_iter().next()
+}
+
+/// Takes the worker lock, replacing one left behind by a dead worker. The
+/// lock is linked into place with the pid already in it, so another worker
+/// never finds it empty and mistakes it for a stale one.
+fn acquire_lock() -> Result<bool> {
+    fs::create_dir_all(queue_dir()).context("failed to create upload queue")?;
+    let claim = queue_dir().join(format!("worker.lock.{}", process::id()));
+    fs::write(&claim, process::id().to_string()).context("failed to write upload lock")?;
+    let result = claim_lock(&claim);
+    let _ = fs::remove_file(&claim);
+    result
+}
+
+fn claim_lock(claim: &Path) -> Result<bool> {
+    for _ in 0..2 {
+        match fs::hard_link(claim, lock_path()) {
+            Ok(()) => return Ok(true),
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+                let holder = fs::read_to_string(lock_path())
+                    .ok()
+                    .and_then(|pid| pid.trim().parse::<u32>().ok());
+                if holder.is_some_and(platform::process_alive) {
+                    return Ok(false);
+                }
+                let _ = fs::remove_file(lock_path());
+            }
+            Err(error) => return Err(error).context("failed to take upload lock"),
+        }
+    }
+    Ok(false)
+}
+
+enum Attempt {
+    Done,
+    Transient(String),
+    Fatal(String),
+}
+
+fn upload_with_retry(file: &Path, config: &UploadConfig) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        match upload_once(file, config)? {
+            Attempt::Done => return Ok(()),
+            Attempt::Fatal(reason) => return Err(anyhow!(reason)),
+            Attempt::Transient(reason) if attempt >= config.retries => {
+                return Err(anyhow!("{} (gave up after {} retries)", reason, attempt))
+            }
+            Attempt::Transient(reason) => {
+                let delay = RETRY_BASE_DELAY * 2u32.pow(attempt);
+                attempt += 1;
+                platform::show_notification(
+                    "Upload interrupted",
+                    &format!(
+                        "{}; retrying in {}s ({}/{})",
+                        reason,
+                        delay.as_secs(),
+                        attempt,
+                        config.retries
+                    ),
+                    2000,
+                );
+                thread::sleep(delay);
+            }
+        }
+    }
+}
+
+/// One `curl --upload-file` run. A URL ending in `/` gets the file name
+/// appended by curl itself.
+fn upload_once(file: &Path, config: &UploadConfig) -> Result<Attempt> {
+    let size_mb = fs::metadata(file).map(|meta| meta.len()).unwrap_or(0) / 1_000_000;
+    let show_progress = size_mb >= config.progress_min_mb;
+    let mut child = Command::new("curl")
+        .args(["--progress-bar", "--write-out", "\n%{http_code}"])
+        .arg("--upload-file")
+        .arg(file)
+        .arg(&config.url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| spawn_error("curl", error))?;
+
+    let mut stderr = child.stderr.take().context("curl stderr unavailable")?;
+    let name = display_name(file);
+    let progress = thread::spawn(move || {
+        let mut buffer = [0u8; 512];
+        let mut line = String::new();
+        let mut next_report = PROGRESS_STEP;
+        let mut tail = String::new();
+        while let Ok(read) = stderr.read(&mut buffer) {
+            if read == 0 {
+                break;
+            }
+            for ch in String::from_utf8_lossy(&buffer[..read]).chars() {
+                if ch != '\r' && ch != '\n' {
+                    line.push(ch);
+                    continue;
+                }
+                let done = std::mem::take(&mut line);
+                match parse_percent(&done) {
+                    Some(percent) if show_progress && percent >= next_report && percent < 100.0 => {
+                        platform::show_status(
+                            "Uploading",
+                            &format!("{}: {:.0}%", name, percent),
+                            1500,
+                        );
+                        next_report =
+                            (percent / PROGRESS_STEP).floor() * PROGRESS_STEP + PROGRESS_STEP;
+                    }
+                    Some(_) => {}
+                    None if !done.trim().is_empty() => tail = done,
+                    None => {}
+                }
+            }
+        }
+        tail
+    });
+
+    let mut stdout = String::new();
+    if let Some(mut out) = child.stdout.take() {
+        let _ = out.read_to_string(&mut stdout);
+    }
+    let status = child.wait().context("failed to wait for curl")?;
+    let message = progress.join().unwrap_or_default();
+    let http_code: u16 = stdout
+        .lines()
+        .last()
+        .and_then(|code| code.trim().parse().ok())
+        .unwrap_or(0);
+
+    Ok(match status.code() {
+        Some(0) if (200..300).contains(&http_code) => Attempt::Done,
+        Some(0) if http_code >= 500 || http_code == 429 => {
+            Attempt::Transient(format!("server answered {}", http_code))
+        }
+        Some(0) => Attempt::Fatal(format!("server answered {}", http_code)),
+        Some(code) if TRANSIENT_CURL_CODES.contains(&code) => {
+            Attempt::Transient(format!("curl exit {}: {}", code, message.trim()))
+        }
+        code => Attempt::Fatal(format!(
+            "curl failed ({}): {}",
+            code.map_or("signal".to_string(), |code| code.to_string()),
+            message.trim()
+        )),
+    })
+}
+
+/// Where `file` ends up: `url` itself, or with the file name appended when
+/// it ends in `/`, as curl does.
+fn uploaded_url(file: &Path, url: &str) -> String {
+    if url.ends_with('/') {
+        format!("{}{}", url, hooks::percent_encode(&display_name(file)))
+    } else {
+        url.to_string()
+    }
+}
+
+/// Reads the percentage off a curl progress bar line like `#####   42.7%`.
+fn parse_percent(line: &str) -> Option<f64> {
+    let value = line.trim().rsplit(' ').next()?.strip_suffix('%')?;
+    value.parse().ok()
+}
+
+fn display_name(file: &Path) -> String {
+    file.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| file.display().to_string())
+}
@@ -0,0 +1,93 @@
+use anyhow::{anyhow, Result};
+use std::thread;
+use std::time::Duration;
+
+use crate::platform;
+use crate::state::{self, RecordingState};
+use crate::{Rect, ZoomConfig};
+
+/// Instance name of the crop filter the zoom commands address.
+pub(crate) const CROP_FILTER: &str = "zoom";
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+/// Smallest view, in pixels, a zoom may narrow down to.
+const MIN_VIEW_PX: i32 = 64;
+
+/// Zooms further in by the configured factor, centered on the pointer.
+pub(crate) fn zoom_in(recording: &RecordingState, zoom: &ZoomConfig) -> Result<()> {
+    let full = full_view(recording);
+    let current = recording.zoom.unwrap_or(full);
+    let w = even((current.w as f64 / zoom.factor) as i32)
+        .max(MIN_VIEW_PX)
+        .min(full.w);
+    let h = even((w as f64 * full.h as f64 / full.w as f64) as i32)
+        .max(MIN_VIEW_PX)
+        .min(full.h);
+    let (pointer_x, pointer_y) = platform::pointer_position()?;
+    let center_x = pointer_x - recording.rect.x;
+    let center_y = pointer_y - recording.rect.y;
+    let target = Rect {
+        x: (center_x - w / 2).clamp(0, full.w - w),
+        y: (center_y - h / 2).clamp(0, full.h - h),
+        w,
+        h,
+    };
+    animate(recording, current, target, zoom)
+}
+
+pub(crate) fn zoom_out(recording: &RecordingState, zoom: &ZoomConfig) -> Result<()> {
+    let full = full_view(recording);
+    let current = recording.zoom.unwrap_or(full);
+    animate(recording, current, full, zoom)
+}
+
+fn animate(recording: &RecordingState, from: Rect, to: Rect, zoom: &ZoomConfig) -> Result<()> {
+    if recording.paused {
+        return Err(anyhow!("cannot zoom while the recording is paused"));
+    }
+    let port = platform::filter_control_port(recording.id);
+    let steps = (zoom.duration_ms / FRAME_INTERVAL.as_millis() as u64).max(1);
+    for step in 1..=steps {
+        let view = interpolate(from, to, ease(step as f64 / steps as f64));
+        for (key, value) in [("w", view.w), ("h", view.h), ("x", view.x), ("y", view.y)] {
+            platform::send_filter_command(
+                port,
+                &format!("crop@{} {} {}", CROP_FILTER, key, value),
+            )?;
+        }
+        if step < steps {
+            thread::sleep(FRAME_INTERVAL);
+        }
+    }
+
+    let mut recording = recording.clone();
+    recording.zoom = (to != full_view(&recording)).then_some(to);
+    state::save(&recording)
+}
+
+fn full_view(recording: &RecordingState) -> Rect {
+    Rect {
+        x: 0,
+        y: 0,
+        w: recording.rect.w,
+        h: recording.rect.h,
+    }
+}
+
+fn interpolate(from: Rect, to: Rect, t: f64) -> Rect {
+    let lerp = |a: i32, b: i32| a + ((b - a) as f64 * t).round() as i32;
+    Rect {
+        x: lerp(from.x, to.x),
+        y: lerp(from.y, to.y),
+        w: even(lerp(from.w, to.w)),
+        h: even(lerp(from.h, to.h)),
+    }
+}
+
+/// Smoothstep easing, so zooms accelerate and settle instead of jumping.
+fn ease(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn even(value: i32) -> i32 {
+    value - value % 2
+}
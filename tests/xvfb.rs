@@ -0,0 +1,257 @@
+//! End-to-end recordings against a throwaway Xvfb server and PulseAudio
+//! daemon, checked with ffprobe. They guard the ffmpeg command line against
+//! regressions that only show up in a real capture.
+//!
+//! Needs Xvfb, pulseaudio, pactl, ffmpeg and ffprobe on the PATH:
+//!
+//! ```sh
+//! cargo test --features xvfb-tests --test xvfb
+//! ```
+#![cfg(feature = "xvfb-tests")]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Recordings share the recorder's state directory, so they run one at a
+/// time.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An X server and a sound server with a null sink, both torn down on drop
+/// together with the scratch home the recorder reads its config from.
+struct Session {
+    display: String,
+    root: PathBuf,
+    xvfb: Child,
+    pulse: Child,
+}
+
+impl Session {
+    fn start(config: &str) -> Session {
+        let root = env::temp_dir().join(format!("screen-recorder-xvfb-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let config_dir = root.join("config/qol-tray/plugins/plugin-screen-recorder");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::create_dir_all(root.join("runtime")).expect("create runtime dir");
+        fs::write(config_dir.join("config.json"), config).expect("write config");
+
+        let number = free_display();
+        let display = format!(":{}", number);
+        let xvfb = Command::new("Xvfb")
+            .args([
+                display.as_str(),
+                "-screen",
+                "0",
+                "1280x720x24",
+                "-nolisten",
+                "tcp",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Xvfb is not installed");
+        let socket = PathBuf::from(format!("/tmp/.X11-unix/X{}", number));
+        wait_until("Xvfb", || socket.exists());
+
+        let pulse = Command::new("pulseaudio")
+            .args([
+                "--daemonize=no",
+                "--system=no",
+                "--use-pid-file=no",
+                "--exit-idle-time=-1",
+                "--disable-shm",
+                "-n",
+                "-L",
+                "module-native-protocol-unix",
+                "-L",
+                "module-null-sink sink_name=dummy",
+            ])
+            .env("XDG_RUNTIME_DIR", root.join("runtime"))
+            .env("HOME", &root)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("pulseaudio is not installed");
+        let session = Session {
+            display,
+            root,
+            xvfb,
+            pulse,
+        };
+        wait_until("pulseaudio", || {
+            session
+                .command("pactl")
+                .arg("info")
+                .output()
+                .is_ok_and(|output| output.status.success())
+        });
+        session
+    }
+
+    /// `program` with the session's display, sound server and home.
+    fn command(&self, program: impl AsRef<std::ffi::OsStr>) -> Command {
+        let mut command = Command::new(program);
+        command
+            .env("DISPLAY", &self.display)
+            .env("HOME", &self.root)
+            .env("XDG_CONFIG_HOME", self.root.join("config"))
+            .env("XDG_RUNTIME_DIR", self.root.join("runtime"))
+            .stdin(Stdio::null());
+        command
+    }
+
+    /// Records `geometry` for `secs` seconds with `headless` into `name`.
+    fn record(&self, geometry: &str, secs: u32, name: &str) -> PathBuf {
+        let output = self.root.join(name);
+        let result = self
+            .command(env!("CARGO_BIN_EXE_screen-recorder"))
+            .args([
+                "headless",
+                "--display",
+                &self.display,
+                "--geometry",
+                geometry,
+            ])
+            .args(["--duration", &secs.to_string(), "--output"])
+            .arg(&output)
+            .output()
+            .expect("run screen-recorder");
+        assert!(
+            result.status.success(),
+            "headless recording failed: {}",
+            String::from_utf8_lossy(&result.stderr)
+        );
+        output
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        for child in [&mut self.pulse, &mut self.xvfb] {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn free_display() -> u32 {
+    (90..200)
+        .find(|number| {
+            !Path::new(&format!("/tmp/.X11-unix/X{}", number)).exists()
+                && !Path::new(&format!("/tmp/.X{}-lock", number)).exists()
+        })
+        .expect("no free X display number")
+}
+
+fn wait_until(what: &str, ready: impl Fn() -> bool) {
+    let started = Instant::now();
+    while !ready() {
+        assert!(
+            started.elapsed() < STARTUP_TIMEOUT,
+            "{} did not come up",
+            what
+        );
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// What ffprobe reports about a recording.
+struct Probe {
+    video: Vec<(u32, u32)>,
+    audio_streams: usize,
+    duration_secs: f64,
+}
+
+fn probe(file: &Path) -> Probe {
+    let Output { status, stdout, .. } = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries"])
+        .arg("stream=codec_type,width,height:format=duration")
+        .args(["-of", "default=noprint_wrappers=1"])
+        .arg(file)
+        .output()
+        .expect("ffprobe is not installed");
+    assert!(
+        status.success(),
+        "ffprobe could not read {}",
+        file.display()
+    );
+    let mut probe = Probe {
+        video: Vec::new(),
+        audio_streams: 0,
+        duration_secs: 0.0,
+    };
+    let mut width = 0;
+    for line in String::from_utf8_lossy(&stdout).lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match (key, value) {
+            ("codec_type", "audio") => probe.audio_streams += 1,
+            ("width", value) => width = value.parse().unwrap_or(0),
+            ("height", value) => {
+                if let Ok(height) = value.parse() {
+                    probe.video.push((width, height));
+                }
+            }
+            ("duration", value) => probe.duration_secs = value.parse().unwrap_or(0.0),
+            _ => {}
+        }
+    }
+    probe
+}
+
+fn assert_duration(probe: &Probe, secs: f64) {
+    assert!(
+        (probe.duration_secs - secs).abs() < 1.0,
+        "expected about {}s, got {}s",
+        secs,
+        probe.duration_secs
+    );
+}
+
+#[test]
+fn video_only_recording_has_the_requested_size_and_length() {
+    let _serial = SERIAL
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let session = Session::start(r#"{ "audio": { "enabled": false } }"#);
+    let file = session.record("640x480+0+0", 3, "video.mkv");
+    let probe = probe(&file);
+    assert_eq!(probe.video, [(640, 480)]);
+    assert_eq!(probe.audio_streams, 0);
+    assert_duration(&probe, 3.0);
+}
+
+#[test]
+fn odd_sizes_are_trimmed_to_even() {
+    let _serial = SERIAL
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let session = Session::start(r#"{ "audio": { "enabled": false } }"#);
+    let file = session.record("641x361+11+7", 2, "odd.mp4");
+    let probe = probe(&file);
+    assert_eq!(probe.video, [(640, 360)]);
+    assert_duration(&probe, 2.0);
+}
+
+#[test]
+fn system_audio_adds_one_audio_stream() {
+    let _serial = SERIAL
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let session = Session::start(
+        r#"{ "audio": { "enabled": true, "inputs": ["system"], "bluetooth_mic": "allow" } }"#,
+    );
+    let file = session.record("320x240+0+0", 3, "audio.mkv");
+    let probe = probe(&file);
+    assert_eq!(probe.video, [(320, 240)]);
+    assert_eq!(probe.audio_streams, 1);
+    assert_duration(&probe, 3.0);
+}